@@ -0,0 +1,80 @@
+// heap allocations of the render once the workers are running, run with:
+// cargo bench --bench allocations
+// the workers reuse their buffers and rng pools from one chunk to the next, the
+// count by pass stays flat with the number of threads
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+use raytracer::raytracer::{BenchScene, ChunkStrategy, Extent};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static BYTES: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const PASSES: u32 = 16;
+
+fn main() {
+    let extent = Extent::new(640, 360);
+
+    for threads in [1, 4, 16] {
+        let builder = pollster::block_on(BenchScene::SPHEREFIELD.builder(extent));
+        let mut tracer = pollster::block_on(
+            builder
+                .threads(threads)
+                .strategy(ChunkStrategy::LINE { pixels: 4096 })
+                .rays(1)
+                .target_samples(PASSES)
+                .build(),
+        );
+
+        // the first pass sizes the buffers
+        while tracer.samples_per_pixel() < 1 {
+            tracer.update();
+        }
+
+        let start = Instant::now();
+        let (allocations, bytes) = (
+            ALLOCATIONS.load(Ordering::Relaxed),
+            BYTES.load(Ordering::Relaxed),
+        );
+        let passes = PASSES - tracer.samples_per_pixel();
+
+        while !tracer.is_complete() {
+            tracer.update();
+        }
+
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+        let bytes = BYTES.load(Ordering::Relaxed) - bytes;
+        let chunks = passes as u64 * extent.width as u64 * extent.height as u64 / 4096;
+
+        println!(
+            "{} threads: {} allocations ({:.1} by chunk), {:.1} MB by pass, {:.2}s",
+            threads,
+            allocations,
+            allocations as f32 / chunks as f32,
+            bytes as f32 / passes as f32 / 1e6,
+            start.elapsed().as_secs_f32()
+        );
+    }
+}
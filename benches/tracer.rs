@@ -0,0 +1,47 @@
+// rays per second of the bench scenes, run with:
+// cargo bench --bench tracer [-- <scene>...]
+// the scenes are SPHEREFIELD, CORNELLBOX and RANDOMSPHERES, all of them by default
+
+use std::{env, thread};
+
+use raytracer::raytracer::{Bench, BenchScene};
+
+fn main() {
+    // cargo bench adds --bench to the arguments
+    let names = env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .map(|arg| arg.to_uppercase())
+        .collect::<Vec<_>>();
+
+    let scenes = BenchScene::ALL
+        .into_iter()
+        .filter(|scene| names.is_empty() || names.contains(&format!("{:?}", scene)));
+
+    let max_threads = thread::available_parallelism().map_or(1, |n| n.get() as u32);
+    let mut threads = [1, 2, 4, max_threads]
+        .into_iter()
+        .filter(|&n| n <= max_threads)
+        .collect::<Vec<_>>();
+    threads.dedup();
+
+    let bench = Bench::new();
+
+    for scene in scenes {
+        let results = pollster::block_on(bench.thread_scaling(scene, &threads));
+        for result in results {
+            println!("{}", result);
+        }
+
+        let strategies = Bench::default_strategies();
+        let results = pollster::block_on(
+            bench
+                .clone()
+                .threads(max_threads)
+                .chunk_strategies(scene, &strategies),
+        );
+        for result in results {
+            println!("{}", result);
+        }
+    }
+}
@@ -1,5 +1,10 @@
+// enum variants are uppercase and constructors of models return them ready for
+// the scene, e.g. Arc<dyn Hitable>
+#![allow(clippy::upper_case_acronyms, clippy::new_ret_no_self)]
+
 use env_logger::Builder;
 
+pub mod prelude;
 pub mod raytracer;
 
 pub fn init_logger() {
@@ -0,0 +1,6 @@
+pub use crate::raytracer::{
+    Backend, BlendMode, Camera, CheckerTexture, ChunkStrategy, Color, Decal, ExportOptions, Extent,
+    Hit, Hitable, ImageTexture, Integrator, LightPower, LodMesh, MarbleTexture, Material, Mesh,
+    NoiseTexture, Outline, PointLight, Ray, SamplerKind, Sphere, Svo, Texture, Tonemap, Tonemapper,
+    Tracer, TracerBuilder, TracerConfig, VoxelGrid, WrapMode,
+};
@@ -1,11 +1,132 @@
+mod aabb;
+mod accel;
+mod animation;
+mod aov;
+mod backend;
+mod background;
+mod bake;
+mod bench;
+mod blue_noise;
 mod buffer;
+mod bvh;
+mod camera;
+mod checkpoint;
+mod color;
+mod config;
+mod debug;
+mod decal;
+#[cfg(feature = "oidn")]
+mod denoise;
+mod distributed;
+mod downsample;
+mod export;
+mod extent;
+mod filter;
+#[cfg(feature = "gpu")]
+mod gpu;
+mod group;
 mod hit;
+mod integrator;
+mod kdtree;
+mod light;
+mod material;
+mod mesh;
+mod normalize;
+mod pacing;
+mod pass;
+mod payload;
+mod perlin;
+mod portal;
+mod post;
+mod primitive;
+mod probe;
+mod queue;
 mod ray;
+mod region;
+mod replay;
+mod rng;
+mod sampler;
+mod sampling;
+pub mod scene;
+mod scene_diff;
+mod scene_file;
+mod snapshot;
 mod sphere;
+mod sphere_set;
+mod stats;
+mod status;
+mod svo;
+mod texture;
+mod timer;
+mod timing;
 mod tracer;
+mod validation;
+#[cfg(feature = "video")]
+mod video;
+mod volume;
+mod watcher;
 
-pub use buffer::ChunkStrategy;
-pub use hit::{Hit, Hitable};
+pub use aabb::Aabb;
+pub use accel::AccelKind;
+pub use animation::Animation;
+pub use backend::Backend;
+pub use background::{Background, Gradient};
+pub use bake::Lightmap;
+pub use bench::{Bench, BenchResult, BenchScene};
+pub use buffer::{ChunkStrategy, ImageOrigin, PreviewMode};
+pub use camera::{Camera, ProjectionMode};
+pub use color::Color;
+pub use config::TracerConfig;
+pub use debug::{DebugView, TraversalCounts};
+pub use decal::{BlendMode, Decal};
+pub use distributed::{Coordinator, Worker};
+pub use downsample::DownsampleFilter;
+pub use export::{ExportOptions, FileNaming, RenderMetadata};
+pub use extent::Extent;
+pub use filter::PixelFilter;
+pub use group::{Group, MotionTrack, Transform};
+pub use hit::{Differentials, Hit, Hitable};
+pub use integrator::{Integrator, Toon};
+pub use light::{
+    ev100_to_exposure, lumens_to_watts, watts_to_lumens, LightPower, LightSampling, PointLight,
+    LUMINOUS_EFFICACY,
+};
+pub use material::{Clearcoat, Material};
+pub use mesh::{LodMesh, Mesh};
+pub use normalize::Normalization;
+pub use pacing::{FramePacing, FrameStats};
+pub use pass::{PassControl, PassHook, PassStats};
+pub use payload::{Payload, PayloadFactory};
+pub use portal::Portal;
+pub use post::{
+    Atrous, Bloom, DenoiseMode, Dither, Encoding, Outline, Palette, PostEffect, PostStage, Tonemap,
+    Tonemapper, Vignette,
+};
+pub use primitive::Primitive;
+pub use probe::{Probe, ProbeSet};
 pub use ray::Ray;
+pub use region::Region;
+pub use replay::RngRecording;
+pub use sampler::SamplerKind;
+pub use sampling::{
+    cosine_hemisphere, reflect, refract, uniform_cone, uniform_cone_pdf, uniform_disk,
+    uniform_sphere, Onb,
+};
+pub use scene_diff::{ChangeKind, SceneChange, SceneDiff, SceneItem};
+pub use scene_file::{
+    CameraDesc, EmissionDesc, LightDesc, MaterialDesc, PortalDesc, PrimitiveDesc, SceneFile,
+    TextureDesc,
+};
+pub use snapshot::Snapshot;
 pub use sphere::Sphere;
+pub use stats::RenderStats;
+pub use status::{ProgressHook, RenderControl, RenderProgress, RenderStatus};
+pub use svo::{Svo, VoxelGrid};
+pub use texture::{CheckerTexture, ImageTexture, MarbleTexture, NoiseTexture, Texture, WrapMode};
+pub use timing::{TileTimings, TimingSummary};
 pub use tracer::{Tracer, TracerBuilder};
+pub use validation::{white_furnace, FurnaceReport};
+#[cfg(feature = "video")]
+pub use video::VideoEncoder;
+pub use volume::VoxelVolume;
+pub use watcher::FileWatcher;
@@ -0,0 +1,90 @@
+use glam::Vec3;
+
+use crate::raytracer::Ray;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            min: Vec3::INFINITY,
+            max: Vec3::NEG_INFINITY,
+        }
+    }
+
+    // contains everything, hit by every ray
+    pub fn infinite() -> Self {
+        Self {
+            min: Vec3::NEG_INFINITY,
+            max: Vec3::INFINITY,
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn grow(&self, p: Vec3) -> Self {
+        Self {
+            min: self.min.min(p),
+            max: self.max.max(p),
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn largest_axis(&self) -> usize {
+        let extent = self.extent();
+
+        if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let e = self.extent();
+
+        2. * (e.x * e.y + e.y * e.z + e.z * e.x)
+    }
+
+    // slab test, returns the entry/exit distances clamped to [min, max]
+    pub fn hit(&self, ray: &Ray, min: f32, max: f32) -> Option<(f32, f32)> {
+        let inv = ray.direction.recip();
+
+        let t0 = (self.min - ray.origin) * inv;
+        let t1 = (self.max - ray.origin) * inv;
+
+        let t_near = t0.min(t1);
+        let t_far = t0.max(t1);
+
+        let t_enter = t_near.x.max(t_near.y).max(t_near.z).max(min);
+        let t_exit = t_far.x.min(t_far.y).min(t_far.z).min(max);
+
+        if t_enter <= t_exit {
+            Some((t_enter, t_exit))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::raytracer::{
+    bvh::Bvh, kdtree::KdTree, sphere_set::SphereSet, stats, Hit, Hitable, Primitive, Ray,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AccelKind {
+    LINEAR,
+    BVH,
+    KDTREE,
+}
+
+impl AccelKind {
+    pub fn build(kind: AccelKind, models: &[Arc<Primitive>]) -> AccelData {
+        match kind {
+            AccelKind::LINEAR => match SphereSet::new(models) {
+                Some(spheres) => AccelData::SPHERES(spheres),
+                None => AccelData::LINEAR,
+            },
+            AccelKind::BVH => AccelData::BVH(Bvh::new(models)),
+            AccelKind::KDTREE => AccelData::KDTREE(KdTree::new(models)),
+        }
+    }
+}
+
+pub enum AccelData {
+    LINEAR,
+    SPHERES(SphereSet),
+    BVH(Bvh),
+    KDTREE(KdTree),
+}
+
+impl AccelData {
+    pub fn hit(&self, models: &[Arc<Primitive>], ray: &Ray, min: f32, max: f32) -> Option<Hit> {
+        if matches!(self, AccelData::LINEAR | AccelData::SPHERES(_)) {
+            stats::record_traversal(0, models.len() as u32);
+        }
+
+        match self {
+            AccelData::LINEAR => models
+                .iter()
+                .enumerate()
+                .filter_map(|(i, m)| m.hit(ray, min, max).map(|hit| hit.with_id(i)))
+                .min_by(|h1, h2| h1.distance.total_cmp(&h2.distance)),
+            AccelData::SPHERES(ref spheres) => spheres.hit(models, ray, min, max),
+            AccelData::BVH(ref bvh) => bvh.hit(models, ray, min, max),
+            AccelData::KDTREE(ref tree) => tree.hit(models, ray, min, max),
+        }
+    }
+
+    pub fn occluded(&self, models: &[Arc<Primitive>], ray: &Ray, min: f32, max: f32) -> bool {
+        if matches!(self, AccelData::LINEAR | AccelData::SPHERES(_)) {
+            stats::record_traversal(0, models.len() as u32);
+        }
+
+        match self {
+            AccelData::LINEAR => models
+                .iter()
+                .any(|m| m.hit_distance(ray, min, max).is_some()),
+            AccelData::SPHERES(ref spheres) => spheres.hit_distance(ray, min, max).is_some(),
+            AccelData::BVH(ref bvh) => bvh.occluded(models, ray, min, max),
+            AccelData::KDTREE(ref tree) => tree.occluded(models, ray, min, max),
+        }
+    }
+
+    pub fn hit_packet(
+        &self,
+        models: &[Arc<Primitive>],
+        rays: &[Ray],
+        min: f32,
+        max: f32,
+    ) -> Vec<Option<Hit>> {
+        match self {
+            AccelData::BVH(ref bvh) => bvh.hit_packet(models, rays, min, max),
+            _ => rays
+                .iter()
+                .map(|ray| self.hit(models, ray, min, max))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::{fs, io, path::Path};
+
+use glam::Vec3;
+
+use crate::raytracer::{camera::ProjectionMode, group::MotionTrack, Camera, Tracer};
+
+#[cfg(feature = "video")]
+use crate::raytracer::video::VideoEncoder;
+
+// keyframes of the models, lights and camera of a tracer, in the units of the builder,
+// the values are interpolated between the keys and held before the first and
+// after the last
+#[derive(Clone, Debug, Default)]
+pub struct Animation {
+    // by name of the group, see TracerBuilder::movable_model
+    models: Vec<(String, MotionTrack)>,
+    // by index of the light
+    lights: Vec<(usize, Keys<Vec3>)>,
+    camera: Keys<Camera>,
+}
+
+impl Animation {
+    #[cfg(feature = "image")]
+    const FRAME_FORMAT: &'static str = "png";
+    #[cfg(not(feature = "image"))]
+    const FRAME_FORMAT: &'static str = "ppm";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn model(mut self, name: &str, track: MotionTrack) -> Self {
+        self.models.retain(|(n, _)| n != name);
+        self.models.push((name.to_string(), track));
+
+        self
+    }
+
+    pub fn light(mut self, index: usize, time: f32, position: Vec3) -> Self {
+        match self.lights.iter_mut().find(|(i, _)| *i == index) {
+            Some((_, keys)) => keys.insert(time, position),
+            None => {
+                let mut keys = Keys::default();
+                keys.insert(time, position);
+                self.lights.push((index, keys));
+            }
+        }
+
+        self
+    }
+
+    pub fn camera(mut self, time: f32, camera: Camera) -> Self {
+        self.camera.insert(time, camera);
+
+        self
+    }
+
+    // time of the last key
+    pub fn duration(&self) -> f32 {
+        self.models
+            .iter()
+            .map(|(_, track)| track.end())
+            .chain(self.lights.iter().map(|(_, keys)| keys.end()))
+            .chain([self.camera.end()])
+            .fold(0., f32::max)
+    }
+
+    // moves the models, lights and camera to their position at time, the groups
+    // with their own motion track follow it too, see Tracer::set_time
+    pub fn sample(&self, time: f32, tracer: &mut Tracer) {
+        tracer.set_time(time);
+
+        for (name, track) in &self.models {
+            if let Some(transform) = track.sample(time) {
+                if !tracer.set_group_transform(name, transform) {
+                    log::warn!("Animation: no model {}", name);
+                }
+            }
+        }
+
+        for (index, keys) in &self.lights {
+            if let Some(position) = keys.sample(time, |a, b, t| a.lerp(*b, t)) {
+                if !tracer.set_light_position(*index, position) {
+                    log::warn!("Animation: no light {}", index);
+                }
+            }
+        }
+
+        if let Some(camera) = self.camera.sample(time, lerp_camera) {
+            tracer.set_camera(camera);
+        }
+    }
+
+    // renders each frame until the target samples or the time limit of the tracer
+    // and writes it to out_dir as frame_00000.png, frame_00001.png... or as ppm
+    // without the image feature
+    pub fn render_sequence(
+        &self,
+        tracer: &mut Tracer,
+        fps: f32,
+        duration: f32,
+        out_dir: &str,
+    ) -> io::Result<()> {
+        fs::create_dir_all(out_dir)?;
+
+        let frames = self.render_frames(tracer, fps, duration, |frame, tracer| {
+            let path =
+                Path::new(out_dir).join(format!("frame_{:05}.{}", frame, Self::FRAME_FORMAT));
+            tracer.save(&path.to_string_lossy())
+        })?;
+
+        log::info!("Sequence done: {} frames in {}", frames, out_dir);
+
+        Ok(())
+    }
+
+    // same as render_sequence, encoded to a single mp4 or webm file
+    #[cfg(feature = "video")]
+    pub fn render_video(
+        &self,
+        tracer: &mut Tracer,
+        fps: f32,
+        duration: f32,
+        path: &str,
+    ) -> io::Result<()> {
+        let mut video = VideoEncoder::new(path, tracer.extent(), fps)?;
+
+        self.render_frames(tracer, fps, duration, |_, tracer| {
+            video.add_frame(&tracer.bytes())
+        })?;
+
+        video.finish()
+    }
+
+    fn render_frames(
+        &self,
+        tracer: &mut Tracer,
+        fps: f32,
+        duration: f32,
+        mut output: impl FnMut(u32, &Tracer) -> io::Result<()>,
+    ) -> io::Result<u32> {
+        let config = tracer.config();
+        if config.target_samples.is_none() && config.time_limit.is_none() {
+            return Err(io::Error::other(
+                "the tracer needs target samples or a time limit",
+            ));
+        }
+
+        let frames = (duration * fps).ceil() as u32;
+        for frame in 0..frames {
+            self.sample(frame as f32 / fps, tracer);
+
+            // the first update starts the render of the new frame
+            loop {
+                tracer.update();
+                if tracer.is_complete() {
+                    break;
+                }
+            }
+
+            output(frame, tracer)?;
+        }
+
+        Ok(frames)
+    }
+}
+
+// sorted by time
+#[derive(Clone, Debug)]
+struct Keys<T> {
+    keys: Vec<(f32, T)>,
+}
+
+impl<T> Default for Keys<T> {
+    fn default() -> Self {
+        Self { keys: Vec::new() }
+    }
+}
+
+impl<T: Copy> Keys<T> {
+    fn insert(&mut self, time: f32, value: T) {
+        let idx = self.keys.partition_point(|(t, _)| *t <= time);
+        self.keys.insert(idx, (time, value));
+    }
+
+    fn end(&self) -> f32 {
+        self.keys.last().map_or(0., |(t, _)| *t)
+    }
+
+    fn sample(&self, time: f32, lerp: impl Fn(&T, &T, f32) -> T) -> Option<T> {
+        let idx = self.keys.partition_point(|(t, _)| *t <= time);
+
+        match (idx.checked_sub(1).map(|i| self.keys[i]), self.keys.get(idx)) {
+            (Some((t0, a)), Some((t1, b))) => Some(lerp(&a, b, (time - t0) / (t1 - t0))),
+            (Some((_, a)), None) => Some(a),
+            (None, Some((_, b))) => Some(*b),
+            (None, None) => None,
+        }
+    }
+}
+
+// the field of view of two perspective keys is interpolated, the other projections
+// are taken from the first key
+fn lerp_camera(a: &Camera, b: &Camera, t: f32) -> Camera {
+    let mode = match (a.mode, b.mode) {
+        (
+            ProjectionMode::PERSPECTIVE {
+                aspect,
+                fovy,
+                near,
+                far,
+            },
+            ProjectionMode::PERSPECTIVE { fovy: fovy_b, .. },
+        ) => ProjectionMode::PERSPECTIVE {
+            aspect,
+            fovy: fovy + (fovy_b - fovy) * t,
+            near,
+            far,
+        },
+        (mode, _) => mode,
+    };
+
+    Camera {
+        position: a.position.lerp(b.position, t),
+        mode,
+        yaw: a.yaw + (b.yaw - a.yaw) * t,
+        pitch: a.pitch + (b.pitch - a.pitch) * t,
+        up: a.up.lerp(b.up, t).normalize(),
+    }
+}
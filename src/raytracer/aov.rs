@@ -0,0 +1,94 @@
+use glam::Vec3;
+
+use crate::raytracer::{debug::TraversalCounts, hit::Differentials, Color, Extent, Hit};
+
+// auxiliary values of the primary hit of a pixel
+#[derive(Clone, Copy, Debug)]
+pub struct AovSample {
+    pub depth: f32,
+    pub id: u32,
+    pub normal: Vec3,
+    pub albedo: Color,
+    pub differentials: Differentials,
+    // only with a debug view
+    pub counts: TraversalCounts,
+}
+
+impl AovSample {
+    pub const NO_ID: u32 = u32::MAX;
+
+    pub fn new(hit: &Option<Hit>) -> Self {
+        match hit {
+            Some(hit) => Self {
+                depth: hit.distance,
+                id: hit.id,
+                normal: hit.normal,
+                albedo: hit.color,
+                differentials: hit.differentials,
+                counts: TraversalCounts::default(),
+            },
+            None => Self {
+                depth: f32::INFINITY,
+                id: Self::NO_ID,
+                normal: Vec3::ZERO,
+                albedo: Color::BLACK,
+                differentials: Differentials::default(),
+                counts: TraversalCounts::default(),
+            },
+        }
+    }
+}
+
+// arbitrary output variables, written by the first sample of each pixel
+#[derive(Clone)]
+pub struct Aovs {
+    pub depth: Vec<f32>,
+    pub id: Vec<u32>,
+    pub normal: Vec<Vec3>,
+    pub albedo: Vec<Color>,
+    pub differentials: Vec<Differentials>,
+    pub counts: Vec<TraversalCounts>,
+}
+
+impl Aovs {
+    pub fn new(size: usize) -> Self {
+        Self {
+            depth: vec![f32::INFINITY; size],
+            id: vec![AovSample::NO_ID; size],
+            normal: vec![Vec3::ZERO; size],
+            albedo: vec![Color::BLACK; size],
+            differentials: vec![Differentials::default(); size],
+            counts: vec![TraversalCounts::default(); size],
+        }
+    }
+
+    pub fn set(&mut self, idx: usize, sample: &AovSample) {
+        self.depth[idx] = sample.depth;
+        self.id[idx] = sample.id;
+        self.normal[idx] = sample.normal;
+        self.albedo[idx] = sample.albedo;
+        self.differentials[idx] = sample.differentials;
+        self.counts[idx] = sample.counts;
+    }
+
+    // the center sample of each block of factor x factor pixels, extent is the size
+    // before the downsampling
+    pub fn downsample(&self, extent: Extent, factor: u32) -> Self {
+        let (width, factor) = (extent.width as usize, factor as usize);
+        let (columns, rows) = (width / factor, extent.height as usize / factor);
+
+        let indices = (0..rows)
+            .flat_map(|y| (0..columns).map(move |x| (x, y)))
+            .map(|(x, y)| x * factor + factor / 2 + (y * factor + factor / 2) * width)
+            .collect::<Vec<_>>();
+
+        Self {
+            depth: indices.iter().map(|&i| self.depth[i]).collect(),
+            id: indices.iter().map(|&i| self.id[i]).collect(),
+            normal: indices.iter().map(|&i| self.normal[i]).collect(),
+            albedo: indices.iter().map(|&i| self.albedo[i]).collect(),
+            differentials: indices.iter().map(|&i| self.differentials[i]).collect(),
+            counts: indices.iter().map(|&i| self.counts[i]).collect(),
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+// where the passes of the main camera are traced, see TracerBuilder::backend
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Backend {
+    #[default]
+    CPU,
+    // spheres and triangles with direct lighting in a compute shader, the views
+    // and the invalidated regions stay on the cpu, as do the scenes with
+    // reflections, textures or another integrator, requires the gpu feature
+    GPU,
+}
@@ -0,0 +1,94 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::raytracer::{Color, Ray};
+
+// vertical gradient, each channel has its own falloff towards the zenith and the ground
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gradient {
+    pub ground: [f32; 3],
+    pub horizon: [f32; 3],
+    pub zenith: [f32; 3],
+    pub falloff: [f32; 3],
+}
+
+impl Gradient {
+    pub fn sample(&self, ray: &Ray) -> Color {
+        let t = ray.direction.y.clamp(-1., 1.);
+        let end = if t >= 0. { self.zenith } else { self.ground };
+
+        let mut rgb = [0.; 3];
+        for c in 0..3 {
+            let w = t.abs().powf(self.falloff[c]);
+            rgb[c] = self.horizon[c] * (1. - w) + end[c] * w;
+        }
+
+        Color::new(rgb[0], rgb[1], rgb[2], 1.)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Background {
+    BLACK,
+    STUDIO,
+    SUNSET,
+    OVERCAST,
+    NIGHT,
+}
+
+impl Background {
+    const STUDIO_GRADIENT: Gradient = Gradient {
+        ground: [0.18, 0.18, 0.18],
+        horizon: [0.8, 0.8, 0.8],
+        zenith: [0.35, 0.35, 0.38],
+        falloff: [0.6, 0.6, 0.6],
+    };
+
+    const SUNSET_GRADIENT: Gradient = Gradient {
+        ground: [0.12, 0.08, 0.07],
+        horizon: [1.6, 0.7, 0.3],
+        zenith: [0.15, 0.2, 0.55],
+        falloff: [0.25, 0.45, 0.8],
+    };
+
+    const OVERCAST_GRADIENT: Gradient = Gradient {
+        ground: [0.25, 0.25, 0.24],
+        horizon: [0.75, 0.77, 0.8],
+        zenith: [0.9, 0.92, 0.95],
+        falloff: [1., 1., 1.],
+    };
+
+    const NIGHT_GRADIENT: Gradient = Gradient {
+        ground: [0.005, 0.005, 0.008],
+        horizon: [0.03, 0.04, 0.08],
+        zenith: [0.002, 0.004, 0.015],
+        falloff: [0.5, 0.5, 0.35],
+    };
+
+    // for TracerBuilder::background()
+    pub fn shader(self) -> fn(&Ray) -> Color {
+        match self {
+            Background::BLACK => |_| Color::BLACK,
+            Background::STUDIO => |ray| Self::STUDIO_GRADIENT.sample(ray),
+            Background::SUNSET => |ray| Self::SUNSET_GRADIENT.sample(ray),
+            Background::OVERCAST => |ray| Self::OVERCAST_GRADIENT.sample(ray),
+            Background::NIGHT => |ray| Self::NIGHT_GRADIENT.sample(ray),
+        }
+    }
+}
+
+impl FromStr for Background {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "black" => Ok(Background::BLACK),
+            "studio" => Ok(Background::STUDIO),
+            "sunset" => Ok(Background::SUNSET),
+            "overcast" => Ok(Background::OVERCAST),
+            "night" => Ok(Background::NIGHT),
+            _ => Err(format!("unknown background: {}", name)),
+        }
+    }
+}
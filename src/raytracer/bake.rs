@@ -0,0 +1,127 @@
+use std::io;
+#[cfg(feature = "image")]
+use std::{fs::File, io::BufWriter};
+
+#[cfg(feature = "image")]
+use image::{codecs::hdr::HdrEncoder, ImageFormat, Rgb, RgbImage, Rgba32FImage};
+
+use crate::raytracer::{export, Color, Encoding, Extent};
+
+// irradiance stored in the texture space of a mesh
+pub struct Lightmap {
+    pub width: u32,
+    pub height: u32,
+    pub texels: Vec<Color>,
+}
+
+impl Lightmap {
+    pub fn new(width: u32, height: u32, texels: Vec<Color>) -> Self {
+        assert_eq!(texels.len(), (width * height) as usize);
+
+        Self {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    // extends the baked texels over the empty ones around them, hides seams when filtering
+    pub fn dilate(&mut self, covered: &[bool], iterations: u32) {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let mut covered = covered.to_vec();
+
+        for _ in 0..iterations {
+            let mut next = covered.clone();
+
+            for y in 0..height {
+                for x in 0..width {
+                    let idx = (x + y * width) as usize;
+                    if covered[idx] {
+                        continue;
+                    }
+
+                    let neighbour = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                        .iter()
+                        .map(|(dx, dy)| (x + dx, y + dy))
+                        .filter(|&(nx, ny)| nx >= 0 && ny >= 0 && nx < width && ny < height)
+                        .map(|(nx, ny)| (nx + ny * width) as usize)
+                        .find(|&n| covered[n]);
+
+                    if let Some(n) = neighbour {
+                        self.texels[idx] = self.texels[n];
+                        next[idx] = true;
+                    }
+                }
+            }
+
+            covered = next;
+        }
+    }
+
+    // exr and hdr keep the linear values, other formats are srgb encoded and
+    // clamped to 8 bits, only ppm without the image feature
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let lower = path.to_lowercase();
+
+        if lower.ends_with(".ppm") {
+            export::write_ppm(path, Extent::new(self.width, self.height), &self.srgb())?;
+        } else {
+            self.save_image(path, &lower)?;
+        }
+
+        log::info!("Lightmap saved: {}", path);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "image")]
+    fn save_image(&self, path: &str, lower: &str) -> io::Result<()> {
+        if lower.ends_with(".hdr") {
+            let pixels = self
+                .texels
+                .iter()
+                .map(|c| Rgb([c.r, c.g, c.b]))
+                .collect::<Vec<_>>();
+
+            let writer = BufWriter::new(File::create(path)?);
+            HdrEncoder::new(writer)
+                .encode(&pixels, self.width as usize, self.height as usize)
+                .map_err(io::Error::other)
+        } else if lower.ends_with(".exr") {
+            let data = self
+                .texels
+                .iter()
+                .flat_map(|c| [c.r, c.g, c.b, 1.])
+                .collect();
+
+            Rgba32FImage::from_raw(self.width, self.height, data)
+                .unwrap()
+                .save_with_format(path, ImageFormat::OpenExr)
+                .map_err(io::Error::other)
+        } else {
+            RgbImage::from_raw(self.width, self.height, self.srgb())
+                .unwrap()
+                .save(path)
+                .map_err(io::Error::other)
+        }
+    }
+
+    #[cfg(not(feature = "image"))]
+    fn save_image(&self, path: &str, _: &str) -> io::Result<()> {
+        Err(export::unsupported(path))
+    }
+
+    // 8 bits rgb
+    fn srgb(&self) -> Vec<u8> {
+        let encode = |x: f32| Encoding::SRGB.apply(x.clamp(0., 1.));
+
+        self.texels
+            .iter()
+            .flat_map(|c| {
+                let [r, g, b, _]: [u8; 4] =
+                    Color::new(encode(c.r), encode(c.g), encode(c.b), 1.).into();
+                [r, g, b]
+            })
+            .collect()
+    }
+}
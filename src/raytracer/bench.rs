@@ -0,0 +1,340 @@
+use std::{fmt, sync::Arc, time::Instant};
+
+use glam::Vec3;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::raytracer::{
+    ChunkStrategy, Color, Extent, LightPower, Material, Mesh, PointLight, Primitive, Sphere,
+    TracerBuilder,
+};
+
+// standard scenes for comparing the speed of the tracer between two versions, in
+// front of the default camera
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BenchScene {
+    // grid of small spheres on a ground
+    SPHEREFIELD,
+    // closed box with colored walls, two spheres and a light under the ceiling
+    CORNELLBOX,
+    // the cover of ray tracing in one weekend, ~500 spheres
+    RANDOMSPHERES,
+}
+
+impl BenchScene {
+    pub const ALL: [BenchScene; 3] = [
+        BenchScene::SPHEREFIELD,
+        BenchScene::CORNELLBOX,
+        BenchScene::RANDOMSPHERES,
+    ];
+
+    pub async fn builder(&self, extent: Extent) -> TracerBuilder {
+        let builder = TracerBuilder::new(extent).await;
+
+        let builder = match self {
+            BenchScene::SPHEREFIELD => Self::sphere_field(builder),
+            BenchScene::CORNELLBOX => Self::cornell_box(builder),
+            BenchScene::RANDOMSPHERES => Self::random_spheres(builder),
+        };
+
+        // a fixed seed for the same samples in each run
+        builder.seed(1)
+    }
+
+    fn sphere_field(builder: TracerBuilder) -> TracerBuilder {
+        let mut builder = builder
+            .model(Sphere::new(
+                "ground",
+                Vec3::new(0., -5000.2, 0.),
+                5000.,
+                Color::GREY,
+                0.1,
+            ))
+            .light(PointLight::new(
+                Vec3::new(0., 2., 0.),
+                Color::WHITE,
+                LightPower::LUMENS(500.),
+            ));
+
+        for i in 0..10 {
+            for j in 0..10 {
+                let center = Vec3::new(-1.35 + 0.3 * i as f32, -0.1, 0.6 + 0.3 * j as f32);
+                let color = Color::new(i as f32 / 9., 0.5, j as f32 / 9., 1.);
+                let name = format!("sphere-{}-{}", i, j);
+
+                builder = builder.model(Sphere::new(&name, center, 0.1, color, 0.2));
+            }
+        }
+
+        builder
+    }
+
+    fn cornell_box(builder: TracerBuilder) -> TracerBuilder {
+        let (x0, x1, y0, y1, z0, z1) = (-1., 1., -1., 1., 1., 3.);
+        let p = |x, y, z| Vec3::new(x, y, z);
+
+        builder
+            .model(quad(
+                "floor",
+                [p(x0, y0, z0), p(x1, y0, z0), p(x1, y0, z1), p(x0, y0, z1)],
+                Color::WHITE,
+            ))
+            .model(quad(
+                "ceiling",
+                [p(x0, y1, z0), p(x0, y1, z1), p(x1, y1, z1), p(x1, y1, z0)],
+                Color::WHITE,
+            ))
+            .model(quad(
+                "back",
+                [p(x0, y0, z1), p(x1, y0, z1), p(x1, y1, z1), p(x0, y1, z1)],
+                Color::WHITE,
+            ))
+            .model(quad(
+                "left",
+                [p(x0, y0, z0), p(x0, y0, z1), p(x0, y1, z1), p(x0, y1, z0)],
+                Color::RED,
+            ))
+            .model(quad(
+                "right",
+                [p(x1, y0, z0), p(x1, y1, z0), p(x1, y1, z1), p(x1, y0, z1)],
+                Color::GREEN,
+            ))
+            .model(Sphere::new(
+                "mirror",
+                p(-0.4, -0.6, 2.2),
+                0.4,
+                Color::WHITE,
+                0.9,
+            ))
+            .model(Sphere::new(
+                "diffuse",
+                p(0.45, -0.7, 1.7),
+                0.3,
+                Color::WHITE,
+                0.,
+            ))
+            .light(
+                PointLight::new(p(0., 0.9, 2.), Color::WHITE, LightPower::LUMENS(250.)).radius(0.1),
+            )
+    }
+
+    fn random_spheres(builder: TracerBuilder) -> TracerBuilder {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let mut builder = builder
+            .model(Sphere::new(
+                "ground",
+                Vec3::new(0., -1001., 12.),
+                1000.,
+                Color::GREY,
+                0.,
+            ))
+            .model(Sphere::new(
+                "mirror",
+                Vec3::new(0., 0., 12.),
+                1.,
+                Color::WHITE,
+                0.9,
+            ))
+            .model(Sphere::new(
+                "diffuse",
+                Vec3::new(-4., 0., 12.),
+                1.,
+                Color::new(0.4, 0.2, 0.1, 1.),
+                0.,
+            ))
+            .model(Sphere::new(
+                "metal",
+                Vec3::new(4., 0., 12.),
+                1.,
+                Color::new(0.7, 0.6, 0.5, 1.),
+                0.8,
+            ))
+            .light(PointLight::new(
+                Vec3::new(0., 10., 8.),
+                Color::WHITE,
+                LightPower::LUMENS(20000.),
+            ));
+
+        for a in -11..11 {
+            for b in -11..11 {
+                let center = Vec3::new(
+                    a as f32 + 0.9 * rng.gen::<f32>(),
+                    -0.8,
+                    12. + b as f32 + 0.9 * rng.gen::<f32>(),
+                );
+                if (center - Vec3::new(4., -0.8, 12.)).length() < 0.9 {
+                    continue;
+                }
+
+                let color = Color::new(rng.gen(), rng.gen(), rng.gen(), 1.);
+                let reflect = if rng.gen::<f32>() < 0.8 { 0. } else { 0.8 };
+                let name = format!("sphere-{}-{}", a, b);
+
+                builder = builder.model(Sphere::new(&name, center, 0.2, color, reflect));
+            }
+        }
+
+        builder
+    }
+}
+
+fn quad(name: &str, corners: [Vec3; 4], color: Color) -> Primitive {
+    Mesh::new(
+        name,
+        corners.to_vec(),
+        vec![[0, 1, 2], [0, 2, 3]],
+        Material::new(Arc::new(color), 0.),
+    )
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BenchResult {
+    pub scene: BenchScene,
+    pub strategy: ChunkStrategy,
+    pub threads: u32,
+    // primary rays
+    pub rays: u64,
+    // seconds
+    pub elapsed: f32,
+}
+
+impl BenchResult {
+    pub fn mrays_per_second(&self) -> f32 {
+        self.rays as f32 / self.elapsed / 1e6
+    }
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?}, {:?}, {} threads: {:.2}s, {:.2} Mrays/s",
+            self.scene,
+            self.strategy,
+            self.threads,
+            self.elapsed,
+            self.mrays_per_second()
+        )
+    }
+}
+
+// renders a scene to a fixed number of samples, e.g. from a cargo bench target:
+// Bench::new().threads(8).run(BenchScene::CORNELLBOX).await
+#[derive(Clone, Debug)]
+pub struct Bench {
+    extent: Extent,
+    samples: u32,
+    threads: u32,
+    strategy: ChunkStrategy,
+}
+
+impl Default for Bench {
+    fn default() -> Self {
+        Self {
+            extent: Extent::new(320, 180),
+            samples: 16,
+            threads: 1,
+            strategy: ChunkStrategy::default(),
+        }
+    }
+}
+
+impl Bench {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn extent(mut self, extent: Extent) -> Self {
+        self.extent = extent;
+
+        self
+    }
+
+    pub fn samples(mut self, samples: u32) -> Self {
+        self.samples = samples;
+
+        self
+    }
+
+    pub fn threads(mut self, threads: u32) -> Self {
+        self.threads = threads;
+
+        self
+    }
+
+    pub fn strategy(mut self, strategy: ChunkStrategy) -> Self {
+        self.strategy = strategy;
+
+        self
+    }
+
+    // the time to build the scene and its acceleration structure is not counted
+    pub async fn run(&self, scene: BenchScene) -> BenchResult {
+        let rays = self.samples.min(4);
+
+        let mut tracer = scene
+            .builder(self.extent)
+            .await
+            .threads(self.threads)
+            .strategy(self.strategy)
+            .rays(rays)
+            .target_samples(self.samples)
+            .build()
+            .await;
+
+        let start = Instant::now();
+        while !tracer.is_complete() {
+            tracer.update();
+        }
+        let elapsed = start.elapsed().as_secs_f32();
+
+        let result = BenchResult {
+            scene,
+            strategy: self.strategy,
+            threads: self.threads,
+            rays: tracer.progress().pixels_done * rays as u64,
+            elapsed,
+        };
+
+        log::info!("Bench: {}", result);
+
+        result
+    }
+
+    // same scene and strategy with each number of threads
+    pub async fn thread_scaling(&self, scene: BenchScene, threads: &[u32]) -> Vec<BenchResult> {
+        let mut results = Vec::new();
+        for &n in threads {
+            results.push(self.clone().threads(n).run(scene).await);
+        }
+
+        results
+    }
+
+    // same scene and threads with each strategy
+    pub async fn chunk_strategies(
+        &self,
+        scene: BenchScene,
+        strategies: &[ChunkStrategy],
+    ) -> Vec<BenchResult> {
+        let mut results = Vec::new();
+        for &strategy in strategies {
+            results.push(self.clone().strategy(strategy).run(scene).await);
+        }
+
+        results
+    }
+
+    // the built-in strategies with their default sizes
+    pub fn default_strategies() -> Vec<ChunkStrategy> {
+        vec![
+            ChunkStrategy::RANDOM { pixels: 4096 },
+            ChunkStrategy::LINE { pixels: 4096 },
+            ChunkStrategy::default(),
+            ChunkStrategy::SPIRAL {
+                width: 128,
+                height: 128,
+            },
+        ]
+    }
+}
@@ -0,0 +1,148 @@
+use std::sync::OnceLock;
+
+// tile of ranks with a blue noise spectrum, built with void-and-cluster
+pub struct BlueNoise {
+    values: Vec<f32>,
+}
+
+impl BlueNoise {
+    pub const SIZE: usize = 64;
+    const SIGMA: f32 = 1.5;
+    const INITIAL_DENSITY: usize = 10;
+
+    pub fn tile() -> &'static BlueNoise {
+        static TILE: OnceLock<BlueNoise> = OnceLock::new();
+
+        TILE.get_or_init(Self::generate)
+    }
+
+    // value in [0, 1) for the given pixel, wraps around the tile
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.values[(x % Self::SIZE) + (y % Self::SIZE) * Self::SIZE]
+    }
+
+    fn generate() -> Self {
+        let n = Self::SIZE * Self::SIZE;
+
+        let kernel = Self::kernel();
+        let mut energy = Energy {
+            values: vec![0.; n],
+            kernel: &kernel,
+        };
+
+        // random initial pattern
+        let mut pattern = vec![false; n];
+        let mut seed = 0x2545f491u32;
+        let mut count = 0;
+        while count < n / Self::INITIAL_DENSITY {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+
+            let idx = seed as usize % n;
+            if !pattern[idx] {
+                pattern[idx] = true;
+                energy.add(idx, 1.);
+                count += 1;
+            }
+        }
+
+        // spread the points until the tightest cluster is the largest void
+        for _ in 0..n {
+            let cluster = energy.tightest_cluster(&pattern);
+            pattern[cluster] = false;
+            energy.add(cluster, -1.);
+
+            let void = energy.largest_void(&pattern);
+            pattern[void] = true;
+            energy.add(void, 1.);
+
+            if void == cluster {
+                break;
+            }
+        }
+
+        let mut ranks = vec![0; n];
+
+        // remove points from the initial pattern, densest first
+        let mut initial = pattern.clone();
+        let mut initial_energy = Energy {
+            values: energy.values.clone(),
+            kernel: &kernel,
+        };
+        for rank in (0..count).rev() {
+            let cluster = initial_energy.tightest_cluster(&initial);
+            initial[cluster] = false;
+            initial_energy.add(cluster, -1.);
+            ranks[cluster] = rank;
+        }
+
+        // then fill the voids
+        for rank in count..n {
+            let void = energy.largest_void(&pattern);
+            pattern[void] = true;
+            energy.add(void, 1.);
+            ranks[void] = rank;
+        }
+
+        Self {
+            values: ranks
+                .into_iter()
+                .map(|rank| (rank as f32 + 0.5) / n as f32)
+                .collect(),
+        }
+    }
+
+    // gaussian weights by toroidal offset
+    fn kernel() -> Vec<f32> {
+        let size = Self::SIZE as i32;
+        let mut kernel = vec![0.; Self::SIZE * Self::SIZE];
+
+        for dy in 0..size {
+            for dx in 0..size {
+                let x = dx.min(size - dx) as f32;
+                let y = dy.min(size - dy) as f32;
+
+                kernel[(dx + dy * size) as usize] =
+                    (-(x * x + y * y) / (2. * Self::SIGMA * Self::SIGMA)).exp();
+            }
+        }
+
+        kernel
+    }
+}
+
+struct Energy<'a> {
+    values: Vec<f32>,
+    kernel: &'a [f32],
+}
+
+impl Energy<'_> {
+    fn add(&mut self, idx: usize, weight: f32) {
+        let size = BlueNoise::SIZE;
+        let (px, py) = (idx % size, idx / size);
+
+        for y in 0..size {
+            for x in 0..size {
+                let dx = (x + size - px) % size;
+                let dy = (y + size - py) % size;
+
+                self.values[x + y * size] += weight * self.kernel[dx + dy * size];
+            }
+        }
+    }
+
+    fn tightest_cluster(&self, pattern: &[bool]) -> usize {
+        (0..pattern.len())
+            .filter(|&i| pattern[i])
+            .max_by(|&a, &b| self.values[a].total_cmp(&self.values[b]))
+            .unwrap()
+    }
+
+    fn largest_void(&self, pattern: &[bool]) -> usize {
+        (0..pattern.len())
+            .filter(|&i| !pattern[i])
+            .min_by(|&a, &b| self.values[a].total_cmp(&self.values[b]))
+            .unwrap()
+    }
+}
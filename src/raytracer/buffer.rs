@@ -1,65 +1,796 @@
-use gobs::{core::Color, render::ImageExtent2D};
-use rand::seq::SliceRandom;
+use std::sync::Arc;
+
+use glam::Vec3;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::raytracer::{
+    aov::{AovSample, Aovs},
+    checkpoint::Checkpoint,
+    color::ColorExt,
+    debug::{DebugView, TraversalCounts},
+    hit::Differentials,
+    timing::TileTimings,
+    Color, Extent,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PreviewMode {
+    COLOR,
+    VARIANCE,
+    ERROR,
+    // directions of the derivatives of the primary hits, to check the tangent frames
+    // and the uv seams
+    DPDU,
+    DPDV,
+    DNDU,
+    DNDV,
+}
+
+impl PreviewMode {
+    pub fn next(&self) -> Self {
+        match self {
+            PreviewMode::COLOR => PreviewMode::VARIANCE,
+            PreviewMode::VARIANCE => PreviewMode::ERROR,
+            PreviewMode::ERROR => PreviewMode::DPDU,
+            PreviewMode::DPDU => PreviewMode::DPDV,
+            PreviewMode::DPDV => PreviewMode::DNDU,
+            PreviewMode::DNDU => PreviewMode::DNDV,
+            PreviewMode::DNDV => PreviewMode::COLOR,
+        }
+    }
+}
+
+// sums of the samples computed for one pixel
+#[derive(Clone, Copy, Debug)]
+pub struct PixelSamples {
+    pub idx: usize,
+    // weighted by the pixel filter
+    pub color: Color,
+    // sum of the weights of color
+    pub weight: f32,
+    pub luminance: f32,
+    pub luminance_sq: f32,
+    pub count: u32,
+    pub aov: AovSample,
+    // samples rescaled by the firefly clamp
+    pub clamped: u32,
+    // NaN or infinite samples, dropped
+    pub invalid: u32,
+    // approximate color, not accumulated
+    pub preview: bool,
+    // only the color and weight spread by the pixel filter from the samples of
+    // the neighbours
+    pub splat: bool,
+}
+
+impl PixelSamples {
+    pub fn new(idx: usize) -> Self {
+        Self {
+            idx,
+            color: Color::BLACK,
+            weight: 0.,
+            luminance: 0.,
+            luminance_sq: 0.,
+            count: 0,
+            aov: AovSample::new(&None),
+            clamped: 0,
+            invalid: 0,
+            preview: false,
+            splat: false,
+        }
+    }
+
+    pub fn add(&mut self, color: Color) {
+        self.add_statistics(color);
+        self.add_weighted(color, 1.);
+    }
+
+    // counts a sample whose color is spread by the pixel filter
+    pub fn add_statistics(&mut self, color: Color) {
+        let luminance = color.luminance();
+
+        self.luminance += luminance;
+        self.luminance_sq += luminance * luminance;
+        self.count += 1;
+    }
+
+    pub fn add_weighted(&mut self, color: Color, weight: f32) {
+        self.color = self.color + color * weight;
+        self.weight += weight;
+    }
+}
 
 pub struct ImageBuffer {
-    pub extent: ImageExtent2D,
-    pub framebuffer: Vec<Color>,
+    pub extent: Extent,
+    // shared with the snapshots, copied on the next write
+    pub framebuffer: Arc<Vec<Color>>,
+    pub variance: Vec<f32>,
+    pub aovs: Aovs,
+    accumulation: Vec<Color>,
+    // sum of the weights of the pixel filter in the accumulation
+    weights: Vec<f32>,
+    luminance: Vec<f32>,
+    luminance_sq: Vec<f32>,
+    samples: Vec<u32>,
+    // pixels without the samples of the current pass
+    pending: Vec<bool>,
+    // tiles of TILE_SIZE pixels changed since the last take_dirty_tiles
+    dirty: Vec<bool>,
     strategy: ChunkStrategyData,
+    // chunk of each pixel in the last pass, see DebugView::CHUNKS
+    chunk_ids: Vec<u32>,
+    chunk_count: u32,
+    // seconds of the chunks spread over their pixels, see Tracer::tile_timings
+    times: Vec<f32>,
+    // version of the last reset of each pixel, older chunks are not added to it
+    since: Vec<u64>,
+    catch_up: Vec<CatchUp>,
+    // pixel whose neighbour chunks are rendered first
+    focus: Option<(u32, u32)>,
 }
 
 impl ImageBuffer {
-    pub fn new(extent: ImageExtent2D, strategy: ChunkStrategy) -> Self {
+    pub const TILE_SIZE: u32 = 64;
+
+    pub fn new(extent: Extent, strategy: ChunkStrategy) -> Self {
         Self {
             extent,
-            framebuffer: Vec::new(),
+            framebuffer: Arc::new(Vec::new()),
+            variance: Vec::new(),
+            aovs: Aovs::new(0),
+            accumulation: Vec::new(),
+            weights: Vec::new(),
+            luminance: Vec::new(),
+            luminance_sq: Vec::new(),
+            samples: Vec::new(),
+            pending: Vec::new(),
+            dirty: Vec::new(),
             strategy: ChunkStrategy::new(strategy, extent),
+            chunk_ids: Vec::new(),
+            chunk_count: 0,
+            times: Vec::new(),
+            since: Vec::new(),
+            catch_up: Vec::new(),
+            focus: None,
         }
     }
 
-    pub fn reset(&mut self) {
+    // the seed gives the order of the chunks
+    pub fn reset(&mut self, seed: u64) {
         log::debug!("Reset buffer");
-        self.framebuffer.clear();
+        let size = self.extent.size() as usize;
 
-        for _ in 0..self.extent.size() {
-            self.framebuffer.push(Color::BLACK);
-        }
+        self.framebuffer = Arc::new(vec![Color::BLACK; size]);
+        self.variance = vec![0.; size];
+        self.aovs = Aovs::new(size);
+        self.accumulation = vec![Color::BLACK; size];
+        self.weights = vec![0.; size];
+        self.luminance = vec![0.; size];
+        self.luminance_sq = vec![0.; size];
+        self.samples = vec![0; size];
+        self.pending = vec![true; size];
+        let (tiles_x, tiles_y) = self.tiles();
+        self.dirty = vec![true; (tiles_x * tiles_y) as usize];
+        self.chunk_ids = vec![0; size];
+        self.chunk_count = 0;
+        self.times = vec![0.; size];
+        self.since = vec![0; size];
+        self.catch_up.clear();
+
+        self.strategy.reset(self.extent, seed);
+        self.apply_focus();
+    }
 
-        self.strategy.reset(self.extent);
+    // used from the next reset
+    pub fn set_strategy(&mut self, strategy: ChunkStrategy) {
+        self.strategy = ChunkStrategy::new(strategy, self.extent);
     }
 
-    pub fn bytes(&self) -> Vec<u8> {
-        self.framebuffer
+    // start a new pass over the image, keeping the accumulated samples
+    pub fn next_pass(&mut self, seed: u64) {
+        log::debug!("Next pass");
+        self.pending.fill(true);
+        self.strategy.reset(self.extent, seed);
+        self.apply_focus();
+    }
+
+    // kept for the next passes, the remaining chunks are reordered, or picked around
+    // it as they are taken with RANDOM
+    pub fn set_focus(&mut self, focus: Option<(u32, u32)>) {
+        self.focus = focus;
+        self.apply_focus();
+    }
+
+    fn apply_focus(&mut self) {
+        if let Some((x, y)) = self.focus {
+            let x = x.min(self.extent.width.saturating_sub(1));
+            let y = y.min(self.extent.height.saturating_sub(1));
+            self.strategy.focus(self.extent, x, y);
+        }
+    }
+
+    pub fn to_bytes(framebuffer: &[Color]) -> Vec<u8> {
+        framebuffer
             .iter()
             .flat_map(|c| Into::<[u8; 4]>::into(*c))
             .collect::<Vec<u8>>()
     }
 
-    pub fn update_pixel(&mut self, idx: usize, c: Color) {
-        self.framebuffer[idx] = c;
+    pub fn add_samples(&mut self, samples: &PixelSamples) {
+        let idx = samples.idx;
+
+        // preview shading, shown until the first sample of the pixel
+        if samples.preview {
+            if self.samples[idx] == 0 {
+                self.aovs.set(idx, &samples.aov);
+                Arc::make_mut(&mut self.framebuffer)[idx] = samples.color;
+                self.mark_dirty(idx);
+            }
+            return;
+        }
+
+        self.accumulation[idx] = self.accumulation[idx] + samples.color;
+        self.weights[idx] += samples.weight;
+
+        // the neighbours don't count as samples of the pixel
+        if samples.splat {
+            self.update_pixel(idx);
+            return;
+        }
+
+        if self.samples[idx] == 0 {
+            self.aovs.set(idx, &samples.aov);
+        }
+
+        self.luminance[idx] += samples.luminance;
+        self.luminance_sq[idx] += samples.luminance_sq;
+        self.samples[idx] += samples.count;
+        self.pending[idx] = false;
+
+        self.update_pixel(idx);
     }
 
-    pub fn is_complete(&self) -> bool {
-        self.strategy.is_complete()
+    fn update_pixel(&mut self, idx: usize) {
+        if self.samples[idx] == 0 {
+            return;
+        }
+        self.mark_dirty(idx);
+
+        let n = self.samples[idx] as f32;
+        let mean = self.luminance[idx] / n;
+
+        // the negative lobes of a filter can cancel the weights of a pixel
+        if self.weights[idx] > 0. {
+            Arc::make_mut(&mut self.framebuffer)[idx] = self.accumulation[idx] / self.weights[idx];
+        }
+        // variance of the mean estimate
+        self.variance[idx] = (self.luminance_sq[idx] / n - mean * mean).max(0.) / n;
     }
 
-    pub fn get_chunk(&mut self) -> Vec<usize> {
+    fn tiles(&self) -> (u32, u32) {
+        (
+            self.extent.width.div_ceil(Self::TILE_SIZE),
+            self.extent.height.div_ceil(Self::TILE_SIZE),
+        )
+    }
+
+    fn mark_dirty(&mut self, idx: usize) {
+        let (tiles_x, _) = self.tiles();
+        let x = idx as u32 % self.extent.width / Self::TILE_SIZE;
+        let y = idx as u32 / self.extent.width / Self::TILE_SIZE;
+
+        self.dirty[(x + y * tiles_x) as usize] = true;
+    }
+
+    pub fn add_time(&mut self, chunk: &[usize], elapsed: f32) {
+        let time = elapsed / chunk.len().max(1) as f32;
+        for &idx in chunk {
+            self.times[idx] += time;
+        }
+    }
+
+    // the times summed over the tiles of the dirty tracking
+    pub fn tile_timings(&self) -> TileTimings {
+        let (tiles_x, tiles_y) = self.tiles();
+        let mut times = vec![0.; (tiles_x * tiles_y) as usize];
+
+        for (idx, time) in self.times.iter().enumerate() {
+            let x = idx as u32 % self.extent.width / Self::TILE_SIZE;
+            let y = idx as u32 / self.extent.width / Self::TILE_SIZE;
+            times[(x + y * tiles_x) as usize] += time;
+        }
+
+        TileTimings {
+            tile_size: Self::TILE_SIZE,
+            tiles_x,
+            tiles_y,
+            times,
+        }
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty.fill(true);
+    }
+
+    // rectangles of the tiles changed since the last call as x, y, width, height
+    pub fn take_dirty_tiles(&mut self) -> Vec<(u32, u32, u32, u32)> {
+        let (tiles_x, _) = self.tiles();
+        let (width, height) = (self.extent.width, self.extent.height);
+
+        let mut tiles = Vec::new();
+        for (i, dirty) in self.dirty.iter_mut().enumerate() {
+            if !std::mem::take(dirty) {
+                continue;
+            }
+
+            let x = (i as u32 % tiles_x) * Self::TILE_SIZE;
+            let y = (i as u32 / tiles_x) * Self::TILE_SIZE;
+            tiles.push((
+                x,
+                y,
+                Self::TILE_SIZE.min(width - x),
+                Self::TILE_SIZE.min(height - y),
+            ));
+        }
+
+        tiles
+    }
+
+    fn clear_pixel(&mut self, idx: usize) {
+        self.mark_dirty(idx);
+        Arc::make_mut(&mut self.framebuffer)[idx] = Color::BLACK;
+        self.variance[idx] = 0.;
+        self.aovs.set(idx, &AovSample::new(&None));
+        self.accumulation[idx] = Color::BLACK;
+        self.weights[idx] = 0.;
+        self.luminance[idx] = 0.;
+        self.luminance_sq[idx] = 0.;
+        self.samples[idx] = 0;
+    }
+
+    // the pass, rays and render time are left for the tracer to fill
+    pub fn checkpoint(&self) -> Checkpoint {
+        let size = self.samples.len();
+        let mut aovs = Aovs::new(size);
+        aovs.depth.clone_from(&self.aovs.depth);
+        aovs.id.clone_from(&self.aovs.id);
+        aovs.normal.clone_from(&self.aovs.normal);
+        aovs.albedo.clone_from(&self.aovs.albedo);
+
+        // the chunks of the regions already done are not tracked, they start over
+        let regions = self
+            .catch_up
+            .iter()
+            .map(|catch_up| (catch_up.chunks.concat(), catch_up.passes))
+            .collect();
+
+        Checkpoint {
+            extent: self.extent,
+            pass: 0,
+            n_rays: 0,
+            render_time: 0.,
+            accumulation: self.accumulation.clone(),
+            weights: self.weights.clone(),
+            luminance: self.luminance.clone(),
+            luminance_sq: self.luminance_sq.clone(),
+            samples: self.samples.clone(),
+            aovs,
+            pending: self.pending.clone(),
+            regions,
+        }
+    }
+
+    // after a reset, the chunks of the pass are restricted to the pending pixels
+    pub fn restore(&mut self, checkpoint: &Checkpoint) {
+        let size = self.samples.len();
+
+        self.accumulation.clone_from(&checkpoint.accumulation);
+        self.weights.clone_from(&checkpoint.weights);
+        self.luminance.clone_from(&checkpoint.luminance);
+        self.luminance_sq.clone_from(&checkpoint.luminance_sq);
+        self.samples.clone_from(&checkpoint.samples);
+        self.pending.clone_from(&checkpoint.pending);
+        self.aovs.depth.clone_from(&checkpoint.aovs.depth);
+        self.aovs.id.clone_from(&checkpoint.aovs.id);
+        self.aovs.normal.clone_from(&checkpoint.aovs.normal);
+        self.aovs.albedo.clone_from(&checkpoint.aovs.albedo);
+
+        for idx in 0..size {
+            self.update_pixel(idx);
+        }
+
+        let mut done = self
+            .pending
+            .iter()
+            .map(|pending| !pending)
+            .collect::<Vec<_>>();
+        for (pixels, passes) in &checkpoint.regions {
+            for &idx in pixels {
+                self.clear_pixel(idx);
+                done[idx] = true;
+            }
+
+            self.catch_up.push(CatchUp {
+                chunks: self.strategy.split(self.extent, pixels),
+                next: 0,
+                pass: 0,
+                passes: *passes,
+            });
+        }
+
+        self.strategy.remove(&done);
+    }
+
+    pub fn preview(&self, mode: PreviewMode) -> Vec<Color> {
+        match mode {
+            PreviewMode::COLOR => self.framebuffer.to_vec(),
+            PreviewMode::VARIANCE => {
+                let max = self.variance.iter().copied().fold(0., f32::max);
+                let scale = if max > 0. { 1. / max } else { 0. };
+
+                self.variance
+                    .iter()
+                    .map(|v| Self::heatmap(v * scale))
+                    .collect()
+            }
+            PreviewMode::ERROR => self
+                .framebuffer
+                .iter()
+                .zip(&self.variance)
+                .map(|(c, v)| {
+                    let luminance = c.luminance();
+                    if luminance > 0. {
+                        Self::heatmap(v.sqrt() / luminance)
+                    } else {
+                        Self::heatmap(0.)
+                    }
+                })
+                .collect(),
+            PreviewMode::DPDU => self.direction_preview(|d| d.dpdu),
+            PreviewMode::DPDV => self.direction_preview(|d| d.dpdv),
+            PreviewMode::DNDU => self.direction_preview(|d| d.dndu),
+            PreviewMode::DNDV => self.direction_preview(|d| d.dndv),
+        }
+    }
+
+    // world directions mapped to colors, black where nothing was hit or the
+    // derivative is zero
+    fn direction_preview(&self, derivative: impl Fn(&Differentials) -> Vec3) -> Vec<Color> {
+        self.aovs
+            .differentials
+            .iter()
+            .map(|d| {
+                let v = derivative(d).normalize_or_zero();
+                if v == Vec3::ZERO {
+                    Color::BLACK
+                } else {
+                    Color::new(0.5 + 0.5 * v.x, 0.5 + 0.5 * v.y, 0.5 + 0.5 * v.z, 1.)
+                }
+            })
+            .collect()
+    }
+
+    // blue (0) -> green -> red (1)
+    fn heatmap(value: f32) -> Color {
+        let value = value.clamp(0., 1.);
+
+        if value < 0.5 {
+            Color::new(0., 2. * value, 1. - 2. * value, 1.)
+        } else {
+            Color::new(2. * value - 1., 2. - 2. * value, 0., 1.)
+        }
+    }
+
+    pub fn is_pass_complete(&self) -> bool {
+        self.catch_up.is_empty() && self.strategy.is_complete()
+    }
+
+    // all the chunks left in the pass at once, None while a region catches up
+    #[cfg(feature = "gpu")]
+    pub fn take_pass(&mut self) -> Option<Vec<usize>> {
+        if !self.catch_up.is_empty() {
+            return None;
+        }
+
+        let mut pixels = Vec::new();
+        while !self.strategy.is_complete() {
+            pixels.extend(self.get_chunk().0);
+        }
+
+        Some(pixels)
+    }
+
+    // the pass of the samples is given for the chunks of a reset region, None for
+    // the current pass
+    pub fn get_chunk(&mut self) -> (Vec<usize>, Option<u32>) {
         log::debug!("Get chunk");
-        self.strategy.get_chunk()
+        let (chunk, pass) = match self.catch_up.last_mut() {
+            Some(catch_up) => {
+                let (chunk, pass) = catch_up.next();
+                if catch_up.is_complete() {
+                    self.catch_up.pop();
+                }
+                (chunk, Some(pass))
+            }
+            None => (self.strategy.get_chunk(), None),
+        };
+
+        self.chunk_count += 1;
+        for &idx in &chunk {
+            self.chunk_ids[idx] = self.chunk_count;
+        }
+
+        (chunk, pass)
+    }
+
+    // clears the pixels of the rectangle, they are removed from the chunks of the
+    // current pass and rendered again for the given number of passes
+    pub fn reset_region(&mut self, rect: (u32, u32, u32, u32), version: u64, passes: u32) {
+        let (x, y, width, height) = rect;
+        let x_max = x.saturating_add(width).min(self.extent.width);
+        let y_max = y.saturating_add(height).min(self.extent.height);
+
+        let mut stale = vec![false; self.samples.len()];
+        let mut pixels = Vec::new();
+
+        for py in y..y_max {
+            for px in x..x_max {
+                let idx = (px + py * self.extent.width) as usize;
+                stale[idx] = true;
+                pixels.push(idx);
+
+                self.clear_pixel(idx);
+                self.pending[idx] = false;
+                self.since[idx] = version;
+            }
+        }
+
+        if pixels.is_empty() {
+            return;
+        }
+
+        self.strategy.remove(&stale);
+
+        // the overlap of an older region is caught up with this one
+        for catch_up in self.catch_up.iter_mut() {
+            catch_up.remove(&stale);
+        }
+        self.catch_up.retain(|catch_up| !catch_up.is_complete());
+
+        if passes > 0 {
+            self.catch_up.push(CatchUp {
+                chunks: self.strategy.split(self.extent, &pixels),
+                next: 0,
+                pass: 0,
+                passes,
+            });
+        }
     }
+
+    // false if the pixel was reset after the chunk was dispatched
+    pub fn is_current(&self, idx: usize, version: u64) -> bool {
+        version >= self.since[idx]
+    }
+
+    pub fn debug_view(&self, view: DebugView) -> Vec<Color> {
+        let counts = &self.aovs.counts;
+
+        let heatmap = |value: fn(&TraversalCounts) -> u32| {
+            let max = counts.iter().map(value).max().unwrap_or(0);
+            let scale = if max > 0 { 1. / max as f32 } else { 0. };
+
+            counts
+                .iter()
+                .map(|c| Self::heatmap(value(c) as f32 * scale))
+                .collect()
+        };
+
+        match view {
+            DebugView::NONE => self.framebuffer.to_vec(),
+            DebugView::NODES => heatmap(|c| c.nodes),
+            DebugView::TESTS => heatmap(|c| c.tests),
+            DebugView::BOUNCES => heatmap(|c| c.bounces),
+            DebugView::CHUNKS => self.chunk_view(),
+            DebugView::TIMES => self.time_view(),
+        }
+    }
+
+    // the time of the tile of each pixel, relative to the slowest tile
+    fn time_view(&self) -> Vec<Color> {
+        let timings = self.tile_timings();
+        let max = timings.times.iter().copied().fold(0., f32::max);
+        let scale = if max > 0. { 1. / max } else { 0. };
+
+        (0..self.times.len() as u32)
+            .map(|idx| {
+                let x = idx % self.extent.width / Self::TILE_SIZE;
+                let y = idx / self.extent.width / Self::TILE_SIZE;
+
+                Self::heatmap(timings.time(x, y) * scale)
+            })
+            .collect()
+    }
+
+    // a hashed color per chunk, white on the boundaries
+    fn chunk_view(&self) -> Vec<Color> {
+        let width = self.extent.width as usize;
+
+        self.chunk_ids
+            .iter()
+            .enumerate()
+            .map(|(idx, &id)| {
+                let x = idx % width;
+                let boundary = (x + 1 < width && self.chunk_ids[idx + 1] != id)
+                    || (idx + width < self.chunk_ids.len() && self.chunk_ids[idx + width] != id);
+
+                if boundary {
+                    return Color::WHITE;
+                }
+                if id == 0 {
+                    return Color::BLACK;
+                }
+
+                let h = id.wrapping_mul(0x9e3779b9);
+                let channel = |shift: u32| 0.2 + 0.6 * ((h >> shift) & 0xff) as f32 / 255.;
+
+                Color::new(channel(8), channel(16), channel(24), 1.)
+            })
+            .collect()
+    }
+}
+
+// chunks of a region reset by ImageBuffer::reset_region, all the chunks are rendered
+// once for each pass
+struct CatchUp {
+    chunks: Vec<Vec<usize>>,
+    next: usize,
+    pass: u32,
+    passes: u32,
+}
+
+impl CatchUp {
+    fn next(&mut self) -> (Vec<usize>, u32) {
+        let chunk = self.chunks[self.next].clone();
+        let pass = self.pass;
+
+        self.next += 1;
+        if self.next == self.chunks.len() {
+            self.next = 0;
+            self.pass += 1;
+        }
+
+        (chunk, pass)
+    }
+
+    fn remove(&mut self, stale: &[bool]) {
+        let mut next = 0;
+        let mut chunks = Vec::new();
+
+        for (i, mut chunk) in self.chunks.drain(..).enumerate() {
+            chunk.retain(|&idx| !stale[idx]);
+            if chunk.is_empty() {
+                continue;
+            }
+            if i < self.next {
+                next += 1;
+            }
+            chunks.push(chunk);
+        }
+
+        self.chunks = chunks;
+        self.next = next;
+
+        // the rest of the current pass was removed
+        if self.next == self.chunks.len() {
+            self.next = 0;
+            self.pass += 1;
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.pass >= self.passes || self.chunks.is_empty()
+    }
+}
+
+// row stored first in exported images, the buffer itself always starts at the top
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ImageOrigin {
+    #[default]
+    TOP,
+    BOTTOM,
 }
 
+impl ImageOrigin {
+    pub fn orient<T: Clone>(&self, extent: Extent, pixels: &[T]) -> Vec<T> {
+        match self {
+            ImageOrigin::TOP => pixels.to_vec(),
+            ImageOrigin::BOTTOM => pixels
+                .chunks(extent.width as usize)
+                .rev()
+                .flatten()
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+// order in which the pixels are dispatched to the threads, sizes are in pixels
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "ChunkStrategyDesc")]
 pub enum ChunkStrategy {
+    RANDOM { pixels: usize },
+    LINE { pixels: usize },
+    BOX { width: u32, height: u32 },
+    // boxes from the center outwards
+    SPIRAL { width: u32, height: u32 },
+}
+
+impl Default for ChunkStrategy {
+    fn default() -> Self {
+        ChunkStrategy::BOX {
+            width: 128,
+            height: 128,
+        }
+    }
+}
+
+// the strategy alone, e.g. strategy = "BOX", comes with the default sizes
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChunkStrategyDesc {
+    NAME(ChunkStrategyName),
+    #[serde(with = "SizedChunkStrategy")]
+    SIZED(ChunkStrategy),
+}
+
+#[derive(Deserialize)]
+enum ChunkStrategyName {
     RANDOM,
     LINE,
     BOX,
+    SPIRAL,
+}
+
+#[derive(Deserialize)]
+#[serde(remote = "ChunkStrategy")]
+enum SizedChunkStrategy {
+    RANDOM { pixels: usize },
+    LINE { pixels: usize },
+    BOX { width: u32, height: u32 },
+    SPIRAL { width: u32, height: u32 },
+}
+
+impl From<ChunkStrategyDesc> for ChunkStrategy {
+    fn from(desc: ChunkStrategyDesc) -> Self {
+        match desc {
+            ChunkStrategyDesc::NAME(ChunkStrategyName::RANDOM) => {
+                ChunkStrategy::RANDOM { pixels: 20000 }
+            }
+            ChunkStrategyDesc::NAME(ChunkStrategyName::LINE) => {
+                ChunkStrategy::LINE { pixels: 1920 }
+            }
+            ChunkStrategyDesc::NAME(ChunkStrategyName::BOX) => ChunkStrategy::default(),
+            ChunkStrategyDesc::NAME(ChunkStrategyName::SPIRAL) => ChunkStrategy::SPIRAL {
+                width: 128,
+                height: 128,
+            },
+            ChunkStrategyDesc::SIZED(strategy) => strategy,
+        }
+    }
 }
 
 impl ChunkStrategy {
-    pub fn new(strategy: ChunkStrategy, extent: ImageExtent2D) -> ChunkStrategyData {
+    pub fn new(strategy: ChunkStrategy, extent: Extent) -> ChunkStrategyData {
         match strategy {
-            ChunkStrategy::RANDOM => ChunkStrategyData::RANDOM(RandomChunk::new()),
-            ChunkStrategy::LINE => ChunkStrategyData::LINE(LineChunk::new()),
-            ChunkStrategy::BOX => ChunkStrategyData::BOX(BoxChunk::new(extent)),
+            ChunkStrategy::RANDOM { pixels } => ChunkStrategyData::RANDOM(RandomChunk::new(pixels)),
+            ChunkStrategy::LINE { pixels } => ChunkStrategyData::LINE(LineChunk::new(pixels)),
+            ChunkStrategy::BOX { width, height } => {
+                ChunkStrategyData::BOX(BoxChunk::new(extent, width, height))
+            }
+            ChunkStrategy::SPIRAL { width, height } => {
+                ChunkStrategyData::SPIRAL(SpiralChunk::new(extent, width, height))
+            }
         }
     }
 }
@@ -68,28 +799,51 @@ pub enum ChunkStrategyData {
     RANDOM(RandomChunk),
     LINE(LineChunk),
     BOX(BoxChunk),
+    SPIRAL(SpiralChunk),
 }
 
 impl ChunkStrategyData {
-    pub fn reset(&mut self, extent: ImageExtent2D) {
+    pub fn reset(&mut self, extent: Extent, seed: u64) {
         match self {
             ChunkStrategyData::RANDOM(ref mut strategy) => {
-                strategy.reset(extent);
+                strategy.reset(extent, seed);
             }
             ChunkStrategyData::LINE(ref mut strategy) => {
-                strategy.reset(extent);
+                strategy.reset(extent, seed);
             }
             ChunkStrategyData::BOX(ref mut strategy) => {
-                strategy.reset(extent);
+                strategy.reset(extent, seed);
             }
+            ChunkStrategyData::SPIRAL(ref mut strategy) => {
+                strategy.reset(extent, seed);
+            }
+        }
+    }
+
+    // the remaining chunks closest to the pixel are returned first
+    pub fn focus(&mut self, extent: Extent, x: u32, y: u32) {
+        match self {
+            ChunkStrategyData::RANDOM(ref mut strategy) => strategy.focus(extent, x, y),
+            ChunkStrategyData::LINE(ref mut strategy) => strategy.focus(extent, y),
+            ChunkStrategyData::BOX(ref mut strategy) => strategy.focus(extent, x, y),
+            ChunkStrategyData::SPIRAL(ref mut strategy) => strategy.boxes.focus(extent, x, y),
         }
     }
 
+    fn distance(extent: Extent, idx: usize, x: u32, y: u32) -> u64 {
+        let px = (idx % extent.width as usize) as i64;
+        let py = (idx / extent.width as usize) as i64;
+        let (dx, dy) = (px - x as i64, py - y as i64);
+
+        (dx * dx + dy * dy) as u64
+    }
+
     pub fn is_complete(&self) -> bool {
         match self {
             ChunkStrategyData::RANDOM(ref strategy) => strategy.is_complete(),
             ChunkStrategyData::LINE(ref strategy) => strategy.is_complete(),
             ChunkStrategyData::BOX(ref strategy) => strategy.is_complete(),
+            ChunkStrategyData::SPIRAL(ref strategy) => strategy.is_complete(),
         }
     }
 
@@ -98,108 +852,182 @@ impl ChunkStrategyData {
             ChunkStrategyData::RANDOM(ref mut strategy) => strategy.get_chunk(),
             ChunkStrategyData::LINE(ref mut strategy) => strategy.get_chunk(),
             ChunkStrategyData::BOX(ref mut strategy) => strategy.get_chunk(),
+            ChunkStrategyData::SPIRAL(ref mut strategy) => strategy.get_chunk(),
+        }
+    }
+
+    // drops the stale pixels from the chunks left in the pass
+    pub fn remove(&mut self, stale: &[bool]) {
+        match self {
+            ChunkStrategyData::RANDOM(ref mut strategy) => {
+                strategy.draw_indexes.retain(|&idx| !stale[idx]);
+            }
+            ChunkStrategyData::LINE(ref mut strategy) => {
+                strategy.draw_indexes.retain(|&idx| !stale[idx]);
+            }
+            ChunkStrategyData::BOX(ref mut strategy) => strategy.remove(stale),
+            ChunkStrategyData::SPIRAL(ref mut strategy) => strategy.boxes.remove(stale),
+        }
+    }
+
+    // chunks of the strategy covering a subset of the pixels
+    pub fn split(&self, extent: Extent, pixels: &[usize]) -> Vec<Vec<usize>> {
+        match self {
+            ChunkStrategyData::RANDOM(strategy) => {
+                pixels.chunks(strategy.pixels).map(|c| c.to_vec()).collect()
+            }
+            ChunkStrategyData::LINE(strategy) => {
+                pixels.chunks(strategy.pixels).map(|c| c.to_vec()).collect()
+            }
+            ChunkStrategyData::BOX(strategy) => strategy.split(extent, pixels),
+            ChunkStrategyData::SPIRAL(strategy) => strategy.boxes.split(extent, pixels),
         }
     }
 }
 
 pub struct RandomChunk {
+    pixels: usize,
     draw_indexes: Vec<usize>,
+    // the pixels closest to it are selected when each chunk is taken, moving it
+    // doesn't sort the rest of the pass
+    focus: Option<(Extent, u32, u32)>,
 }
 
 impl RandomChunk {
-    const PIXEL_PER_CHUNK: usize = 20000;
-
-    pub fn new() -> Self {
+    pub fn new(pixels: usize) -> Self {
         Self {
+            pixels: pixels.max(1),
             draw_indexes: Vec::new(),
+            focus: None,
         }
     }
 
-    fn reset(&mut self, extent: ImageExtent2D) {
+    fn reset(&mut self, extent: Extent, seed: u64) {
         self.draw_indexes.clear();
+        self.focus = None;
 
         for i in 0..extent.size() {
             self.draw_indexes.push(i as usize);
         }
-        let mut rng = rand::thread_rng();
+        let mut rng = StdRng::seed_from_u64(seed);
         self.draw_indexes.shuffle(&mut rng)
     }
 
+    fn focus(&mut self, extent: Extent, x: u32, y: u32) {
+        self.focus = Some((extent, x, y));
+    }
+
     fn is_complete(&self) -> bool {
         self.draw_indexes.is_empty()
     }
 
     fn get_chunk(&mut self) -> Vec<usize> {
-        self.draw_indexes
-            .drain(0..Self::PIXEL_PER_CHUNK.min(self.draw_indexes.len()))
-            .collect::<Vec<usize>>()
+        let n = self.pixels.min(self.draw_indexes.len());
+
+        if let Some((extent, x, y)) = self.focus {
+            if n > 0 && n < self.draw_indexes.len() {
+                self.draw_indexes.select_nth_unstable_by_key(n - 1, |&idx| {
+                    ChunkStrategyData::distance(extent, idx, x, y)
+                });
+            }
+        }
+
+        self.draw_indexes.drain(0..n).collect::<Vec<usize>>()
     }
 }
 
 pub struct LineChunk {
+    pixels: usize,
     draw_indexes: Vec<usize>,
+    // width of the image and row, the closest lines are selected when each chunk is
+    // taken as with RandomChunk
+    focus: Option<(usize, usize)>,
 }
 
 impl LineChunk {
-    const PIXEL_PER_CHUNK: usize = 1920;
-
-    pub fn new() -> Self {
+    pub fn new(pixels: usize) -> Self {
         Self {
+            pixels: pixels.max(1),
             draw_indexes: Vec::new(),
+            focus: None,
         }
     }
 
-    fn reset(&mut self, extent: ImageExtent2D) {
+    fn reset(&mut self, extent: Extent, _seed: u64) {
         self.draw_indexes.clear();
+        self.focus = None;
 
         for i in 0..extent.size() {
             self.draw_indexes.push(i as usize);
         }
     }
 
+    fn focus(&mut self, extent: Extent, y: u32) {
+        self.focus = Some((extent.width as usize, y as usize));
+    }
+
     fn is_complete(&self) -> bool {
         self.draw_indexes.is_empty()
     }
 
+    // closest lines first, the order inside a line is kept
     fn get_chunk(&mut self) -> Vec<usize> {
-        self.draw_indexes
-            .drain(0..Self::PIXEL_PER_CHUNK.min(self.draw_indexes.len()))
-            .collect::<Vec<usize>>()
+        let n = self.pixels.min(self.draw_indexes.len());
+
+        if let Some((width, y)) = self.focus {
+            let key = |idx: &usize| ((idx / width).abs_diff(y), *idx);
+            if n > 0 && n < self.draw_indexes.len() {
+                self.draw_indexes.select_nth_unstable_by_key(n - 1, key);
+            }
+            self.draw_indexes[..n].sort_unstable_by_key(key);
+        }
+
+        self.draw_indexes.drain(0..n).collect::<Vec<usize>>()
     }
 }
 
 pub struct BoxChunk {
+    width: u32,
+    height: u32,
     cols: u32,
     rows: u32,
     draw_boxes: Vec<Vec<usize>>,
 }
 
 impl BoxChunk {
-    const BOX_WIDTH: u32 = 128;
-    const BOX_HEIGHT: u32 = 128;
-
-    pub fn new(extent: ImageExtent2D) -> Self {
-        let cols = extent.width.div_ceil(Self::BOX_WIDTH);
-        let rows = extent.height.div_ceil(Self::BOX_HEIGHT);
+    pub fn new(extent: Extent, width: u32, height: u32) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        let cols = extent.width.div_ceil(width);
+        let rows = extent.height.div_ceil(height);
 
         Self {
+            width,
+            height,
             cols,
             rows,
             draw_boxes: Vec::new(),
         }
     }
 
-    pub fn reset(&mut self, extent: ImageExtent2D) {
+    pub fn reset(&mut self, extent: Extent, seed: u64) {
+        self.fill(extent);
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        self.draw_boxes.shuffle(&mut rng)
+    }
+
+    // boxes in row order
+    fn fill(&mut self, extent: Extent) {
         self.draw_boxes.clear();
 
         for j in 0..self.rows {
             for i in 0..self.cols {
                 let mut chunk = Vec::new();
 
-                let x_min = i * Self::BOX_WIDTH;
-                let x_max = (x_min + Self::BOX_WIDTH).min(extent.width);
-                let y_min = j * Self::BOX_HEIGHT;
-                let y_max = (y_min + Self::BOX_HEIGHT).min(extent.height);
+                let x_min = i * self.width;
+                let x_max = (x_min + self.width).min(extent.width);
+                let y_min = j * self.height;
+                let y_max = (y_min + self.height).min(extent.height);
 
                 for x in x_min..x_max {
                     for y in y_min..y_max {
@@ -209,9 +1037,21 @@ impl BoxChunk {
                 self.draw_boxes.push(chunk)
             }
         }
+    }
 
-        let mut rng = rand::thread_rng();
-        self.draw_boxes.shuffle(&mut rng)
+    // boxes are popped from the end, the closest goes last
+    fn focus(&mut self, extent: Extent, x: u32, y: u32) {
+        let width = extent.width as usize;
+
+        self.draw_boxes.sort_by_key(|chunk| {
+            // first and last pixels are opposite corners of the box
+            let first = chunk.first().copied().unwrap_or(0);
+            let last = chunk.last().copied().unwrap_or(0);
+            let cx = (first % width + last % width) / 2;
+            let cy = (first / width + last / width) / 2;
+
+            std::cmp::Reverse(ChunkStrategyData::distance(extent, cx + cy * width, x, y))
+        });
     }
 
     fn is_complete(&self) -> bool {
@@ -225,4 +1065,73 @@ impl BoxChunk {
 
         chunk
     }
+
+    fn remove(&mut self, stale: &[bool]) {
+        for chunk in self.draw_boxes.iter_mut() {
+            chunk.retain(|&idx| !stale[idx]);
+        }
+        self.draw_boxes.retain(|chunk| !chunk.is_empty());
+    }
+
+    // pixels grouped by box, in row order
+    fn split(&self, extent: Extent, pixels: &[usize]) -> Vec<Vec<usize>> {
+        let width = extent.width as usize;
+        let mut boxes = vec![Vec::new(); (self.cols * self.rows) as usize];
+
+        for &idx in pixels {
+            let i = (idx % width) as u32 / self.width;
+            let j = (idx / width) as u32 / self.height;
+            boxes[(i + j * self.cols) as usize].push(idx);
+        }
+        boxes.retain(|chunk| !chunk.is_empty());
+
+        boxes
+    }
+}
+
+pub struct SpiralChunk {
+    boxes: BoxChunk,
+}
+
+impl SpiralChunk {
+    pub fn new(extent: Extent, width: u32, height: u32) -> Self {
+        Self {
+            boxes: BoxChunk::new(extent, width, height),
+        }
+    }
+
+    pub fn reset(&mut self, extent: Extent, _seed: u64) {
+        self.boxes.fill(extent);
+
+        let (cols, rows) = (self.boxes.cols, self.boxes.rows);
+
+        // rings of boxes around the center, turning inside each ring
+        let key = |k: usize| {
+            let dx = (k as u32 % cols) as f32 + 0.5 - cols as f32 / 2.;
+            let dy = (k as u32 / cols) as f32 + 0.5 - rows as f32 / 2.;
+
+            (dx.abs().max(dy.abs()).round(), dy.atan2(dx))
+        };
+
+        let mut boxes = self
+            .boxes
+            .draw_boxes
+            .drain(..)
+            .enumerate()
+            .map(|(k, chunk)| (key(k), chunk))
+            .collect::<Vec<_>>();
+
+        // chunks are popped from the end, the center goes last
+        boxes.sort_by(|(a, _), (b, _)| b.0.total_cmp(&a.0).then(b.1.total_cmp(&a.1)));
+
+        self.boxes.draw_boxes = boxes.into_iter().map(|(_, chunk)| chunk).collect();
+    }
+
+    fn is_complete(&self) -> bool {
+        self.boxes.is_complete()
+    }
+
+    fn get_chunk(&mut self) -> Vec<usize> {
+        self.boxes.get_chunk()
+    }
 }
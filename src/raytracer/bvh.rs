@@ -0,0 +1,439 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    sync::Arc,
+};
+
+use glam::Vec3;
+
+use crate::raytracer::{aabb::Aabb, stats, Hit, Hitable, Primitive, Ray};
+
+enum BvhNode {
+    Leaf {
+        bounds: Aabb,
+        items: Vec<usize>,
+    },
+    Inner {
+        bounds: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bounds, .. } => bounds,
+            BvhNode::Inner { bounds, .. } => bounds,
+        }
+    }
+}
+
+// nodes left to visit by a traversal, kept on the stack of the thread unless the
+// tree is deeper than INLINE
+struct NodeStack {
+    inline: [usize; NodeStack::INLINE],
+    len: usize,
+    spill: Vec<usize>,
+}
+
+impl NodeStack {
+    const INLINE: usize = 64;
+
+    // starts at the root
+    fn new() -> Self {
+        Self {
+            inline: [0; Self::INLINE],
+            len: 1,
+            spill: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, idx: usize) {
+        if self.len < Self::INLINE {
+            self.inline[self.len] = idx;
+        } else {
+            self.spill.push(idx);
+        }
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+
+        if self.len < Self::INLINE {
+            Some(self.inline[self.len])
+        } else {
+            self.spill.pop()
+        }
+    }
+}
+
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+}
+
+impl Bvh {
+    const LEAF_SIZE: usize = 2;
+
+    // "BVH" + format version
+    const MAGIC: [u8; 4] = *b"BVH1";
+    // magic, key and node count
+    const HEADER_BYTES: u64 = 16;
+    // tag, bounds and item count of an empty leaf
+    const MIN_NODE_BYTES: u64 = 29;
+
+    pub fn new(models: &[Arc<Primitive>]) -> Self {
+        let bounds = models.iter().map(|m| m.bounds()).collect::<Vec<Aabb>>();
+
+        Self::from_bounds(&bounds)
+    }
+
+    // items are the indexes in bounds
+    pub fn from_bounds(bounds: &[Aabb]) -> Self {
+        let mut bvh = Self { nodes: Vec::new() };
+
+        let items = (0..bounds.len()).collect::<Vec<usize>>();
+
+        if !items.is_empty() {
+            bvh.build(bounds, items);
+        }
+
+        log::debug!("BVH: {} nodes", bvh.nodes.len());
+
+        bvh
+    }
+
+    fn build(&mut self, bounds: &[Aabb], mut items: Vec<usize>) -> usize {
+        let node_bounds = items
+            .iter()
+            .fold(Aabb::empty(), |acc, &i| acc.union(&bounds[i]));
+
+        let idx = self.nodes.len();
+
+        if items.len() <= Self::LEAF_SIZE {
+            self.nodes.push(BvhNode::Leaf {
+                bounds: node_bounds,
+                items,
+            });
+            return idx;
+        }
+
+        // median split along the largest axis of the centroids
+        let centroid_bounds = items
+            .iter()
+            .fold(Aabb::empty(), |acc, &i| acc.grow(bounds[i].centroid()));
+        let axis = centroid_bounds.largest_axis();
+
+        items.sort_by(|&a, &b| bounds[a].centroid()[axis].total_cmp(&bounds[b].centroid()[axis]));
+
+        let right_items = items.split_off(items.len() / 2);
+
+        // reserve the slot, children are filled in once built
+        self.nodes.push(BvhNode::Leaf {
+            bounds: node_bounds,
+            items: Vec::new(),
+        });
+
+        let left = self.build(bounds, items);
+        let right = self.build(bounds, right_items);
+
+        self.nodes[idx] = BvhNode::Inner {
+            bounds: node_bounds,
+            left,
+            right,
+        };
+
+        idx
+    }
+
+    pub fn hit(&self, models: &[Arc<Primitive>], ray: &Ray, min: f32, max: f32) -> Option<Hit> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut closest: Option<Hit> = None;
+        let mut max = max;
+        let (mut nodes, mut tests) = (0, 0);
+
+        let mut stack = NodeStack::new();
+
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            nodes += 1;
+
+            if node.bounds().hit(ray, min, max).is_none() {
+                continue;
+            }
+
+            match node {
+                BvhNode::Leaf { items, .. } => {
+                    tests += items.len() as u32;
+                    for &i in items {
+                        if let Some(hit) = models[i].hit(ray, min, max) {
+                            max = hit.distance;
+                            closest = Some(hit.with_id(i));
+                        }
+                    }
+                }
+                BvhNode::Inner { left, right, .. } => {
+                    stack.push(*right);
+                    stack.push(*left);
+                }
+            }
+        }
+
+        stats::record_traversal(nodes, tests);
+
+        closest
+    }
+
+    // nodes are visited once for the whole packet and skipped when no ray hits them
+    pub fn hit_packet(
+        &self,
+        models: &[Arc<Primitive>],
+        rays: &[Ray],
+        min: f32,
+        max: f32,
+    ) -> Vec<Option<Hit>> {
+        let mut hits: Vec<Option<Hit>> = vec![None; rays.len()];
+
+        if self.nodes.is_empty() {
+            return hits;
+        }
+
+        let mut max = vec![max; rays.len()];
+
+        let mut stack = NodeStack::new();
+
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+
+            let active = rays
+                .iter()
+                .zip(&max)
+                .any(|(ray, max)| node.bounds().hit(ray, min, *max).is_some());
+
+            if !active {
+                continue;
+            }
+
+            match node {
+                BvhNode::Leaf { items, .. } => {
+                    for (k, ray) in rays.iter().enumerate() {
+                        for &i in items {
+                            if let Some(hit) = models[i].hit(ray, min, max[k]) {
+                                max[k] = hit.distance;
+                                hits[k] = Some(hit.with_id(i));
+                            }
+                        }
+                    }
+                }
+                BvhNode::Inner { left, right, .. } => {
+                    stack.push(*right);
+                    stack.push(*left);
+                }
+            }
+        }
+
+        hits
+    }
+
+    pub fn occluded(&self, models: &[Arc<Primitive>], ray: &Ray, min: f32, max: f32) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let (mut nodes, mut tests) = (0, 0);
+
+        let mut stack = NodeStack::new();
+
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            nodes += 1;
+
+            if node.bounds().hit(ray, min, max).is_none() {
+                continue;
+            }
+
+            match node {
+                BvhNode::Leaf { items, .. } => {
+                    let occluded = items.iter().any(|&i| {
+                        tests += 1;
+                        models[i].hit_distance(ray, min, max).is_some()
+                    });
+                    if occluded {
+                        stats::record_traversal(nodes, tests);
+                        return true;
+                    }
+                }
+                BvhNode::Inner { left, right, .. } => {
+                    stack.push(*right);
+                    stack.push(*left);
+                }
+            }
+        }
+
+        stats::record_traversal(nodes, tests);
+
+        false
+    }
+
+    // calls hit for the items of the leaves crossed by the ray, hit returns the
+    // distance of the intersection which then bounds the rest of the traversal
+    pub fn visit<F>(&self, ray: &Ray, min: f32, max: f32, mut hit: F)
+    where
+        F: FnMut(usize, f32) -> Option<f32>,
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut max = max;
+        let (mut nodes, mut tests) = (0, 0);
+
+        let mut stack = NodeStack::new();
+
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            nodes += 1;
+
+            if node.bounds().hit(ray, min, max).is_none() {
+                continue;
+            }
+
+            match node {
+                BvhNode::Leaf { items, .. } => {
+                    tests += items.len() as u32;
+                    for &i in items {
+                        if let Some(t) = hit(i, max) {
+                            max = t;
+                        }
+                    }
+                }
+                BvhNode::Inner { left, right, .. } => {
+                    stack.push(*right);
+                    stack.push(*left);
+                }
+            }
+        }
+
+        stats::record_traversal(nodes, tests);
+    }
+
+    // little endian: magic, key (u64), node count (u32), then for each node a tag (u8),
+    // the bounds (6 f32) and either the items (count + u32s) or the children (2 u32)
+    pub fn save(&self, path: &str, key: u64) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(&Self::MAGIC)?;
+        writer.write_all(&key.to_le_bytes())?;
+        writer.write_all(&(self.nodes.len() as u32).to_le_bytes())?;
+
+        for node in &self.nodes {
+            let bounds = node.bounds();
+
+            writer.write_all(&[matches!(node, BvhNode::Inner { .. }) as u8])?;
+            for v in bounds.min.to_array().iter().chain(&bounds.max.to_array()) {
+                writer.write_all(&v.to_le_bytes())?;
+            }
+
+            match node {
+                BvhNode::Leaf { items, .. } => {
+                    writer.write_all(&(items.len() as u32).to_le_bytes())?;
+                    for &i in items {
+                        writer.write_all(&(i as u32).to_le_bytes())?;
+                    }
+                }
+                BvhNode::Inner { left, right, .. } => {
+                    writer.write_all(&(*left as u32).to_le_bytes())?;
+                    writer.write_all(&(*right as u32).to_le_bytes())?;
+                }
+            }
+        }
+
+        writer.flush()
+    }
+
+    // fails if the file was written for another key or refers to more than n_items
+    pub fn load(path: &str, key: u64, n_items: usize) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != Self::MAGIC {
+            return Err(invalid("not a bvh file"));
+        }
+
+        if read_u64(&mut reader)? != key {
+            return Err(invalid("bvh built for other content"));
+        }
+
+        let n_nodes = read_u32(&mut reader)? as usize;
+        if len.saturating_sub(Self::HEADER_BYTES) / Self::MIN_NODE_BYTES < n_nodes as u64 {
+            return Err(invalid("truncated bvh file"));
+        }
+        let mut nodes = Vec::with_capacity(n_nodes);
+
+        for idx in 0..n_nodes {
+            let mut tag = [0; 1];
+            reader.read_exact(&mut tag)?;
+
+            let mut v = [0.; 6];
+            for x in v.iter_mut() {
+                *x = f32::from_bits(read_u32(&mut reader)?);
+            }
+            let bounds = Aabb::new(Vec3::new(v[0], v[1], v[2]), Vec3::new(v[3], v[4], v[5]));
+
+            let node = if tag[0] == 0 {
+                let count = read_u32(&mut reader)? as usize;
+                let items = (0..count)
+                    .map(|_| read_u32(&mut reader).map(|i| i as usize))
+                    .collect::<io::Result<Vec<_>>>()?;
+                if items.iter().any(|&i| i >= n_items) {
+                    return Err(invalid("bvh item out of range"));
+                }
+
+                BvhNode::Leaf { bounds, items }
+            } else {
+                let left = read_u32(&mut reader)? as usize;
+                let right = read_u32(&mut reader)? as usize;
+                // children are always stored after their parent
+                if left <= idx || right <= idx || left >= n_nodes || right >= n_nodes {
+                    return Err(invalid("bvh node out of range"));
+                }
+
+                BvhNode::Inner {
+                    bounds,
+                    left,
+                    right,
+                }
+            };
+
+            nodes.push(node);
+        }
+
+        Ok(Self { nodes })
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u64::from_le_bytes(bytes))
+}
@@ -0,0 +1,212 @@
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::raytracer::Ray;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProjectionMode {
+    PERSPECTIVE {
+        aspect: f32,
+        fovy: f32,
+        near: f32,
+        far: f32,
+    },
+    ORTHO {
+        width: f32,
+        height: f32,
+        near: f32,
+        far: f32,
+    },
+    // rays are generated by unprojecting the pixels with the inverse of the matrices
+    MATRIX {
+        inverse_view_proj: Mat4,
+        perspective: bool,
+        // depth of the near plane in ndc, 1 with reversed depth
+        near_z: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+impl ProjectionMode {
+    pub fn near(&self) -> f32 {
+        match *self {
+            ProjectionMode::PERSPECTIVE { near, .. } => near,
+            ProjectionMode::ORTHO { near, .. } => near,
+            ProjectionMode::MATRIX { near, .. } => near,
+        }
+    }
+
+    pub fn far(&self) -> f32 {
+        match *self {
+            ProjectionMode::PERSPECTIVE { far, .. } => far,
+            ProjectionMode::ORTHO { far, .. } => far,
+            ProjectionMode::MATRIX { far, .. } => far,
+        }
+    }
+
+    // half size of the image plane for an image of this aspect, at a distance of 1
+    // in perspective and in world units in ortho, the height of the view is kept
+    pub fn half_plane(&self, aspect: f32) -> Vec2 {
+        match *self {
+            ProjectionMode::PERSPECTIVE { fovy, .. } => {
+                let h = (fovy / 2.).tan();
+                Vec2::new(h * aspect, h)
+            }
+            ProjectionMode::ORTHO { height, .. } => Vec2::new(height * aspect, height) / 2.,
+            ProjectionMode::MATRIX { .. } => Vec2::ONE,
+        }
+    }
+}
+
+// angles in radians, same conventions as the gobs camera
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    pub mode: ProjectionMode,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub up: Vec3,
+}
+
+impl Camera {
+    #[allow(clippy::too_many_arguments)]
+    pub fn perspective(
+        position: Vec3,
+        aspect: f32,
+        fovy: f32,
+        near: f32,
+        far: f32,
+        yaw: f32,
+        pitch: f32,
+        up: Vec3,
+    ) -> Self {
+        Self {
+            position,
+            mode: ProjectionMode::PERSPECTIVE {
+                aspect,
+                fovy,
+                near,
+                far,
+            },
+            yaw,
+            pitch,
+            up,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn ortho(
+        position: Vec3,
+        width: f32,
+        height: f32,
+        near: f32,
+        far: f32,
+        yaw: f32,
+        pitch: f32,
+        up: Vec3,
+    ) -> Self {
+        Self {
+            position,
+            mode: ProjectionMode::ORTHO {
+                width,
+                height,
+                near,
+                far,
+            },
+            yaw,
+            pitch,
+            up,
+        }
+    }
+
+    // same camera for an image of another shape, the height of the view is kept,
+    // the matrices of the host are left as they are
+    pub fn with_aspect(mut self, aspect: f32) -> Self {
+        match &mut self.mode {
+            ProjectionMode::PERSPECTIVE { aspect: a, .. } => *a = aspect,
+            ProjectionMode::ORTHO { width, height, .. } => *width = *height * aspect,
+            ProjectionMode::MATRIX { .. } => (),
+        }
+
+        self
+    }
+
+    // view and projection as used by the rasterizer of the host engine, with a
+    // depth range of 0..1 (reversed depth is supported)
+    pub fn from_view_proj(view: Mat4, proj: Mat4) -> Self {
+        let inverse_view = view.inverse();
+        let inverse_proj = proj.inverse();
+
+        let position = inverse_view.transform_point3(Vec3::ZERO);
+
+        // distances of the depth planes along the view axis
+        let depth = |z: f32| {
+            let d = -inverse_proj.project_point3(Vec3::new(0., 0., z)).z;
+            if d.is_finite() {
+                d
+            } else {
+                f32::MAX
+            }
+        };
+        let reversed = depth(0.) > depth(1.);
+        let (near, far) = match reversed {
+            true => (depth(1.), depth(0.)),
+            false => (depth(0.), depth(1.)),
+        };
+
+        let forward = inverse_view.transform_vector3(Vec3::NEG_Z).normalize();
+
+        Self {
+            position,
+            mode: ProjectionMode::MATRIX {
+                inverse_view_proj: inverse_view * inverse_proj,
+                // the last row of a perspective projection is (0, 0, -1, 0)
+                perspective: proj.w_axis.w == 0.,
+                near_z: if reversed { 1. } else { 0. },
+                near,
+                far,
+            },
+            yaw: forward.z.atan2(forward.x),
+            pitch: forward.y.clamp(-1., 1.).asin(),
+            up: inverse_view.transform_vector3(Vec3::Y).normalize(),
+        }
+    }
+
+    // ray through a point of the screen in normalized device coordinates,
+    // only for cameras built from matrices
+    pub fn ray(&self, ndc: Vec2) -> Option<Ray> {
+        let ProjectionMode::MATRIX {
+            inverse_view_proj,
+            perspective,
+            near_z,
+            near,
+            ..
+        } = self.mode
+        else {
+            return None;
+        };
+
+        // the middle of the depth range stays finite with an infinite far plane
+        let a = inverse_view_proj.project_point3(ndc.extend(near_z));
+        let b = inverse_view_proj.project_point3(ndc.extend(0.5));
+        let direction = (b - a).normalize();
+
+        // the rays start at the eye so that near is the distance to the first hit
+        let origin = if perspective {
+            self.position
+        } else {
+            a - direction * near
+        };
+
+        Some(Ray::new(origin, direction))
+    }
+
+    pub fn direction(&self) -> Vec3 {
+        Vec3::new(
+            self.yaw.cos() * self.pitch.cos(),
+            self.pitch.sin(),
+            self.yaw.sin() * self.pitch.cos(),
+        )
+        .normalize()
+    }
+}
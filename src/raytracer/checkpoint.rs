@@ -0,0 +1,202 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+
+use glam::Vec3;
+
+use crate::raytracer::{aov::Aovs, Color, Extent};
+
+// accumulated samples of the main camera with the pixels left in the current pass,
+// see Tracer::save_checkpoint and TracerBuilder::resume_from
+pub struct Checkpoint {
+    pub extent: Extent,
+    pub pass: u32,
+    pub n_rays: u32,
+    // seconds
+    pub render_time: f32,
+    pub accumulation: Vec<Color>,
+    pub weights: Vec<f32>,
+    pub luminance: Vec<f32>,
+    pub luminance_sq: Vec<f32>,
+    pub samples: Vec<u32>,
+    // only the depth, id, normal and albedo are kept
+    pub aovs: Aovs,
+    // pixels without the samples of the current pass, including the chunks in flight
+    pub pending: Vec<bool>,
+    // pixels of the invalidated regions still catching up, with their number of passes
+    pub regions: Vec<(Vec<usize>, u32)>,
+}
+
+impl Checkpoint {
+    const MAGIC: [u8; 4] = *b"CKP2";
+    // magic, extent, pass, rays and render time
+    const HEADER_BYTES: u64 = 24;
+    // see save
+    const PIXEL_BYTES: u64 = 4 * 7 + 4 + 1 + 4 + 4 + 4 * 7;
+
+    // little endian: magic, width, height, pass, rays (u32), render time (f32), then
+    // for each pixel the accumulation (4 f32), weight, luminance, luminance squared
+    // (3 f32), samples (u32), pending (u8), depth (f32), id (u32), normal (3 f32),
+    // albedo (4 f32) and last the regions: count (u32), then passes, pixel count and
+    // pixels
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(&Self::MAGIC)?;
+        for v in [
+            self.extent.width,
+            self.extent.height,
+            self.pass,
+            self.n_rays,
+        ] {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+        writer.write_all(&self.render_time.to_le_bytes())?;
+
+        for idx in 0..self.samples.len() {
+            let c = self.accumulation[idx];
+            let albedo = self.aovs.albedo[idx];
+            let normal = self.aovs.normal[idx];
+            let values = [
+                c.r,
+                c.g,
+                c.b,
+                c.a,
+                self.weights[idx],
+                self.luminance[idx],
+                self.luminance_sq[idx],
+            ];
+            for v in values {
+                writer.write_all(&v.to_le_bytes())?;
+            }
+            writer.write_all(&self.samples[idx].to_le_bytes())?;
+            writer.write_all(&[self.pending[idx] as u8])?;
+            writer.write_all(&self.aovs.depth[idx].to_le_bytes())?;
+            writer.write_all(&self.aovs.id[idx].to_le_bytes())?;
+            for v in normal
+                .to_array()
+                .iter()
+                .chain(&[albedo.r, albedo.g, albedo.b, albedo.a])
+            {
+                writer.write_all(&v.to_le_bytes())?;
+            }
+        }
+
+        writer.write_all(&(self.regions.len() as u32).to_le_bytes())?;
+        for (pixels, passes) in &self.regions {
+            writer.write_all(&passes.to_le_bytes())?;
+            writer.write_all(&(pixels.len() as u32).to_le_bytes())?;
+            for &idx in pixels {
+                writer.write_all(&(idx as u32).to_le_bytes())?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    // the extent is checked against the expected one and the file length before
+    // anything is allocated
+    pub fn load(path: &str, expected: Extent) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != Self::MAGIC {
+            return Err(invalid("not a checkpoint file"));
+        }
+
+        let extent = Extent::new(read_u32(&mut reader)?, read_u32(&mut reader)?);
+        if extent != expected {
+            return Err(invalid("checkpoint rendered with another extent"));
+        }
+        let size = extent.width as u64 * extent.height as u64;
+        if len < Self::HEADER_BYTES + size * Self::PIXEL_BYTES {
+            return Err(invalid("truncated checkpoint"));
+        }
+
+        let pass = read_u32(&mut reader)?;
+        let n_rays = read_u32(&mut reader)?;
+        let render_time = read_f32(&mut reader)?;
+
+        let size = size as usize;
+        let mut accumulation = Vec::with_capacity(size);
+        let mut weights = Vec::with_capacity(size);
+        let mut luminance = Vec::with_capacity(size);
+        let mut luminance_sq = Vec::with_capacity(size);
+        let mut samples = Vec::with_capacity(size);
+        let mut pending = Vec::with_capacity(size);
+        let mut aovs = Aovs::new(size);
+
+        for idx in 0..size {
+            let mut v = [0.; 7];
+            for x in v.iter_mut() {
+                *x = read_f32(&mut reader)?;
+            }
+            accumulation.push(Color::new(v[0], v[1], v[2], v[3]));
+            weights.push(v[4]);
+            luminance.push(v[5]);
+            luminance_sq.push(v[6]);
+            samples.push(read_u32(&mut reader)?);
+
+            let mut flag = [0; 1];
+            reader.read_exact(&mut flag)?;
+            pending.push(flag[0] != 0);
+
+            aovs.depth[idx] = read_f32(&mut reader)?;
+            aovs.id[idx] = read_u32(&mut reader)?;
+
+            let mut v = [0.; 7];
+            for x in v.iter_mut() {
+                *x = read_f32(&mut reader)?;
+            }
+            aovs.normal[idx] = Vec3::new(v[0], v[1], v[2]);
+            aovs.albedo[idx] = Color::new(v[3], v[4], v[5], v[6]);
+        }
+
+        let n_regions = read_u32(&mut reader)? as usize;
+        let mut regions = Vec::new();
+        for _ in 0..n_regions {
+            let passes = read_u32(&mut reader)?;
+            let count = read_u32(&mut reader)? as usize;
+            let pixels = (0..count)
+                .map(|_| read_u32(&mut reader).map(|i| i as usize))
+                .collect::<io::Result<Vec<_>>>()?;
+            if pixels.iter().any(|&i| i >= size) {
+                return Err(invalid("checkpoint pixel out of range"));
+            }
+
+            regions.push((pixels, passes));
+        }
+
+        Ok(Self {
+            extent,
+            pass,
+            n_rays,
+            render_time,
+            accumulation,
+            weights,
+            luminance,
+            luminance_sq,
+            samples,
+            aovs,
+            pending,
+            regions,
+        })
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    Ok(f32::from_bits(read_u32(reader)?))
+}
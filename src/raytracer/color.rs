@@ -0,0 +1,108 @@
+use std::ops::{Add, Div, Mul};
+
+// linear rgba, alpha is carried along but not premultiplied
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::new(0., 0., 0., 1.);
+    pub const WHITE: Color = Color::new(1., 1., 1., 1.);
+    pub const GREY: Color = Color::new(0.5, 0.5, 0.5, 1.);
+    pub const RED: Color = Color::new(1., 0., 0., 1.);
+    pub const GREEN: Color = Color::new(0., 1., 0., 1.);
+    pub const BLUE: Color = Color::new(0., 0., 1., 1.);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    // chromaticity of a black body from 1667K to 25000K, brightest channel at 1
+    pub fn from_temperature(kelvin: f32) -> Self {
+        let t = kelvin.clamp(1667., 25000.);
+
+        // Planckian locus approximation from Kim et al.
+        let x = if t <= 4000. {
+            -0.2661239e9 / t.powi(3) - 0.2343589e6 / t.powi(2) + 0.8776956e3 / t + 0.179910
+        } else {
+            -3.0258469e9 / t.powi(3) + 2.107038e6 / t.powi(2) + 0.2226347e3 / t + 0.240390
+        };
+        let y = if t <= 2222. {
+            -1.106381 * x.powi(3) - 1.34811 * x.powi(2) + 2.185558 * x - 0.2021968
+        } else if t <= 4000. {
+            -0.9549476 * x.powi(3) - 1.374186 * x.powi(2) + 2.09137 * x - 0.1674887
+        } else {
+            3.081758 * x.powi(3) - 5.873387 * x.powi(2) + 3.75113 * x - 0.3700148
+        };
+
+        // xyY with Y = 1 to linear sRGB
+        let (cx, cy, cz) = (x / y, 1., (1. - x - y) / y);
+        let r = (3.2406 * cx - 1.5372 * cy - 0.4986 * cz).max(0.);
+        let g = (-0.9689 * cx + 1.8758 * cy + 0.0415 * cz).max(0.);
+        let b = (0.0557 * cx - 0.2040 * cy + 1.0570 * cz).max(0.);
+        let max = r.max(g).max(b);
+
+        Color::new(r / max, g / max, b / max, 1.)
+    }
+}
+
+impl Add for Color {
+    type Output = Color;
+
+    fn add(self, rhs: Color) -> Color {
+        Color::new(
+            self.r + rhs.r,
+            self.g + rhs.g,
+            self.b + rhs.b,
+            self.a + rhs.a,
+        )
+    }
+}
+
+impl Mul<f32> for Color {
+    type Output = Color;
+
+    fn mul(self, rhs: f32) -> Color {
+        Color::new(self.r * rhs, self.g * rhs, self.b * rhs, self.a * rhs)
+    }
+}
+
+impl Div<f32> for Color {
+    type Output = Color;
+
+    fn div(self, rhs: f32) -> Color {
+        Color::new(self.r / rhs, self.g / rhs, self.b / rhs, self.a / rhs)
+    }
+}
+
+impl From<Color> for [u8; 4] {
+    fn from(color: Color) -> [u8; 4] {
+        let quantize = |x: f32| (x.clamp(0., 1.) * 255.).round() as u8;
+
+        [
+            quantize(color.r),
+            quantize(color.g),
+            quantize(color.b),
+            quantize(color.a),
+        ]
+    }
+}
+
+pub trait ColorExt {
+    fn modulate(self, other: Color) -> Color;
+    fn luminance(self) -> f32;
+}
+
+impl ColorExt for Color {
+    fn modulate(self, other: Color) -> Color {
+        Color::new(self.r * other.r, self.g * other.g, self.b * other.b, self.a)
+    }
+
+    fn luminance(self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+}
@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+use crate::raytracer::{
+    AccelKind, ChunkStrategy, DownsampleFilter, ImageOrigin, Integrator, LightSampling, Palette,
+    PixelFilter, SamplerKind, Tonemap,
+};
+
+// the settings added over time are optional, missing ones take the defaults of
+// TracerBuilder::new
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TracerConfig {
+    pub width: u32,
+    pub height: u32,
+    pub rays: u32,
+    pub reflects: u32,
+    pub threads: u32,
+    #[serde(default)]
+    pub target_samples: Option<u32>,
+    // seconds
+    #[serde(default)]
+    pub time_limit: Option<f32>,
+    pub alpha_cutoff: f32,
+    #[serde(default = "TracerConfig::default_epsilon")]
+    pub epsilon: f32,
+    #[serde(default = "TracerConfig::default_shadow_bias")]
+    pub shadow_bias: f32,
+    #[serde(default = "TracerConfig::default_min_throughput")]
+    pub min_throughput: f32,
+    // ev100, see TracerBuilder::exposure
+    pub exposure: f32,
+    pub ambient: f32,
+    pub strategy: ChunkStrategy,
+    pub accel: AccelKind,
+    #[serde(default)]
+    pub integrator: Integrator,
+    #[serde(default = "TracerConfig::default_sampler")]
+    pub sampler: SamplerKind,
+    #[serde(default = "TracerConfig::default_light_sampler")]
+    pub light_sampler: SamplerKind,
+    #[serde(default)]
+    pub light_sampling: LightSampling,
+    #[serde(default)]
+    pub seed: u32,
+    #[serde(default)]
+    pub tonemap: Tonemap,
+    // quantization of the exports and the preview
+    #[serde(default)]
+    pub palette: Option<Palette>,
+    #[serde(default)]
+    pub origin: ImageOrigin,
+    #[serde(default)]
+    pub sample_clamp: Option<f32>,
+    #[serde(default = "TracerConfig::default_supersample")]
+    pub supersample: u32,
+    #[serde(default)]
+    pub downsample: DownsampleFilter,
+    #[serde(default)]
+    pub filter: PixelFilter,
+}
+
+impl TracerConfig {
+    pub(crate) fn default_epsilon() -> f32 {
+        1e-4
+    }
+
+    pub(crate) fn default_shadow_bias() -> f32 {
+        1e-3
+    }
+
+    pub(crate) fn default_min_throughput() -> f32 {
+        1e-3
+    }
+
+    pub(crate) fn default_sampler() -> SamplerKind {
+        SamplerKind::RANDOM
+    }
+
+    pub(crate) fn default_light_sampler() -> SamplerKind {
+        SamplerKind::BLUENOISE
+    }
+
+    pub(crate) fn default_supersample() -> u32 {
+        1
+    }
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+// false color views of the render cost, see Tracer::set_debug_view
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DebugView {
+    #[default]
+    NONE,
+    // nodes of the acceleration structures visited by the first sample of each pixel
+    NODES,
+    // intersection tests against the models
+    TESTS,
+    // depth of the reflections
+    BOUNCES,
+    // the chunks of the last pass, with their boundaries
+    CHUNKS,
+    // compute time of the tiles since the start of the render
+    TIMES,
+}
+
+// traversal counts of the rays of one sample, with the reflections and the shadows,
+// see stats::start_sample
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TraversalCounts {
+    pub nodes: u32,
+    pub tests: u32,
+    pub bounces: u32,
+}
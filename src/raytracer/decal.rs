@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use glam::{Vec2, Vec3};
+
+use crate::raytracer::{Color, Hit, Texture};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlendMode {
+    REPLACE,
+    MULTIPLY,
+    ALPHA,
+}
+
+pub struct Decal {
+    position: Vec3,
+    forward: Vec3,
+    right: Vec3,
+    up: Vec3,
+    tan_x: f32,
+    tan_y: f32,
+    near: f32,
+    far: f32,
+    texture: Arc<dyn Texture + Send + Sync>,
+    blend: BlendMode,
+}
+
+impl Decal {
+    pub fn new(
+        position: Vec3,
+        direction: Vec3,
+        fov: f32,
+        aspect: f32,
+        texture: Arc<dyn Texture + Send + Sync>,
+        blend: BlendMode,
+    ) -> Self {
+        let forward = direction.normalize();
+        let up = if forward.dot(Vec3::Y).abs() > 0.999 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+        let right = forward.cross(up).normalize();
+        let up = right.cross(forward);
+
+        let tan_y = (0.5 * fov).tan();
+
+        Self {
+            position,
+            forward,
+            right,
+            up,
+            tan_x: tan_y * aspect,
+            tan_y,
+            near: 0.,
+            far: f32::MAX,
+            texture,
+            blend,
+        }
+    }
+
+    pub(crate) fn rescale(&mut self, center: Vec3, scale: f32) {
+        self.position = (self.position - center) * scale;
+        self.near *= scale;
+        self.far *= scale;
+    }
+
+    pub fn range(mut self, near: f32, far: f32) -> Self {
+        self.near = near;
+        self.far = far;
+
+        self
+    }
+
+    fn project(&self, position: Vec3) -> Option<Vec2> {
+        let local = position - self.position;
+
+        let z = local.dot(self.forward);
+        if z < self.near || z > self.far || z <= 0. {
+            return None;
+        }
+
+        let x = local.dot(self.right) / (z * self.tan_x);
+        let y = local.dot(self.up) / (z * self.tan_y);
+
+        if x.abs() > 1. || y.abs() > 1. {
+            return None;
+        }
+
+        Some(Vec2::new(0.5 + 0.5 * x, 0.5 - 0.5 * y))
+    }
+
+    pub fn apply(&self, hit: &Hit) -> Color {
+        // only project on surfaces facing the projector
+        if hit.normal.dot(self.forward) >= 0. {
+            return hit.color;
+        }
+
+        let Some(uv) = self.project(hit.position) else {
+            return hit.color;
+        };
+
+        let decal = self.texture.sample(uv, hit.position);
+        let base = hit.color;
+
+        match self.blend {
+            BlendMode::REPLACE => Color::new(decal.r, decal.g, decal.b, base.a),
+            BlendMode::MULTIPLY => {
+                let a = decal.a;
+                Color::new(
+                    base.r * (1. - a + a * decal.r),
+                    base.g * (1. - a + a * decal.g),
+                    base.b * (1. - a + a * decal.b),
+                    base.a,
+                )
+            }
+            BlendMode::ALPHA => {
+                let a = decal.a;
+                Color::new(
+                    base.r * (1. - a) + decal.r * a,
+                    base.g * (1. - a) + decal.g * a,
+                    base.b * (1. - a) + decal.b * a,
+                    base.a,
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use crate::raytracer::{aov::Aovs, Color, Extent};
+
+// Open Image Denoise over the linear color, guided by the albedo and normal AOVs
+pub fn oidn(extent: Extent, framebuffer: &[Color], aovs: &Aovs) -> Vec<Color> {
+    let color = framebuffer
+        .iter()
+        .flat_map(|c| [c.r, c.g, c.b])
+        .collect::<Vec<f32>>();
+    let albedo = aovs
+        .albedo
+        .iter()
+        .flat_map(|c| [c.r, c.g, c.b])
+        .collect::<Vec<f32>>();
+    let normal = aovs
+        .normal
+        .iter()
+        .flat_map(|n| [n.x, n.y, n.z])
+        .collect::<Vec<f32>>();
+
+    let mut output = vec![0.; color.len()];
+
+    let device = oidn::Device::new();
+    oidn::RayTracing::new(&device)
+        .hdr(true)
+        .srgb(false)
+        .image_dimensions(extent.width as usize, extent.height as usize)
+        .albedo_normal(&albedo, &normal)
+        .filter(&color, &mut output)
+        .expect("Denoise");
+
+    if let Err((_, message)) = device.get_error() {
+        log::error!("OIDN error: {}", message);
+    }
+
+    output
+        .chunks_exact(3)
+        .zip(framebuffer)
+        .map(|(c, original)| Color::new(c[0], c[1], c[2], original.a))
+        .collect()
+}
@@ -0,0 +1,412 @@
+use std::{
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use glam::Vec3;
+
+use crate::raytracer::{
+    aov::AovSample,
+    buffer::PixelSamples,
+    queue::{ChunkQueue, Job},
+    scene::ChunkScratch,
+    timer::Timer,
+    Color, Extent, Tracer,
+};
+
+// little endian messages:
+// - hello, worker to coordinator: magic, width, height, rays (u32)
+// - chunk, coordinator to worker: camera, first sample (u32), seed (u64), preview (u8),
+//   pixel count and pixels (u32)
+// - samples, worker to coordinator: pixel count (u32), then for each pixel the index
+//   (u32), color (4 f32), weight, luminance, luminance squared (3 f32), count,
+//   clamped, invalid (u32), preview, splat (2 u8), depth (f32), id (u32), normal
+//   (3 f32), albedo (4 f32)
+const MAGIC: [u8; 4] = *b"RTD2";
+
+// hands out the chunks of the tracer to the workers connected over TCP, the samples
+// are added to the image of the coordinator like the ones of the local threads,
+// see Tracer::serve
+pub struct Coordinator {
+    address: SocketAddr,
+    workers: Arc<AtomicUsize>,
+    // stops the listener, the connected workers end with the queue
+    stopped: Arc<AtomicBool>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl Coordinator {
+    // idle workers check the queue again after this delay
+    const POLL: Duration = Duration::from_millis(5);
+    // the listener checks for new workers and for the end of the tracer
+    const ACCEPT_POLL: Duration = Duration::from_millis(50);
+
+    pub(crate) fn bind(address: impl ToSocketAddrs, queue: Weak<ChunkQueue>) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let workers = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        log::info!("Waiting for workers on {}", address);
+
+        let (connected, stop) = (workers.clone(), stopped.clone());
+        let listener = thread::Builder::new()
+            .name("coordinator".to_string())
+            .spawn(move || {
+                // the port is released when the thread ends
+                while !stop.load(Ordering::Acquire) && queue.strong_count() > 0 {
+                    match listener.accept() {
+                        Ok((stream, _)) => Self::accept(stream, queue.clone(), connected.clone()),
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::sleep(Self::ACCEPT_POLL)
+                        }
+                        Err(e) => log::warn!("Worker connection failed: {}", e),
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            address,
+            workers,
+            stopped,
+            listener: Some(listener),
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    // connected workers
+    pub fn workers(&self) -> usize {
+        self.workers.load(Ordering::Acquire)
+    }
+
+    fn accept(stream: TcpStream, queue: Weak<ChunkQueue>, workers: Arc<AtomicUsize>) {
+        let peer = stream
+            .peer_addr()
+            .map_or("unknown".to_string(), |addr| addr.to_string());
+
+        let spawned = thread::Builder::new()
+            .name(format!("worker-{}", peer))
+            .spawn(move || {
+                // the stream can inherit the mode of the listener
+                let connection = stream
+                    .set_nonblocking(false)
+                    .and_then(|_| Connection::new(stream));
+                let mut connection = match connection {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        log::warn!("Worker {} rejected: {}", peer, e);
+                        return;
+                    }
+                };
+
+                log::info!(
+                    "Worker {} connected: {}x{}, {} rays",
+                    peer,
+                    connection.extent.width,
+                    connection.extent.height,
+                    connection.n_rays
+                );
+
+                workers.fetch_add(1, Ordering::AcqRel);
+                if let Err(e) = connection.serve(&queue) {
+                    log::warn!("Worker {} lost: {}", peer, e);
+                }
+                workers.fetch_sub(1, Ordering::AcqRel);
+
+                log::info!("Worker {} disconnected", peer);
+            });
+
+        if let Err(e) = spawned {
+            log::warn!("Cannot start the worker thread: {}", e);
+        }
+    }
+}
+
+impl Drop for Coordinator {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Release);
+
+        if let Some(listener) = self.listener.take() {
+            let _ = listener.join();
+        }
+    }
+}
+
+// a worker seen from the coordinator
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    extent: Extent,
+    n_rays: u32,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let writer = BufWriter::new(stream);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid("not a raytracer worker"));
+        }
+
+        let extent = Extent::new(read_u32(&mut reader)?, read_u32(&mut reader)?);
+        let n_rays = read_u32(&mut reader)?;
+
+        Ok(Self {
+            reader,
+            writer,
+            extent,
+            n_rays,
+        })
+    }
+
+    // until the tracer is dropped or the worker disconnects
+    fn serve(&mut self, queue: &Weak<ChunkQueue>) -> io::Result<()> {
+        let mut scratch = ChunkScratch::default();
+        let mut result = Vec::new();
+
+        while let Some(queue) = queue.upgrade() {
+            let Some(job) = queue.take() else {
+                drop(queue);
+                thread::sleep(Coordinator::POLL);
+                continue;
+            };
+
+            let mut timer = Timer::new();
+
+            // the chunk is rendered here if the worker cannot take it or is lost
+            let exchange = self.accepts(&job).then(|| self.exchange(&job, &mut result));
+            if !matches!(exchange, Some(Ok(()))) {
+                job.run(&mut scratch, &mut result);
+            }
+
+            queue.complete(&job, &result, timer.delta());
+
+            exchange.transpose()?;
+        }
+
+        Ok(())
+    }
+
+    // the workers render the scene they were built with, a camera of another size
+    // stays on the coordinator
+    fn accepts(&self, job: &Job) -> bool {
+        job.scene.extent == self.extent && job.scene.n_rays == self.n_rays
+    }
+
+    fn exchange(&mut self, job: &Job, result: &mut Vec<PixelSamples>) -> io::Result<()> {
+        let writer = &mut self.writer;
+
+        writer.write_all(&(job.camera as u32).to_le_bytes())?;
+        writer.write_all(&job.first_sample.to_le_bytes())?;
+        writer.write_all(&job.rng_seed.to_le_bytes())?;
+        writer.write_all(&[job.preview as u8])?;
+        writer.write_all(&(job.chunk.len() as u32).to_le_bytes())?;
+        for &idx in &job.chunk {
+            writer.write_all(&(idx as u32).to_le_bytes())?;
+        }
+        writer.flush()?;
+
+        read_samples(&mut self.reader, self.extent.size() as usize, result)
+    }
+}
+
+// renders the chunks of a coordinator with its own copy of the scene, the scene and
+// the settings must be the same on both sides
+pub struct Worker {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl Worker {
+    pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        })
+    }
+
+    // blocks until the coordinator closes the connection
+    pub fn run(&mut self, tracer: &Tracer) -> io::Result<()> {
+        let main = tracer.camera_scene(0).expect("Main camera");
+        let extent = main.extent;
+        let size = extent.size() as usize;
+
+        self.writer.write_all(&MAGIC)?;
+        for v in [extent.width, extent.height, main.n_rays] {
+            self.writer.write_all(&v.to_le_bytes())?;
+        }
+        self.writer.flush()?;
+
+        let mut scratch = ChunkScratch::default();
+        let mut result = Vec::new();
+        let mut chunks = 0;
+
+        loop {
+            let camera = match read_u32(&mut self.reader) {
+                Ok(camera) => camera as usize,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+            let first_sample = read_u32(&mut self.reader)?;
+            let rng_seed = read_u64(&mut self.reader)?;
+            let mut preview = [0; 1];
+            self.reader.read_exact(&mut preview)?;
+
+            let count = read_u32(&mut self.reader)? as usize;
+            let chunk = (0..count)
+                .map(|_| read_u32(&mut self.reader).map(|i| i as usize))
+                .collect::<io::Result<Vec<_>>>()?;
+            if chunk.iter().any(|&i| i >= size) {
+                return Err(invalid("pixel out of range"));
+            }
+
+            let scene = tracer
+                .camera_scene(camera)
+                .ok_or_else(|| invalid("unknown camera"))?;
+
+            if preview[0] != 0 {
+                scene.preview_chunk(&chunk, &mut result);
+            } else {
+                scene.compute_chunk(&chunk, first_sample, rng_seed, &mut scratch, &mut result);
+            }
+
+            write_samples(&mut self.writer, &result)?;
+            chunks += 1;
+        }
+
+        log::info!("Coordinator done: {} chunks rendered", chunks);
+
+        Ok(())
+    }
+}
+
+fn write_samples(writer: &mut impl Write, result: &[PixelSamples]) -> io::Result<()> {
+    writer.write_all(&(result.len() as u32).to_le_bytes())?;
+
+    for samples in result {
+        let (c, aov) = (samples.color, &samples.aov);
+
+        writer.write_all(&(samples.idx as u32).to_le_bytes())?;
+        for v in [
+            c.r,
+            c.g,
+            c.b,
+            c.a,
+            samples.weight,
+            samples.luminance,
+            samples.luminance_sq,
+        ] {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+        for v in [samples.count, samples.clamped, samples.invalid] {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+        writer.write_all(&[samples.preview as u8, samples.splat as u8])?;
+        writer.write_all(&aov.depth.to_le_bytes())?;
+        writer.write_all(&aov.id.to_le_bytes())?;
+        for v in aov.normal.to_array().iter().chain(&[
+            aov.albedo.r,
+            aov.albedo.g,
+            aov.albedo.b,
+            aov.albedo.a,
+        ]) {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+    }
+
+    writer.flush()
+}
+
+// the differentials and the traversal counts of the aovs are not sent
+fn read_samples(
+    reader: &mut impl Read,
+    size: usize,
+    result: &mut Vec<PixelSamples>,
+) -> io::Result<()> {
+    result.clear();
+
+    let count = read_u32(reader)? as usize;
+    for _ in 0..count {
+        let idx = read_u32(reader)? as usize;
+        if idx >= size {
+            return Err(invalid("pixel out of range"));
+        }
+
+        let mut samples = PixelSamples::new(idx);
+
+        let mut v = [0.; 7];
+        for x in v.iter_mut() {
+            *x = read_f32(reader)?;
+        }
+        // the chunk is rendered again locally rather than spoiling the accumulation
+        if v.iter().any(|x| !x.is_finite()) {
+            return Err(invalid("non-finite sample"));
+        }
+        samples.color = Color::new(v[0], v[1], v[2], v[3]);
+        samples.weight = v[4];
+        samples.luminance = v[5];
+        samples.luminance_sq = v[6];
+        samples.count = read_u32(reader)?;
+        samples.clamped = read_u32(reader)?;
+        samples.invalid = read_u32(reader)?;
+
+        let mut flags = [0; 2];
+        reader.read_exact(&mut flags)?;
+        samples.preview = flags[0] != 0;
+        samples.splat = flags[1] != 0;
+
+        let mut aov = AovSample::new(&None);
+        aov.depth = read_f32(reader)?;
+        aov.id = read_u32(reader)?;
+        let mut v = [0.; 7];
+        for x in v.iter_mut() {
+            *x = read_f32(reader)?;
+        }
+        aov.normal = Vec3::new(v[0], v[1], v[2]);
+        aov.albedo = Color::new(v[3], v[4], v[5], v[6]);
+        samples.aov = aov;
+
+        result.push(samples);
+    }
+
+    Ok(())
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    Ok(f32::from_bits(read_u32(reader)?))
+}
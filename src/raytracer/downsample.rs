@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+use crate::raytracer::{filter::mitchell, Color, Extent};
+
+// reduces the image rendered with TracerBuilder::supersample to the output size
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DownsampleFilter {
+    // average of the pixels covered by the output pixel
+    BOX,
+    // mitchell-netravali with b = c = 1/3 over two output pixels, sharper
+    #[default]
+    MITCHELL,
+}
+
+impl DownsampleFilter {
+    // extent is the size of the image, divided by factor in the result
+    pub fn apply(&self, extent: Extent, factor: u32, image: &[Color]) -> Vec<Color> {
+        if factor <= 1 {
+            return image.to_vec();
+        }
+
+        let (width, height) = (extent.width as usize, extent.height as usize);
+        let columns = self.weights(width, factor);
+        let rows = self.weights(height, factor);
+
+        // separable: the rows first, then the columns
+        let mut horizontal = Vec::with_capacity(columns.len() * height);
+        for y in 0..height {
+            let row = &image[y * width..(y + 1) * width];
+            horizontal.extend(columns.iter().map(|taps| Self::filter(taps, |x| row[x])));
+        }
+
+        let mut result = Vec::with_capacity(columns.len() * rows.len());
+        for taps in &rows {
+            for x in 0..columns.len() {
+                result.push(Self::filter(taps, |y| horizontal[x + y * columns.len()]));
+            }
+        }
+
+        result
+    }
+
+    fn filter(taps: &[(usize, f32)], pixel: impl Fn(usize) -> Color) -> Color {
+        let c = taps
+            .iter()
+            .fold(Color::new(0., 0., 0., 0.), |c, &(i, w)| c + pixel(i) * w);
+
+        // the negative lobes can undershoot next to a bright pixel
+        Color::new(c.r.max(0.), c.g.max(0.), c.b.max(0.), c.a.clamp(0., 1.))
+    }
+
+    // for each output pixel, the input pixels and their normalized weights
+    fn weights(&self, size: usize, factor: u32) -> Vec<Vec<(usize, f32)>> {
+        let factor = factor as usize;
+
+        (0..size / factor)
+            .map(|i| match self {
+                Self::BOX => (i * factor..(i + 1) * factor)
+                    .map(|j| (j, 1. / factor as f32))
+                    .collect(),
+                Self::MITCHELL => {
+                    let center = (i * factor) as f32 + 0.5 * factor as f32;
+                    let first = (i * factor).saturating_sub(2 * factor);
+                    let last = ((i + 3) * factor).min(size);
+
+                    let taps = (first..last)
+                        .map(|j| (j, mitchell((j as f32 + 0.5 - center) / factor as f32)))
+                        .filter(|&(_, w)| w != 0.)
+                        .collect::<Vec<_>>();
+                    let total = taps.iter().map(|&(_, w)| w).sum::<f32>();
+
+                    taps.into_iter().map(|(j, w)| (j, w / total)).collect()
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::raytracer::Extent;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileNaming {
+    // prefix_0001.png, after the highest number in the directory
+    #[default]
+    NUMBERED,
+    // prefix_<unix time>.png, numbered if two exports share the same second
+    TIMESTAMP,
+}
+
+// png files written by Tracer::export, e.g. for screenshots
+#[derive(Clone, Debug)]
+pub struct ExportOptions {
+    dir: PathBuf,
+    prefix: String,
+    naming: FileNaming,
+    metadata: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("."),
+            prefix: "raytracer".to_string(),
+            naming: FileNaming::default(),
+            metadata: true,
+        }
+    }
+}
+
+impl ExportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // created if missing
+    pub fn dir(mut self, dir: &str) -> Self {
+        self.dir = PathBuf::from(dir);
+
+        self
+    }
+
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+
+        self
+    }
+
+    pub fn naming(mut self, naming: FileNaming) -> Self {
+        self.naming = naming;
+
+        self
+    }
+
+    // the render metadata in text chunks of the png
+    pub fn metadata(mut self, metadata: bool) -> Self {
+        self.metadata = metadata;
+
+        self
+    }
+
+    // first file name not used in the directory
+    fn next_path(&self) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+
+        match self.naming {
+            FileNaming::NUMBERED => {
+                let last = fs::read_dir(&self.dir)?
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| {
+                        let name = entry.file_name().into_string().ok()?;
+                        let n = name
+                            .strip_prefix(&format!("{}_", self.prefix))?
+                            .strip_suffix(".png")?;
+                        n.parse::<u32>().ok()
+                    })
+                    .max()
+                    .unwrap_or(0);
+
+                Ok(self.file(&format!("{:04}", last + 1)))
+            }
+            FileNaming::TIMESTAMP => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_err(io::Error::other)?
+                    .as_secs();
+
+                let mut path = self.file(&now.to_string());
+                let mut n = 1;
+                while path.exists() {
+                    path = self.file(&format!("{}_{}", now, n));
+                    n += 1;
+                }
+
+                Ok(path)
+            }
+        }
+    }
+
+    fn file(&self, suffix: &str) -> PathBuf {
+        self.dir.join(format!("{}_{}.png", self.prefix, suffix))
+    }
+}
+
+// written to the text chunks of the exports
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderMetadata {
+    pub samples_per_pixel: u32,
+    // seconds
+    pub render_time: f32,
+    pub seed: u32,
+    // settings, camera, lights and models, see Tracer::scene_hash
+    pub scene_hash: u64,
+}
+
+impl RenderMetadata {
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Software", "raytracer".to_string()),
+            ("Samples", self.samples_per_pixel.to_string()),
+            ("Render time", format!("{:.2}s", self.render_time)),
+            ("Seed", self.seed.to_string()),
+            ("Scene hash", format!("{:016x}", self.scene_hash)),
+        ]
+    }
+}
+
+// 8 bits rgb
+pub(crate) fn write_png(
+    options: &ExportOptions,
+    extent: Extent,
+    rgb: &[u8],
+    metadata: &RenderMetadata,
+) -> io::Result<PathBuf> {
+    let path = options.next_path()?;
+    let writer = BufWriter::new(File::create(&path)?);
+
+    let mut encoder = png::Encoder::new(writer, extent.width, extent.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    if options.metadata {
+        for (key, value) in metadata.entries() {
+            encoder
+                .add_text_chunk(key.to_string(), value)
+                .map_err(io::Error::other)?;
+        }
+    }
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(rgb).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)?;
+
+    Ok(path)
+}
+
+// 8 bits rgb, binary ppm needs no encoder
+pub(crate) fn write_ppm(path: &str, extent: Extent, rgb: &[u8]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write!(writer, "P6\n{} {}\n255\n", extent.width, extent.height)?;
+    writer.write_all(rgb)?;
+
+    writer.flush()
+}
+
+// the other formats are written with the image crate
+#[cfg(not(feature = "image"))]
+pub(crate) fn unsupported(path: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{}: only ppm without the image feature", path),
+    )
+}
+
+// FNV-1a, stable across runs and platforms
+pub(crate) fn hash(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    const PRIME: u64 = 0x100000001b3;
+
+    bytes.into_iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    })
+}
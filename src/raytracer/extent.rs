@@ -0,0 +1,21 @@
+// size of an image in pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Extent {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Extent {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.width * self.height
+    }
+
+    // width / height
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+}
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+// weight of a sample in the pixels around it, see TracerBuilder::pixel_filter
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum PixelFilter {
+    // the samples only count in their own pixel
+    #[default]
+    BOX,
+    TENT,
+    GAUSSIAN,
+    // mitchell-netravali with b = c = 1/3, the negative lobes sharpen the edges
+    MITCHELL,
+}
+
+impl PixelFilter {
+    // in pixels, from the center of the pixel
+    pub fn radius(&self) -> f32 {
+        match self {
+            Self::BOX => 0.5,
+            Self::TENT => 1.,
+            Self::GAUSSIAN => 1.5,
+            Self::MITCHELL => 2.,
+        }
+    }
+
+    // x, y from the center of the pixel to the sample, separable
+    pub fn weight(&self, x: f32, y: f32) -> f32 {
+        self.weight_1d(x) * self.weight_1d(y)
+    }
+
+    fn weight_1d(&self, x: f32) -> f32 {
+        let x = x.abs();
+
+        match self {
+            Self::BOX => (x <= 0.5) as u32 as f32,
+            Self::TENT => (1. - x).max(0.),
+            Self::GAUSSIAN => {
+                // alpha = 2, shifted to reach 0 at the radius
+                let r = self.radius();
+                ((-2. * x * x).exp() - (-2. * r * r).exp()).max(0.)
+            }
+            Self::MITCHELL => mitchell(x),
+        }
+    }
+}
+
+// b = c = 1/3, 0 from 2
+pub(crate) fn mitchell(x: f32) -> f32 {
+    const B: f32 = 1. / 3.;
+    const C: f32 = 1. / 3.;
+
+    let x = x.abs();
+    let w = if x < 1. {
+        (12. - 9. * B - 6. * C) * x.powi(3) + (-18. + 12. * B + 6. * C) * x.powi(2) + (6. - 2. * B)
+    } else if x < 2. {
+        (-B - 6. * C) * x.powi(3)
+            + (6. * B + 30. * C) * x.powi(2)
+            + (-12. * B - 48. * C) * x
+            + (8. * B + 24. * C)
+    } else {
+        0.
+    };
+
+    w / 6.
+}
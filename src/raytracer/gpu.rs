@@ -0,0 +1,455 @@
+use std::{
+    borrow::Cow,
+    f32::consts::PI,
+    sync::mpsc::{self, TryRecvError},
+};
+
+use glam::{Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::raytracer::{
+    aov::AovSample, buffer::PixelSamples, camera::ProjectionMode, color::ColorExt,
+    group::Transform, material::Material, scene::Scene, Color, Hitable, Integrator, Primitive, Ray,
+};
+
+// spheres and triangles traced by a compute shader, for the passes of the main
+// camera, see Backend::GPU
+pub(crate) struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    // None if the scene has models the shader doesn't support
+    geometry: Option<Geometry>,
+}
+
+struct Geometry {
+    spheres: wgpu::Buffer,
+    triangles: wgpu::Buffer,
+    lights: wgpu::Buffer,
+    background: wgpu::Buffer,
+    counts: [u32; 3],
+}
+
+// a pass submitted to the gpu, see Gpu::poll
+pub(crate) struct GpuPass {
+    staging: wgpu::Buffer,
+    mapped: mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    n_rays: u32,
+}
+
+pub(crate) enum GpuStatus {
+    PENDING,
+    DONE(Vec<PixelSamples>),
+    // the pass is lost
+    FAILED,
+}
+
+impl Gpu {
+    // lat-long map of the background, the shader cannot call the function
+    const BACKGROUND_WIDTH: u32 = 64;
+    const WORKGROUP_SIZE: u32 = 8;
+    // floats per output pixel
+    const PIXEL_SIZE: usize = 12;
+
+    pub async fn new() -> Option<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+
+        let Some(adapter) = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                force_fallback_adapter: false,
+                compatible_surface: None,
+            })
+            .await
+        else {
+            log::warn!("No GPU adapter found");
+            return None;
+        };
+
+        let (device, queue) = match adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("raytracer"),
+                    required_features: wgpu::Features::empty(),
+                    required_limits: adapter.limits(),
+                },
+                None,
+            )
+            .await
+        {
+            Ok(device) => device,
+            Err(e) => {
+                log::warn!("Cannot open the GPU: {}", e);
+                return None;
+            }
+        };
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("raytracer"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("gpu.wgsl"))),
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("raytracer"),
+            layout: None,
+            module: &shader,
+            entry_point: "main",
+        });
+
+        log::info!("GPU backend: {}", adapter.get_info().name);
+
+        Some(Self {
+            device,
+            queue,
+            pipeline,
+            geometry: None,
+        })
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.geometry.is_some()
+    }
+
+    // the shader only renders the direct light of the point lights on diffuse solid
+    // colors, the scenes it cannot render as the cpu does are left to the cpu
+    pub fn upload(&mut self, scene: &Scene) {
+        self.geometry = None;
+
+        if let Some(feature) = Self::unsupported(scene) {
+            log::warn!("GPU backend disabled: {} not supported", feature);
+            return;
+        }
+
+        let mut spheres = Vec::new();
+        let mut triangles = Vec::new();
+
+        for (id, model) in scene.models.iter().enumerate() {
+            let id = f32::from_bits(id as u32);
+
+            if let Err(feature) = Self::add_model(
+                model,
+                &Transform::IDENTITY,
+                id,
+                &mut spheres,
+                &mut triangles,
+            ) {
+                log::warn!("GPU backend disabled: {} not supported", feature);
+                return;
+            }
+        }
+
+        let mut lights = Vec::new();
+        for light in scene.lights.iter() {
+            let intensity = light.color * light.intensity();
+
+            lights.extend([light.position.x, light.position.y, light.position.z, 0.]);
+            lights.extend([intensity.r, intensity.g, intensity.b, 0.]);
+        }
+
+        let (width, height) = (Self::BACKGROUND_WIDTH, Self::BACKGROUND_WIDTH / 2);
+        let mut background = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let phi = ((x as f32 + 0.5) / width as f32 - 0.5) * 2. * PI;
+                let theta = (y as f32 + 0.5) / height as f32 * PI;
+                let direction = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+
+                let c = (scene.background)(&Ray::new(Vec3::ZERO, direction));
+                background.extend([c.r, c.g, c.b, c.a]);
+            }
+        }
+
+        let counts = [
+            (spheres.len() / 12) as u32,
+            (triangles.len() / 20) as u32,
+            (lights.len() / 8) as u32,
+        ];
+
+        self.geometry = Some(Geometry {
+            spheres: self.storage("spheres", &spheres),
+            triangles: self.storage("triangles", &triangles),
+            lights: self.storage("lights", &lights),
+            background: self.storage("background", &background),
+            counts,
+        });
+    }
+
+    // the settings of the scene the shader doesn't render
+    fn unsupported(scene: &Scene) -> Option<String> {
+        if scene.integrator != Integrator::WHITTED {
+            return Some(format!("{:?} integrator", scene.integrator));
+        }
+        if !scene.portals.is_empty() {
+            return Some("portals".to_string());
+        }
+        if !scene.decals.is_empty() {
+            return Some("decals".to_string());
+        }
+        if scene.lights.iter().any(|light| light.radius > 0.) {
+            return Some("soft shadows".to_string());
+        }
+        if scene.sample_clamp.is_some() {
+            return Some("sample clamp".to_string());
+        }
+
+        None
+    }
+
+    // the groups are flattened with their current transform, the feature of the
+    // first model the shader doesn't support otherwise
+    fn add_model(
+        model: &Primitive,
+        transform: &Transform,
+        id: f32,
+        spheres: &mut Vec<f32>,
+        triangles: &mut Vec<f32>,
+    ) -> Result<(), String> {
+        match model {
+            Primitive::SPHERE(sphere) => {
+                let (center, radius) = sphere.sphere().expect("Sphere");
+                let (albedo, emission) = Self::material(sphere.material())
+                    .ok_or_else(|| format!("material of {}", model.name()))?;
+                let (center, radius) = (transform.point(center), radius * transform.scale);
+
+                spheres.extend([center.x, center.y, center.z, radius]);
+                spheres.extend(albedo);
+                spheres.extend([emission[0], emission[1], emission[2], id]);
+            }
+            Primitive::MESH(mesh) => {
+                let (albedo, emission) = Self::material(mesh.material())
+                    .ok_or_else(|| format!("material of {}", model.name()))?;
+
+                for triangle in mesh.triangle_positions() {
+                    for p in triangle.map(|p| transform.point(p)) {
+                        triangles.extend([p.x, p.y, p.z, 0.]);
+                    }
+                    triangles.extend(albedo);
+                    triangles.extend([emission[0], emission[1], emission[2], id]);
+                }
+            }
+            Primitive::GROUP(group) => {
+                let transform = transform.compose(&group.get_transform());
+                for child in group.children() {
+                    Self::add_model(child, &transform, id, spheres, triangles)?;
+                }
+            }
+            _ => return Err(format!("model {}", model.name())),
+        }
+
+        Ok(())
+    }
+
+    // None for the reflections, the textures and the transparent surfaces
+    fn material(material: &Material) -> Option<([f32; 4], [f32; 3])> {
+        let c = material.albedo.solid()?;
+        if material.reflect > 0. || material.clearcoat.is_some() || c.a < 1. {
+            return None;
+        }
+        let e = material.emission;
+
+        Some(([c.r, c.g, c.b, c.a], [e.r, e.g, e.b]))
+    }
+
+    // a binding cannot be empty
+    fn storage(&self, label: &str, data: &[f32]) -> wgpu::Buffer {
+        let mut bytes = to_bytes(data);
+        if bytes.is_empty() {
+            bytes.resize(16, 0);
+        }
+
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: &bytes,
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+    }
+
+    // submits n_rays samples of every pixel of the main camera, None if the scene is
+    // not supported
+    pub fn dispatch(&self, scene: &Scene, first_sample: u32, seed: u32) -> Option<GpuPass> {
+        let geometry = self.geometry.as_ref()?;
+        let extent = scene.extent;
+        let size = extent.size() as usize;
+
+        let (inverse_view_proj, mode, near_z) = match scene.camera.mode {
+            ProjectionMode::MATRIX {
+                inverse_view_proj,
+                perspective,
+                near_z,
+                ..
+            } => (inverse_view_proj, if perspective { 1. } else { 2. }, near_z),
+            // along +z through the image plane, same as Scene::pixel_ray
+            ProjectionMode::ORTHO { near, .. } => {
+                let half = scene.camera.mode.half_plane(extent.aspect());
+                let plane = Mat4::from_translation(scene.camera.position + Vec3::Z * near)
+                    * Mat4::from_scale(half.extend(1.));
+                (plane, 2., 0.)
+            }
+            ProjectionMode::PERSPECTIVE { .. } => {
+                let half = scene.camera.mode.half_plane(extent.aspect());
+                (Mat4::from_scale(half.extend(1.)), 0., 0.)
+            }
+        };
+        let position = scene.camera.position;
+
+        let mut params = to_bytes(&inverse_view_proj.to_cols_array());
+        params.extend(to_bytes(&[position.x, position.y, position.z, mode]));
+        params.extend(to_bytes_u32(&[
+            extent.width,
+            extent.height,
+            scene.n_rays,
+            first_sample,
+        ]));
+        params.extend(to_bytes_u32(&[
+            geometry.counts[0],
+            geometry.counts[1],
+            geometry.counts[2],
+            seed,
+        ]));
+        params.extend(to_bytes(&[
+            near_z,
+            scene.camera.mode.near(),
+            scene.camera.mode.far().min(f32::MAX),
+            scene.exposure,
+        ]));
+        params.extend(to_bytes(&[
+            scene.ambient,
+            scene.epsilon,
+            scene.shadow_bias,
+            Self::BACKGROUND_WIDTH as f32,
+        ]));
+
+        let params = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("params"),
+                contents: &params,
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+        let output_size = (size * Self::PIXEL_SIZE * 4) as u64;
+        let output = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("pixels"),
+            size: output_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("staging"),
+            size: output_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("raytracer"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                (0, &params),
+                (1, &geometry.spheres),
+                (2, &geometry.triangles),
+                (3, &geometry.lights),
+                (4, &geometry.background),
+                (5, &output),
+            ]
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding,
+                resource: buffer.as_entire_binding(),
+            }),
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("raytracer"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(
+                extent.width.div_ceil(Self::WORKGROUP_SIZE),
+                extent.height.div_ceil(Self::WORKGROUP_SIZE),
+                1,
+            );
+        }
+        encoder.copy_buffer_to_buffer(&output, 0, &staging, 0, output_size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let (sender, mapped) = mpsc::channel();
+        staging
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+
+        Some(GpuPass {
+            staging,
+            mapped,
+            n_rays: scene.n_rays,
+        })
+    }
+
+    // non blocking, the samples once the gpu is done with the pass
+    pub fn poll(&self, pass: &GpuPass) -> GpuStatus {
+        self.device.poll(wgpu::Maintain::Poll);
+
+        match pass.mapped.try_recv() {
+            Err(TryRecvError::Empty) => return GpuStatus::PENDING,
+            Err(TryRecvError::Disconnected) => return GpuStatus::FAILED,
+            Ok(Err(e)) => {
+                log::warn!("Cannot read the GPU samples: {}", e);
+                return GpuStatus::FAILED;
+            }
+            Ok(Ok(())) => {}
+        }
+
+        let data = pass.staging.slice(..).get_mapped_range();
+        let values = data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect::<Vec<_>>();
+        drop(data);
+        pass.staging.unmap();
+
+        let n = pass.n_rays;
+        let result = values
+            .chunks_exact(Self::PIXEL_SIZE)
+            .enumerate()
+            .map(|(idx, v)| {
+                let mut samples = PixelSamples::new(idx);
+                samples.color = Color::new(v[0], v[1], v[2], n as f32);
+                samples.weight = n as f32;
+                samples.luminance = samples.color.luminance();
+                samples.luminance_sq = v[3];
+                samples.count = n;
+
+                let id = v[11].to_bits();
+                if id != AovSample::NO_ID {
+                    samples.aov.depth = v[7];
+                    samples.aov.normal = Vec3::new(v[4], v[5], v[6]);
+                    samples.aov.albedo = Color::new(v[8], v[9], v[10], 1.);
+                    samples.aov.id = id;
+                }
+
+                samples
+            })
+            .collect();
+
+        GpuStatus::DONE(result)
+    }
+}
+
+fn to_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn to_bytes_u32(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
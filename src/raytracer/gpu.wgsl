@@ -0,0 +1,285 @@
+// primary rays and direct lighting of the spheres and triangles, see gpu.rs for the
+// layout of the buffers
+
+struct Params {
+    inverse_view_proj: mat4x4<f32>,
+    // w: 0 for a perspective camera along +z, the matrix scales the ndc to the image
+    // plane at a distance of 1, 1 for a perspective matrix, 2 for an ortho matrix
+    position: vec4<f32>,
+    // width, height, rays, first sample
+    extent: vec4<u32>,
+    // spheres, triangles, lights, seed
+    counts: vec4<u32>,
+    // near_z, near, far, exposure
+    camera: vec4<f32>,
+    // ambient, epsilon, shadow bias, background width
+    shading: vec4<f32>,
+}
+
+struct Sphere {
+    // xyz: center, w: radius
+    center: vec4<f32>,
+    albedo: vec4<f32>,
+    emission: vec4<f32>,
+}
+
+struct Triangle {
+    a: vec4<f32>,
+    b: vec4<f32>,
+    c: vec4<f32>,
+    albedo: vec4<f32>,
+    emission: vec4<f32>,
+}
+
+struct Light {
+    position: vec4<f32>,
+    // color times the intensity, in candela
+    intensity: vec4<f32>,
+}
+
+struct Pixel {
+    // xyz: sum of the colors, w: sum of the squared luminances
+    color: vec4<f32>,
+    // xyz: normal, w: depth of the first sample
+    normal: vec4<f32>,
+    // xyz: albedo, w: id of the model
+    albedo: vec4<f32>,
+}
+
+struct Hit {
+    distance: f32,
+    normal: vec3<f32>,
+    albedo: vec3<f32>,
+    emission: vec3<f32>,
+    id: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> spheres: array<Sphere>;
+@group(0) @binding(2) var<storage, read> triangles: array<Triangle>;
+@group(0) @binding(3) var<storage, read> lights: array<Light>;
+// lat-long map of the background, twice as wide as high
+@group(0) @binding(4) var<storage, read> background: array<vec4<f32>>;
+@group(0) @binding(5) var<storage, read_write> pixels: array<Pixel>;
+
+const PI: f32 = 3.14159265;
+const NO_ID: u32 = 0xffffffffu;
+
+fn hash(x: u32) -> u32 {
+    var h = x * 747796405u + 2891336453u;
+    h = ((h >> ((h >> 28u) + 4u)) ^ h) * 277803737u;
+    return (h >> 22u) ^ h;
+}
+
+fn random(state: ptr<function, u32>) -> f32 {
+    *state = hash(*state);
+    return f32(*state >> 8u) / 16777216.0;
+}
+
+fn luminance(c: vec3<f32>) -> f32 {
+    return dot(c, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+fn project(p: vec3<f32>) -> vec3<f32> {
+    let v = params.inverse_view_proj * vec4<f32>(p, 1.0);
+    return v.xyz / v.w;
+}
+
+// same as Scene::pixel_ray
+fn pixel_ray(x: f32, y: f32, origin: ptr<function, vec3<f32>>) -> vec3<f32> {
+    let width = f32(params.extent.x);
+    let height = f32(params.extent.y);
+    let mode = params.position.w;
+    let ndc = vec2<f32>(2.0 * x / width - 1.0, 1.0 - 2.0 * y / height);
+
+    if mode > 0.5 {
+        let a = project(vec3<f32>(ndc, params.camera.x));
+        let b = project(vec3<f32>(ndc, 0.5));
+        let direction = normalize(b - a);
+        if mode < 1.5 {
+            *origin = params.position.xyz;
+        } else {
+            *origin = a - direction * params.camera.y;
+        }
+        return direction;
+    }
+
+    *origin = params.position.xyz;
+    return normalize((params.inverse_view_proj * vec4<f32>(ndc, 1.0, 1.0)).xyz);
+}
+
+fn hit_sphere(i: u32, origin: vec3<f32>, direction: vec3<f32>, t_min: f32, t_max: f32) -> f32 {
+    let sphere = spheres[i];
+    let oc = origin - sphere.center.xyz;
+    let b = dot(oc, direction);
+    let c = dot(oc, oc) - sphere.center.w * sphere.center.w;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return -1.0;
+    }
+
+    let root = sqrt(discriminant);
+    var t = -b - root;
+    if t < t_min || t > t_max {
+        t = -b + root;
+    }
+    if t < t_min || t > t_max {
+        return -1.0;
+    }
+    return t;
+}
+
+// moller-trumbore
+fn hit_triangle(i: u32, origin: vec3<f32>, direction: vec3<f32>, t_min: f32, t_max: f32) -> f32 {
+    let triangle = triangles[i];
+    let e1 = triangle.b.xyz - triangle.a.xyz;
+    let e2 = triangle.c.xyz - triangle.a.xyz;
+    let p = cross(direction, e2);
+    let det = dot(e1, p);
+    if abs(det) < 1e-7 {
+        return -1.0;
+    }
+
+    let s = origin - triangle.a.xyz;
+    let u = dot(s, p) / det;
+    if u < 0.0 || u > 1.0 {
+        return -1.0;
+    }
+    let q = cross(s, e1);
+    let v = dot(direction, q) / det;
+    if v < 0.0 || u + v > 1.0 {
+        return -1.0;
+    }
+
+    let t = dot(e2, q) / det;
+    if t < t_min || t > t_max {
+        return -1.0;
+    }
+    return t;
+}
+
+// brute force, the models are not sorted on the gpu
+fn closest_hit(origin: vec3<f32>, direction: vec3<f32>, t_min: f32, t_max: f32) -> Hit {
+    var hit: Hit;
+    hit.distance = t_max;
+    hit.id = NO_ID;
+
+    for (var i = 0u; i < params.counts.x; i++) {
+        let t = hit_sphere(i, origin, direction, t_min, hit.distance);
+        if t > 0.0 {
+            let sphere = spheres[i];
+            hit.distance = t;
+            hit.normal = (origin + direction * t - sphere.center.xyz) / sphere.center.w;
+            hit.albedo = sphere.albedo.xyz;
+            hit.emission = sphere.emission.xyz;
+            hit.id = bitcast<u32>(sphere.emission.w);
+        }
+    }
+
+    for (var i = 0u; i < params.counts.y; i++) {
+        let t = hit_triangle(i, origin, direction, t_min, hit.distance);
+        if t > 0.0 {
+            let triangle = triangles[i];
+            hit.distance = t;
+            hit.normal = normalize(cross(
+                triangle.b.xyz - triangle.a.xyz,
+                triangle.c.xyz - triangle.a.xyz,
+            ));
+            hit.albedo = triangle.albedo.xyz;
+            hit.emission = triangle.emission.xyz;
+            hit.id = bitcast<u32>(triangle.emission.w);
+        }
+    }
+
+    return hit;
+}
+
+fn is_occluded(origin: vec3<f32>, direction: vec3<f32>, t_min: f32, t_max: f32) -> bool {
+    for (var i = 0u; i < params.counts.x; i++) {
+        if hit_sphere(i, origin, direction, t_min, t_max) > 0.0 {
+            return true;
+        }
+    }
+    for (var i = 0u; i < params.counts.y; i++) {
+        if hit_triangle(i, origin, direction, t_min, t_max) > 0.0 {
+            return true;
+        }
+    }
+    return false;
+}
+
+fn background_color(direction: vec3<f32>) -> vec3<f32> {
+    let width = u32(params.shading.w);
+    let height = width / 2u;
+    let u = atan2(direction.z, direction.x) / (2.0 * PI) + 0.5;
+    let v = acos(clamp(direction.y, -1.0, 1.0)) / PI;
+    let x = min(u32(u * f32(width)), width - 1u);
+    let y = min(u32(v * f32(height)), height - 1u);
+
+    return background[x + y * width].xyz;
+}
+
+// lambertian response to the point lights, with hard shadows
+fn shade(position: vec3<f32>, hit: Hit) -> vec3<f32> {
+    let exposure = params.camera.w;
+    let origin = position + hit.normal * params.shading.z;
+    let t_min = params.shading.y * (1.0 + max(max(abs(position.x), abs(position.y)), abs(position.z)) + hit.distance);
+
+    var light = vec3<f32>(0.0);
+    for (var i = 0u; i < params.counts.z; i++) {
+        let to_light = lights[i].position.xyz - origin;
+        let distance = length(to_light);
+        let cos_theta = dot(hit.normal, to_light / distance);
+        if cos_theta <= 0.0 || is_occluded(origin, to_light / distance, t_min, distance) {
+            continue;
+        }
+
+        light += lights[i].intensity.xyz * (cos_theta / (PI * distance * distance) * exposure);
+    }
+
+    return hit.albedo * (light + params.shading.x) + hit.emission * exposure;
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.extent.x || id.y >= params.extent.y {
+        return;
+    }
+
+    let idx = id.x + id.y * params.extent.x;
+    var pixel: Pixel;
+    pixel.normal = vec4<f32>(0.0, 0.0, 0.0, 1e30);
+    pixel.albedo = vec4<f32>(0.0, 0.0, 0.0, bitcast<f32>(NO_ID));
+
+    for (var s = 0u; s < params.extent.z; s++) {
+        var state = hash(idx ^ hash(params.extent.w + s) ^ params.counts.w);
+        let x = f32(id.x) + random(&state);
+        let y = f32(id.y) + random(&state);
+
+        var origin: vec3<f32>;
+        let direction = pixel_ray(x, y, &origin);
+
+        let hit = closest_hit(origin, direction, params.camera.y, params.camera.z);
+
+        var color: vec3<f32>;
+        if hit.id == NO_ID {
+            color = background_color(direction);
+        } else {
+            var shading = hit;
+            if dot(shading.normal, direction) > 0.0 {
+                shading.normal = -shading.normal;
+            }
+            color = shade(origin + direction * hit.distance, shading);
+
+            if s == 0u {
+                pixel.normal = vec4<f32>(shading.normal, hit.distance);
+                pixel.albedo = vec4<f32>(hit.albedo, bitcast<f32>(hit.id));
+            }
+        }
+
+        let l = luminance(color);
+        pixel.color += vec4<f32>(color, l * l);
+    }
+
+    pixels[idx] = pixel;
+}
@@ -0,0 +1,293 @@
+use std::sync::Arc;
+
+use glam::{Quat, Vec3};
+
+use crate::raytracer::{aabb::Aabb, bvh::Bvh, hit::Differentials, Hit, Hitable, Primitive, Ray};
+
+// rigid transform with a uniform scale, local to world
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: 1.,
+    };
+
+    pub fn new(translation: Vec3, rotation: Quat, scale: f32) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn point(&self, p: Vec3) -> Vec3 {
+        self.translation + self.rotation * (p * self.scale)
+    }
+
+    // inner is applied first
+    pub fn compose(&self, inner: &Transform) -> Self {
+        Self {
+            translation: self.point(inner.translation),
+            rotation: self.rotation * inner.rotation,
+            scale: self.scale * inner.scale,
+        }
+    }
+
+    pub fn lerp(&self, other: &Transform, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+}
+
+// keyframes of a transform, interpolated between the keys and held before the
+// first and after the last
+#[derive(Clone, Debug, Default)]
+pub struct MotionTrack {
+    keys: Vec<(f32, Transform)>,
+}
+
+impl MotionTrack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn key(mut self, time: f32, transform: Transform) -> Self {
+        let idx = self.keys.partition_point(|(t, _)| *t <= time);
+        self.keys.insert(idx, (time, transform));
+
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // time of the last key
+    pub fn end(&self) -> f32 {
+        self.keys.last().map_or(0., |(t, _)| *t)
+    }
+
+    pub fn sample(&self, time: f32) -> Option<Transform> {
+        let idx = self.keys.partition_point(|(t, _)| *t <= time);
+
+        match (idx.checked_sub(1).map(|i| self.keys[i]), self.keys.get(idx)) {
+            (Some((t0, a)), Some((t1, b))) => Some(a.lerp(b, (time - t0) / (t1 - t0))),
+            (Some((_, a)), None) => Some(a),
+            (None, Some((_, b))) => Some(*b),
+            (None, None) => None,
+        }
+    }
+
+    fn rescale(&mut self, center: Vec3, scale: f32) {
+        for (_, transform) in self.keys.iter_mut() {
+            transform.translation = (transform.translation - center) * scale;
+            transform.scale *= scale;
+        }
+    }
+}
+
+// models moved together, e.g. the spheres of a snowman, the children are shared
+// by the copies of the group
+#[derive(Clone)]
+pub struct Group {
+    name: String,
+    children: Arc<Vec<Arc<Primitive>>>,
+    // in the space of the group
+    bounds: Aabb,
+    bvh: Arc<Bvh>,
+    motion: MotionTrack,
+    transform: Transform,
+}
+
+impl Group {
+    pub fn build(name: &str, children: Vec<Primitive>) -> Self {
+        Self::shared(name, children.into_iter().map(Arc::new).collect())
+    }
+
+    // the models are shared with the scene, e.g. a model moved by the tracer
+    pub(crate) fn shared(name: &str, children: Vec<Arc<Primitive>>) -> Self {
+        let child_bounds = children.iter().map(|c| c.bounds()).collect::<Vec<_>>();
+        let bounds = child_bounds
+            .iter()
+            .fold(Aabb::empty(), |bounds, b| bounds.union(b));
+
+        Self {
+            name: name.to_string(),
+            bvh: Arc::new(Bvh::from_bounds(&child_bounds)),
+            children: Arc::new(children),
+            bounds,
+            motion: MotionTrack::new(),
+            transform: Transform::IDENTITY,
+        }
+    }
+
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+
+        self
+    }
+
+    // the transform follows the track, see Tracer::set_time
+    pub fn motion(mut self, motion: MotionTrack) -> Self {
+        if let Some(transform) = motion.sample(0.) {
+            self.transform = transform;
+        }
+        self.motion = motion;
+
+        self
+    }
+
+    pub fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    // in the space of the group
+    pub fn children(&self) -> &[Arc<Primitive>] {
+        &self.children
+    }
+
+    // None if the group has no motion
+    pub(crate) fn at_time(&self, time: f32) -> Option<Self> {
+        let transform = self.motion.sample(time)?;
+
+        Some(self.clone().transform(transform))
+    }
+}
+
+impl From<Group> for Primitive {
+    fn from(group: Group) -> Self {
+        Primitive::GROUP(group)
+    }
+}
+
+impl Hitable for Group {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn bounds(&self) -> Aabb {
+        let transform = self.transform;
+        let (min, max) = (self.bounds.min, self.bounds.max);
+
+        (0..8).fold(Aabb::empty(), |bounds, i| {
+            let corner = Vec3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            );
+
+            bounds.grow(transform.point(corner))
+        })
+    }
+
+    fn hit_distance(&self, ray: &Ray, min: f32, max: f32) -> Option<f32> {
+        let transform = self.transform;
+        let local = Ray::new(
+            transform.rotation.inverse() * (ray.origin - transform.translation) / transform.scale,
+            transform.rotation.inverse() * ray.direction,
+        );
+        let (min, max) = (min / transform.scale, max / transform.scale);
+
+        self.bounds.hit(&local, min, max)?;
+
+        let mut closest = None;
+        self.bvh.visit(&local, min, max, |i, max| {
+            let t = self.children[i].hit_distance(&local, min, max)?;
+            closest = Some(t);
+
+            Some(t)
+        });
+
+        closest.map(|t| t * transform.scale)
+    }
+
+    fn hit(&self, ray: &Ray, min: f32, max: f32) -> Option<Hit> {
+        let transform = self.transform;
+        let local = Ray::new(
+            transform.rotation.inverse() * (ray.origin - transform.translation) / transform.scale,
+            transform.rotation.inverse() * ray.direction,
+        );
+        let (min, max) = (min / transform.scale, max / transform.scale);
+
+        self.bounds.hit(&local, min, max)?;
+
+        let mut closest: Option<Hit> = None;
+        self.bvh.visit(&local, min, max, |i, max| {
+            let hit = self.children[i].hit(&local, min, max)?;
+            let t = hit.distance;
+            closest = Some(hit);
+
+            Some(t)
+        });
+
+        closest.map(|hit| Hit {
+            distance: hit.distance * transform.scale,
+            position: transform.point(hit.position),
+            normal: (transform.rotation * hit.normal).normalize(),
+            differentials: Differentials::new(
+                transform.rotation * hit.differentials.dpdu * transform.scale,
+                transform.rotation * hit.differentials.dpdv * transform.scale,
+            )
+            .normal(
+                transform.rotation * hit.differentials.dndu,
+                transform.rotation * hit.differentials.dndv,
+            ),
+            ..hit
+        })
+    }
+
+    fn transmittance(&self, ray: &Ray, min: f32, max: f32) -> f32 {
+        let transform = self.transform;
+        let local = Ray::new(
+            transform.rotation.inverse() * (ray.origin - transform.translation) / transform.scale,
+            transform.rotation.inverse() * ray.direction,
+        );
+        let (min, max) = (min / transform.scale, max / transform.scale);
+
+        if self.bounds.hit(&local, min, max).is_none() {
+            return 1.;
+        }
+
+        self.children
+            .iter()
+            .filter(|c| c.is_medium())
+            .map(|c| c.transmittance(&local, min, max))
+            .product()
+    }
+
+    fn is_medium(&self) -> bool {
+        self.children.iter().any(|c| c.is_medium())
+    }
+
+    fn shadow_softness(&self) -> f32 {
+        self.children
+            .first()
+            .map(|c| c.shadow_softness())
+            .unwrap_or(1.)
+    }
+
+    fn rescale(&mut self, center: Vec3, scale: f32) -> bool {
+        self.transform.translation = (self.transform.translation - center) * scale;
+        self.transform.scale *= scale;
+
+        self.motion.rescale(center, scale);
+
+        true
+    }
+}
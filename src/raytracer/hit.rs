@@ -1,19 +1,121 @@
-use glam::Vec3;
-use gobs::core::Color;
+use glam::{Vec2, Vec3};
 
-use crate::raytracer::Ray;
+use crate::raytracer::{aabb::Aabb, material::Clearcoat, sampling::Onb, Color, Ray};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Hit {
     pub distance: f32,
     pub position: Vec3,
     pub normal: Vec3,
+    pub uv: Vec2,
     pub color: Color,
     pub reflect: f32,
+    pub clearcoat: Option<Clearcoat>,
+    pub emission: Color,
+    pub differentials: Differentials,
+    // false if the ray hit the back of the surface, the normal always faces the ray
+    pub front_face: bool,
+    // collision inside a participating medium, scattered by its phase function
+    // instead of a surface, the normal is then the reverse of the ray
+    pub medium: bool,
+    // index of the model in the scene
+    pub id: u32,
+}
+
+// derivatives of the position and of the outward normal along the texture coordinates
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Differentials {
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    pub dndu: Vec3,
+    pub dndv: Vec3,
+}
+
+impl Differentials {
+    pub fn new(dpdu: Vec3, dpdv: Vec3) -> Self {
+        Self {
+            dpdu,
+            dpdv,
+            ..Default::default()
+        }
+    }
+
+    pub fn normal(mut self, dndu: Vec3, dndv: Vec3) -> Self {
+        self.dndu = dndu;
+        self.dndv = dndv;
+
+        self
+    }
+}
+
+impl Hit {
+    pub fn with_id(self, id: usize) -> Self {
+        Self {
+            id: id as u32,
+            ..self
+        }
+    }
+
+    // normal is the outward normal of the surface
+    pub fn facing(self, ray: &Ray) -> Self {
+        let front_face = self.normal.dot(ray.direction) <= 0.;
+
+        Self {
+            normal: if front_face {
+                self.normal
+            } else {
+                -self.normal
+            },
+            front_face,
+            ..self
+        }
+    }
+
+    // shading frame around the normal
+    pub fn onb(&self) -> Onb {
+        Onb::new(self.normal)
+    }
+}
+
+// uniform point on the surface of a model, area is the area of the whole surface
+#[derive(Clone, Copy, Debug)]
+pub struct SurfaceSample {
+    pub position: Vec3,
+    // outward
+    pub normal: Vec3,
+    pub emission: Color,
+    pub area: f32,
 }
 
 pub trait Hitable {
     fn name(&self) -> &str;
     fn hit(&self, ray: &Ray, min: f32, max: f32) -> Option<Hit>;
     fn hit_distance(&self, ray: &Ray, min: f32, max: f32) -> Option<f32>;
+    // unbounded by default, such models are tested by every ray
+    fn bounds(&self) -> Aabb {
+        Aabb::infinite()
+    }
+    fn sphere(&self) -> Option<(Vec3, f32)> {
+        None
+    }
+    fn shadow_softness(&self) -> f32 {
+        1.
+    }
+    // fraction of the light going through the model between min and max, the
+    // participating media attenuate the shadow rays instead of blocking them
+    fn transmittance(&self, _ray: &Ray, _min: f32, _max: f32) -> f32 {
+        1.
+    }
+    fn is_medium(&self) -> bool {
+        false
+    }
+    // positions become (p - center) * scale, false if the model cannot be rescaled
+    fn rescale(&mut self, _center: Vec3, _scale: f32) -> bool {
+        false
+    }
+    // point of an emissive surface for u, v in [0, 1], None if the model doesn't
+    // emit light or cannot be sampled
+    fn sample_emitter(&self, _u: f32, _v: f32) -> Option<SurfaceSample> {
+        None
+    }
 }
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+use crate::raytracer::Hit;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Integrator {
+    #[default]
+    WHITTED,
+    TOON(Toon),
+    // diffuse and mirror bounces, the lights and the emissive surfaces are sampled
+    // at each diffuse bounce
+    PATH,
+}
+
+// cel shading with silhouette and crease lines
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Toon {
+    pub bands: u32,
+    // relative depth change between neighbour pixels
+    pub depth_threshold: f32,
+    // 1 - cos of the angle between neighbour normals
+    pub normal_threshold: f32,
+}
+
+impl Default for Toon {
+    fn default() -> Self {
+        Self {
+            bands: 3,
+            depth_threshold: 0.1,
+            normal_threshold: 0.3,
+        }
+    }
+}
+
+impl Toon {
+    pub fn new(bands: u32, depth_threshold: f32, normal_threshold: f32) -> Self {
+        Self {
+            bands: bands.max(1),
+            depth_threshold,
+            normal_threshold,
+        }
+    }
+
+    pub fn quantize(&self, intensity: f32) -> f32 {
+        let bands = self.bands as f32;
+
+        (intensity.clamp(0., 1.) * bands).ceil() / bands
+    }
+
+    pub fn is_edge(&self, hit: &Option<Hit>, neighbour: &Option<Hit>) -> bool {
+        match (hit, neighbour) {
+            (None, None) => false,
+            // silhouette against the background
+            (Some(_), None) | (None, Some(_)) => true,
+            (Some(hit), Some(neighbour)) => {
+                let depth = (hit.distance - neighbour.distance).abs() / hit.distance;
+                let crease = 1. - hit.normal.dot(neighbour.normal);
+
+                depth > self.depth_threshold || crease > self.normal_threshold
+            }
+        }
+    }
+}
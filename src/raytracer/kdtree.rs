@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use crate::raytracer::{aabb::Aabb, stats, Hit, Hitable, Primitive, Ray};
+
+enum KdNode {
+    Leaf {
+        items: Vec<usize>,
+    },
+    Inner {
+        axis: usize,
+        split: f32,
+        left: usize,
+        right: usize,
+    },
+}
+
+pub struct KdTree {
+    bounds: Aabb,
+    nodes: Vec<KdNode>,
+}
+
+impl KdTree {
+    const LEAF_SIZE: usize = 2;
+    const MAX_DEPTH: u32 = 20;
+
+    pub fn new(models: &[Arc<Primitive>]) -> Self {
+        let bounds = models.iter().map(|m| m.bounds()).collect::<Vec<Aabb>>();
+        let scene_bounds = bounds.iter().fold(Aabb::empty(), |acc, b| acc.union(b));
+
+        let mut tree = Self {
+            bounds: scene_bounds,
+            nodes: Vec::new(),
+        };
+
+        if !bounds.is_empty() {
+            tree.build(&bounds, (0..models.len()).collect(), scene_bounds, 0);
+        }
+
+        log::debug!("Kd-tree: {} nodes", tree.nodes.len());
+
+        tree
+    }
+
+    fn build(&mut self, bounds: &[Aabb], items: Vec<usize>, cell: Aabb, depth: u32) -> usize {
+        let idx = self.nodes.len();
+        self.nodes.push(KdNode::Leaf { items: Vec::new() });
+
+        if items.len() <= Self::LEAF_SIZE || depth >= Self::MAX_DEPTH {
+            self.nodes[idx] = KdNode::Leaf { items };
+            return idx;
+        }
+
+        let axis = cell.largest_axis();
+
+        let mut centroids = items
+            .iter()
+            .map(|&i| bounds[i].centroid()[axis])
+            .collect::<Vec<f32>>();
+        centroids.sort_by(f32::total_cmp);
+        let split = centroids[centroids.len() / 2];
+
+        if split <= cell.min[axis] || split >= cell.max[axis] {
+            self.nodes[idx] = KdNode::Leaf { items };
+            return idx;
+        }
+
+        // items straddling the split plane go to both sides
+        let left_items = items
+            .iter()
+            .copied()
+            .filter(|&i| bounds[i].min[axis] <= split)
+            .collect::<Vec<usize>>();
+        let right_items = items
+            .iter()
+            .copied()
+            .filter(|&i| bounds[i].max[axis] >= split)
+            .collect::<Vec<usize>>();
+
+        if left_items.len() == items.len() && right_items.len() == items.len() {
+            self.nodes[idx] = KdNode::Leaf { items };
+            return idx;
+        }
+
+        let mut left_cell = cell;
+        left_cell.max[axis] = split;
+        let mut right_cell = cell;
+        right_cell.min[axis] = split;
+
+        let left = self.build(bounds, left_items, left_cell, depth + 1);
+        let right = self.build(bounds, right_items, right_cell, depth + 1);
+
+        self.nodes[idx] = KdNode::Inner {
+            axis,
+            split,
+            left,
+            right,
+        };
+
+        idx
+    }
+
+    pub fn hit(&self, models: &[Arc<Primitive>], ray: &Ray, min: f32, max: f32) -> Option<Hit> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let (t_min, t_max) = self.bounds.hit(ray, min, max)?;
+
+        self.traverse(0, models, ray, min, t_min, t_max)
+    }
+
+    fn traverse(
+        &self,
+        idx: usize,
+        models: &[Arc<Primitive>],
+        ray: &Ray,
+        min: f32,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<Hit> {
+        match &self.nodes[idx] {
+            KdNode::Leaf { items } => {
+                stats::record_traversal(1, items.len() as u32);
+
+                // only accept hits inside the cell, farther ones may be hidden by the next cells
+                items
+                    .iter()
+                    .filter_map(|&i| models[i].hit(ray, min, t_max).map(|hit| hit.with_id(i)))
+                    .min_by(|h1, h2| h1.distance.total_cmp(&h2.distance))
+            }
+            KdNode::Inner {
+                axis,
+                split,
+                left,
+                right,
+            } => {
+                stats::record_traversal(1, 0);
+
+                let origin = ray.origin[*axis];
+                let direction = ray.direction[*axis];
+
+                let (near, far) = if origin < *split || (origin == *split && direction <= 0.) {
+                    (*left, *right)
+                } else {
+                    (*right, *left)
+                };
+
+                if direction == 0. {
+                    return self.traverse(near, models, ray, min, t_min, t_max);
+                }
+
+                let t_split = (*split - origin) / direction;
+
+                if t_split > t_max || t_split <= 0. {
+                    self.traverse(near, models, ray, min, t_min, t_max)
+                } else if t_split < t_min {
+                    self.traverse(far, models, ray, min, t_min, t_max)
+                } else {
+                    self.traverse(near, models, ray, min, t_min, t_split)
+                        .or_else(|| self.traverse(far, models, ray, min, t_split, t_max))
+                }
+            }
+        }
+    }
+
+    pub fn occluded(&self, models: &[Arc<Primitive>], ray: &Ray, min: f32, max: f32) -> bool {
+        self.hit(models, ray, min, max).is_some()
+    }
+}
@@ -0,0 +1,117 @@
+use std::f32::consts::PI;
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::raytracer::{sampling, Color};
+
+// lm/W at 555nm
+pub const LUMINOUS_EFFICACY: f32 = 683.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightPower {
+    WATTS(f32),
+    LUMENS(f32),
+}
+
+impl LightPower {
+    pub fn lumens(&self) -> f32 {
+        match *self {
+            LightPower::WATTS(watts) => watts_to_lumens(watts),
+            LightPower::LUMENS(lumens) => lumens,
+        }
+    }
+
+    pub fn watts(&self) -> f32 {
+        match *self {
+            LightPower::WATTS(watts) => watts,
+            LightPower::LUMENS(lumens) => lumens_to_watts(lumens),
+        }
+    }
+}
+
+pub fn watts_to_lumens(watts: f32) -> f32 {
+    watts * LUMINOUS_EFFICACY
+}
+
+pub fn lumens_to_watts(lumens: f32) -> f32 {
+    lumens / LUMINOUS_EFFICACY
+}
+
+// scale applied to luminance for a given exposure value at ISO 100
+pub fn ev100_to_exposure(ev100: f32) -> f32 {
+    1. / (1.2 * 2_f32.powf(ev100))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum LightSampling {
+    // every light is tested for each sample
+    #[default]
+    ALL,
+    // a single random light is tested for each sample
+    ONE,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PointLight {
+    pub position: Vec3,
+    pub color: Color,
+    pub power: LightPower,
+    // spherical light with soft shadows when > 0
+    pub radius: f32,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, color: Color, power: LightPower) -> Self {
+        Self {
+            position,
+            color,
+            power,
+            radius: 0.,
+        }
+    }
+
+    pub fn radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+
+        self
+    }
+
+    // uniform point on the surface of the light
+    pub fn sample(&self, u: f32, v: f32) -> Vec3 {
+        if self.radius <= 0. {
+            return self.position;
+        }
+
+        self.position + self.radius * sampling::uniform_sphere(u, v)
+    }
+
+    // candela, the flux is emitted evenly over the whole sphere
+    pub fn intensity(&self) -> f32 {
+        self.power.lumens() / (4. * PI)
+    }
+
+    // bytes of the fields that change the image, for Tracer::scene_hash
+    pub(crate) fn fingerprint(&self) -> Vec<u8> {
+        let power = match self.power {
+            LightPower::WATTS(watts) => [0., watts],
+            LightPower::LUMENS(lumens) => [1., lumens],
+        };
+
+        [
+            self.position.to_array(),
+            [self.color.r, self.color.g, self.color.b],
+        ]
+        .into_iter()
+        .flatten()
+        .chain(power)
+        .chain([self.radius])
+        .flat_map(|v| v.to_le_bytes())
+        .collect()
+    }
+
+    // lux received at the given position, facing the light
+    pub fn illuminance(&self, position: Vec3) -> f32 {
+        self.intensity() / (self.position - position).length_squared()
+    }
+}
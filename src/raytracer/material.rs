@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use glam::{Vec2, Vec3};
+
+use crate::raytracer::{aabb::Aabb, texture::RescaledTexture, Color, Texture};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Clearcoat {
+    pub strength: f32,
+    pub ior: f32,
+}
+
+impl Clearcoat {
+    fn fresnel(&self, cos_theta: f32) -> f32 {
+        let r0 = ((1. - self.ior) / (1. + self.ior)).powi(2);
+
+        r0 + (1. - r0) * (1. - cos_theta.clamp(0., 1.)).powi(5)
+    }
+
+    pub fn reflectance(&self, cos_theta: f32) -> f32 {
+        self.strength * self.fresnel(cos_theta)
+    }
+
+    pub fn layer(&self, base: Color, coat: Color, cos_theta: f32) -> Color {
+        // energy that is reflected by the coat never reaches the base layer
+        let f = self.reflectance(cos_theta);
+
+        base * (1. - f) + coat * f
+    }
+}
+
+// diffuse and mirror weights of a material against the cosine between the view and
+// the normal, read by the preview shading instead of evaluating the layers
+#[derive(Clone, Debug)]
+pub(crate) struct ResponseLut {
+    diffuse: [f32; Self::SIZE],
+    reflect: [f32; Self::SIZE],
+}
+
+impl ResponseLut {
+    const SIZE: usize = 32;
+
+    pub fn bake(material: &Material) -> Self {
+        let mut diffuse = [0.; Self::SIZE];
+        let mut reflect = [0.; Self::SIZE];
+
+        for i in 0..Self::SIZE {
+            let cos_theta = i as f32 / (Self::SIZE - 1) as f32;
+
+            reflect[i] = match material.clearcoat {
+                Some(clearcoat) => {
+                    let f = clearcoat.reflectance(cos_theta);
+                    material.reflect * (1. - f) + f
+                }
+                None => material.reflect,
+            };
+            diffuse[i] = 1. - reflect[i];
+        }
+
+        Self { diffuse, reflect }
+    }
+
+    // diffuse and mirror weights
+    pub fn lookup(&self, cos_theta: f32) -> (f32, f32) {
+        let i = (cos_theta.clamp(0., 1.) * (Self::SIZE - 1) as f32).round() as usize;
+
+        (self.diffuse[i], self.reflect[i])
+    }
+}
+
+#[derive(Clone)]
+pub struct Material {
+    pub albedo: Arc<dyn Texture + Send + Sync>,
+    pub reflect: f32,
+    pub clearcoat: Option<Clearcoat>,
+    // scales the size of the lights when this object casts a shadow
+    pub shadow_softness: f32,
+    // luminance in cd/m2, the alpha of the surface is left to the albedo
+    pub emission: Color,
+}
+
+impl Material {
+    pub fn new(albedo: Arc<dyn Texture + Send + Sync>, reflect: f32) -> Self {
+        Self {
+            albedo,
+            reflect,
+            clearcoat: None,
+            shadow_softness: 1.,
+            emission: Color::new(0., 0., 0., 0.),
+        }
+    }
+
+    pub fn clearcoat(mut self, strength: f32, ior: f32) -> Self {
+        self.clearcoat = Some(Clearcoat { strength, ior });
+
+        self
+    }
+
+    pub fn shadow_softness(mut self, shadow_softness: f32) -> Self {
+        self.shadow_softness = shadow_softness;
+
+        self
+    }
+
+    pub fn emissive(mut self, color: Color, luminance: f32) -> Self {
+        self.emission = Color {
+            a: 0.,
+            ..color * luminance
+        };
+
+        self
+    }
+
+    // color of a black body at the temperature in kelvin, e.g. 2700 for a warm bulb
+    pub fn blackbody(self, kelvin: f32, luminance: f32) -> Self {
+        self.emissive(Color::from_temperature(kelvin), luminance)
+    }
+
+    // bytes of the fields that change the image, for Tracer::scene_hash: the textures
+    // have no description and are sampled at fixed points of the model bounds
+    pub(crate) fn fingerprint(&self, bounds: &Aabb) -> Vec<u8> {
+        const PROBES: u32 = 4;
+
+        let mut values = vec![
+            self.reflect,
+            self.shadow_softness,
+            self.emission.r,
+            self.emission.g,
+            self.emission.b,
+        ];
+        if let Some(clearcoat) = self.clearcoat {
+            values.extend([clearcoat.strength, clearcoat.ior]);
+        }
+
+        let (min, extent) = if bounds.extent().is_finite() {
+            (bounds.min, bounds.extent())
+        } else {
+            (Vec3::ZERO, Vec3::ONE)
+        };
+        for i in 0..PROBES * PROBES {
+            let uv = (Vec2::new((i % PROBES) as f32, (i / PROBES) as f32) + 0.5) / PROBES as f32;
+            let position = min + extent * uv.extend((uv.x + uv.y) / 2.);
+            let c = self.albedo.sample(uv, position);
+            values.extend([c.r, c.g, c.b, c.a]);
+        }
+
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    // with the model, the texture keeps its size in the units of the builder
+    pub(crate) fn rescale(&mut self, center: Vec3, scale: f32) {
+        if self.albedo.solid().is_none() {
+            self.albedo = Arc::new(RescaledTexture::new(self.albedo.clone(), center, scale));
+        }
+    }
+}
@@ -0,0 +1,455 @@
+use std::{fs, path::Path};
+
+use glam::{Vec2, Vec3};
+
+use crate::raytracer::{
+    aabb::Aabb, bvh::Bvh, hit::Differentials, sampling::Onb, Hit, Hitable, Material, Primitive, Ray,
+};
+
+pub struct Mesh {
+    name: String,
+    positions: Vec<Vec3>,
+    triangles: Vec<[u32; 3]>,
+    // per vertex texture coordinates, barycentric coordinates are used without them
+    uvs: Option<Vec<Vec2>>,
+    material: Material,
+    bounds: Aabb,
+    bvh: Bvh,
+    // mean edge length, used to pick a level of detail
+    feature_size: f32,
+}
+
+impl Mesh {
+    const EPSILON: f32 = 1e-7;
+
+    pub fn new(
+        name: &str,
+        positions: Vec<Vec3>,
+        triangles: Vec<[u32; 3]>,
+        material: Material,
+    ) -> Primitive {
+        Primitive::MESH(Self::build(name, positions, triangles, material))
+    }
+
+    pub fn build(
+        name: &str,
+        positions: Vec<Vec3>,
+        triangles: Vec<[u32; 3]>,
+        material: Material,
+    ) -> Self {
+        let bvh = Bvh::from_bounds(&Self::triangle_bounds(&positions, &triangles));
+
+        Self::with_bvh(name, positions, triangles, material, bvh)
+    }
+
+    // the triangle bvh is read from cache_dir when it was already built for the same
+    // geometry, the file is keyed by a hash of the positions and triangles
+    pub fn build_cached(
+        name: &str,
+        positions: Vec<Vec3>,
+        triangles: Vec<[u32; 3]>,
+        material: Material,
+        cache_dir: &str,
+    ) -> Self {
+        let key = Self::content_hash(&positions, &triangles);
+        let path = Path::new(cache_dir).join(format!("{:016x}.bvh", key));
+        let path = path.to_string_lossy();
+
+        let bvh = match Bvh::load(&path, key, triangles.len()) {
+            Ok(bvh) => {
+                log::debug!("BVH loaded from cache: {}", path);
+                bvh
+            }
+            Err(_) => {
+                let bvh = Bvh::from_bounds(&Self::triangle_bounds(&positions, &triangles));
+                let saved = fs::create_dir_all(cache_dir).and_then(|_| bvh.save(&path, key));
+                if let Err(e) = saved {
+                    log::warn!("Cannot save BVH to {}: {}", path, e);
+                }
+                bvh
+            }
+        };
+
+        Self::with_bvh(name, positions, triangles, material, bvh)
+    }
+
+    // FNV-1a, stable across runs and platforms
+    pub fn content_hash(positions: &[Vec3], triangles: &[[u32; 3]]) -> u64 {
+        const PRIME: u64 = 0x100000001b3;
+
+        let floats = positions
+            .iter()
+            .flat_map(|p| p.to_array())
+            .map(f32::to_bits);
+        let indices = triangles.iter().flatten().copied();
+
+        floats
+            .chain(indices)
+            .flat_map(u32::to_le_bytes)
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(PRIME)
+            })
+    }
+
+    fn triangle_bounds(positions: &[Vec3], triangles: &[[u32; 3]]) -> Vec<Aabb> {
+        triangles
+            .iter()
+            .map(|t| {
+                t.iter().fold(Aabb::empty(), |bounds, &i| {
+                    bounds.grow(positions[i as usize])
+                })
+            })
+            .collect()
+    }
+
+    fn with_bvh(
+        name: &str,
+        positions: Vec<Vec3>,
+        triangles: Vec<[u32; 3]>,
+        material: Material,
+        bvh: Bvh,
+    ) -> Self {
+        let bounds = positions
+            .iter()
+            .fold(Aabb::empty(), |bounds, &p| bounds.grow(p));
+
+        let perimeter: f32 = triangles
+            .iter()
+            .map(|t| {
+                let (a, b, c) = (
+                    positions[t[0] as usize],
+                    positions[t[1] as usize],
+                    positions[t[2] as usize],
+                );
+                a.distance(b) + b.distance(c) + c.distance(a)
+            })
+            .sum();
+        let feature_size = perimeter / (3 * triangles.len().max(1)) as f32;
+
+        Self {
+            name: name.to_string(),
+            positions,
+            triangles,
+            uvs: None,
+            material,
+            bounds,
+            bvh,
+            feature_size,
+        }
+    }
+
+    pub fn uvs(mut self, uvs: Vec<Vec2>) -> Self {
+        assert_eq!(uvs.len(), self.positions.len());
+
+        self.uvs = Some(uvs);
+
+        self
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub(crate) fn material(&self) -> &Material {
+        &self.material
+    }
+
+    #[cfg(feature = "gpu")]
+    pub(crate) fn triangle_positions(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.triangles
+            .iter()
+            .map(|triangle| triangle.map(|i| self.positions[i as usize]))
+    }
+
+    fn normal(&self, triangle: &[u32; 3]) -> Vec3 {
+        let a = self.positions[triangle[0] as usize];
+        let b = self.positions[triangle[1] as usize];
+        let c = self.positions[triangle[2] as usize];
+
+        (b - a).cross(c - a).normalize()
+    }
+
+    // derivatives of the position along the texture coordinates, the barycentric
+    // coordinates without uvs, None if the uvs of the triangle are degenerate
+    fn derivatives(&self, triangle: &[u32; 3]) -> Option<(Vec3, Vec3)> {
+        let p = triangle.map(|i| self.positions[i as usize]);
+        let uv = match &self.uvs {
+            Some(uvs) => triangle.map(|i| uvs[i as usize]),
+            None => [Vec2::ZERO, Vec2::X, Vec2::Y],
+        };
+
+        let (duv1, duv2) = (uv[1] - uv[0], uv[2] - uv[0]);
+        let (dp1, dp2) = (p[1] - p[0], p[2] - p[0]);
+
+        let det = duv1.x * duv2.y - duv1.y * duv2.x;
+        if det.abs() < 1e-12 {
+            return None;
+        }
+
+        Some((
+            (duv2.y * dp1 - duv1.y * dp2) / det,
+            (duv1.x * dp2 - duv2.x * dp1) / det,
+        ))
+    }
+
+    // texels of a width x height map covered by the mesh, with their position and normal
+    pub fn texels(&self, width: u32, height: u32) -> Vec<(usize, Vec3, Vec3)> {
+        let Some(uvs) = &self.uvs else {
+            return Vec::new();
+        };
+
+        let mut texels = Vec::new();
+        let size = Vec2::new(width as f32, height as f32);
+
+        for triangle in &self.triangles {
+            let [ia, ib, ic] = triangle.map(|i| i as usize);
+            let (ta, tb, tc) = (uvs[ia] * size, uvs[ib] * size, uvs[ic] * size);
+
+            let area = (tb - ta).perp_dot(tc - ta);
+            if area.abs() < Self::EPSILON {
+                continue;
+            }
+
+            let normal = self.normal(triangle);
+
+            let min = ta.min(tb).min(tc).floor().max(Vec2::ZERO);
+            let max = ta.max(tb).max(tc).ceil().min(size);
+
+            for y in min.y as u32..max.y as u32 {
+                for x in min.x as u32..max.x as u32 {
+                    let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+
+                    let wa = (tb - p).perp_dot(tc - p) / area;
+                    let wb = (tc - p).perp_dot(ta - p) / area;
+                    let wc = 1. - wa - wb;
+
+                    if wa < 0. || wb < 0. || wc < 0. {
+                        continue;
+                    }
+
+                    let position =
+                        self.positions[ia] * wa + self.positions[ib] * wb + self.positions[ic] * wc;
+
+                    texels.push(((x + y * width) as usize, position, normal));
+                }
+            }
+        }
+
+        texels
+    }
+
+    // Möller-Trumbore, returns the distance and barycentric coordinates
+    fn hit_triangle(
+        &self,
+        triangle: &[u32; 3],
+        ray: &Ray,
+        min: f32,
+        max: f32,
+    ) -> Option<(f32, Vec2)> {
+        let a = self.positions[triangle[0] as usize];
+        let e1 = self.positions[triangle[1] as usize] - a;
+        let e2 = self.positions[triangle[2] as usize] - a;
+
+        let p = ray.direction.cross(e2);
+        let det = e1.dot(p);
+        if det.abs() < Self::EPSILON {
+            return None;
+        }
+        let inv_det = 1. / det;
+
+        let s = ray.origin - a;
+        let u = s.dot(p) * inv_det;
+        if !(0. ..=1.).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(e1);
+        let v = ray.direction.dot(q) * inv_det;
+        if v < 0. || u + v > 1. {
+            return None;
+        }
+
+        let t = e2.dot(q) * inv_det;
+        if t >= min && t <= max {
+            Some((t, Vec2::new(u, v)))
+        } else {
+            None
+        }
+    }
+
+    fn closest(&self, ray: &Ray, min: f32, max: f32) -> Option<(f32, Vec2, usize)> {
+        self.bounds.hit(ray, min, max)?;
+
+        let mut closest = None;
+
+        self.bvh.visit(ray, min, max, |i, max| {
+            let (t, uv) = self.hit_triangle(&self.triangles[i], ray, min, max)?;
+            closest = Some((t, uv, i));
+
+            Some(t)
+        });
+
+        closest
+    }
+}
+
+impl Hitable for Mesh {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn shadow_softness(&self) -> f32 {
+        self.material.shadow_softness
+    }
+
+    fn rescale(&mut self, center: Vec3, scale: f32) -> bool {
+        for p in self.positions.iter_mut() {
+            *p = (*p - center) * scale;
+        }
+
+        self.bounds = Aabb::new(
+            (self.bounds.min - center) * scale,
+            (self.bounds.max - center) * scale,
+        );
+        self.bvh = Bvh::from_bounds(&Self::triangle_bounds(&self.positions, &self.triangles));
+        self.feature_size *= scale;
+        self.material.rescale(center, scale);
+
+        true
+    }
+
+    fn hit_distance(&self, ray: &Ray, min: f32, max: f32) -> Option<f32> {
+        self.closest(ray, min, max).map(|(t, _, _)| t)
+    }
+
+    fn hit(&self, ray: &Ray, min: f32, max: f32) -> Option<Hit> {
+        let (t, barycentric, i) = self.closest(ray, min, max)?;
+
+        let triangle = &self.triangles[i];
+
+        let uv = match &self.uvs {
+            Some(uvs) => {
+                uvs[triangle[0] as usize] * (1. - barycentric.x - barycentric.y)
+                    + uvs[triangle[1] as usize] * barycentric.x
+                    + uvs[triangle[2] as usize] * barycentric.y
+            }
+            None => barycentric,
+        };
+
+        // flat shading
+        let normal = self.normal(triangle);
+        let (dpdu, dpdv) = self.derivatives(triangle).unwrap_or_else(|| {
+            let onb = Onb::new(normal);
+            (onb.tangent, onb.bitangent)
+        });
+        let position = ray.origin + t * ray.direction;
+
+        Some(
+            Hit {
+                distance: t,
+                position,
+                normal,
+                uv,
+                color: self.material.albedo.sample(uv, position),
+                reflect: self.material.reflect,
+                clearcoat: self.material.clearcoat,
+                emission: self.material.emission,
+                differentials: Differentials::new(dpdu, dpdv),
+                front_face: true,
+                medium: false,
+                id: 0,
+            }
+            .facing(ray),
+        )
+    }
+}
+
+// set of meshes of decreasing detail for the same model
+pub struct LodMesh {
+    name: String,
+    levels: Vec<Mesh>,
+    // angle covered by one pixel, in radians
+    pixel_spread: f32,
+    bounds: Aabb,
+}
+
+impl LodMesh {
+    // levels are ordered from the most to the least detailed
+    pub fn new(name: &str, levels: Vec<Mesh>, pixel_spread: f32) -> Primitive {
+        assert!(!levels.is_empty());
+
+        let bounds = levels
+            .iter()
+            .fold(Aabb::empty(), |bounds, level| bounds.union(&level.bounds));
+
+        Primitive::LOD(Self {
+            name: name.to_string(),
+            levels,
+            pixel_spread,
+            bounds,
+        })
+    }
+
+    // the levels share the material of the most detailed one
+    pub(crate) fn material(&self) -> &Material {
+        self.levels[0].material()
+    }
+
+    // width of the ray cone at the given distance
+    fn footprint(&self, distance: f32) -> f32 {
+        distance * self.pixel_spread
+    }
+
+    // coarsest level whose details are still smaller than the ray footprint
+    fn select(&self, ray: &Ray, min: f32, max: f32) -> Option<&Mesh> {
+        let (t, _) = self.bounds.hit(ray, min, max)?;
+        let footprint = self.footprint(t);
+
+        let level = self
+            .levels
+            .iter()
+            .rposition(|level| level.feature_size <= footprint)
+            .unwrap_or(0);
+
+        Some(&self.levels[level])
+    }
+}
+
+impl Hitable for LodMesh {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn shadow_softness(&self) -> f32 {
+        self.levels[0].shadow_softness()
+    }
+
+    fn rescale(&mut self, center: Vec3, scale: f32) -> bool {
+        for level in self.levels.iter_mut() {
+            level.rescale(center, scale);
+        }
+
+        self.bounds = Aabb::new(
+            (self.bounds.min - center) * scale,
+            (self.bounds.max - center) * scale,
+        );
+
+        true
+    }
+
+    fn hit_distance(&self, ray: &Ray, min: f32, max: f32) -> Option<f32> {
+        self.select(ray, min, max)?.hit_distance(ray, min, max)
+    }
+
+    fn hit(&self, ray: &Ray, min: f32, max: f32) -> Option<Hit> {
+        self.select(ray, min, max)?.hit(ray, min, max)
+    }
+}
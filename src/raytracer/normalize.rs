@@ -0,0 +1,138 @@
+use glam::{Mat4, Vec3};
+
+use crate::raytracer::{
+    aabb::Aabb,
+    camera::ProjectionMode,
+    light::{LightPower, PointLight},
+    portal::Portal,
+    Camera, Hit, Ray,
+};
+
+// maps the scene into a cube of side 2 around the origin, the fixed epsilons
+// and the float precision work best for positions in this range
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Normalization {
+    pub center: Vec3,
+    pub scale: f32,
+}
+
+impl Normalization {
+    const SIZE: f32 = 2.;
+
+    pub fn from_bounds(bounds: &Aabb) -> Option<Self> {
+        let size = bounds.extent().max_element();
+        if !size.is_finite() || size <= 0. {
+            return None;
+        }
+
+        Some(Self {
+            center: bounds.centroid(),
+            scale: Self::SIZE / size,
+        })
+    }
+
+    pub fn point(&self, p: Vec3) -> Vec3 {
+        (p - self.center) * self.scale
+    }
+
+    pub fn world_point(&self, p: Vec3) -> Vec3 {
+        p / self.scale + self.center
+    }
+
+    // from the units of the render back to the units of the builder
+    pub fn inverse(&self) -> Self {
+        Self {
+            center: -self.center * self.scale,
+            scale: 1. / self.scale,
+        }
+    }
+
+    pub fn ray(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.point(ray.origin),
+            direction: ray.direction,
+        }
+    }
+
+    pub fn world_hit(&self, hit: &Hit) -> Hit {
+        let mut hit = *hit;
+        hit.position = self.world_point(hit.position);
+        hit.distance /= self.scale;
+        hit.differentials.dpdu /= self.scale;
+        hit.differentials.dpdv /= self.scale;
+
+        hit
+    }
+
+    // the power is scaled with the square of the distances to keep the same illuminance
+    pub fn light(&self, light: &PointLight) -> PointLight {
+        let factor = self.scale * self.scale;
+        let power = match light.power {
+            LightPower::WATTS(watts) => LightPower::WATTS(watts * factor),
+            LightPower::LUMENS(lumens) => LightPower::LUMENS(lumens * factor),
+        };
+
+        PointLight {
+            position: self.point(light.position),
+            power,
+            radius: light.radius * self.scale,
+            ..*light
+        }
+    }
+
+    pub fn portal(&self, portal: &Portal) -> Portal {
+        Portal::new(
+            self.point(portal.corner),
+            portal.u * self.scale,
+            portal.v * self.scale,
+        )
+    }
+
+    pub fn camera(&self, camera: &Camera) -> Camera {
+        let mode = match camera.mode {
+            ProjectionMode::PERSPECTIVE {
+                aspect,
+                fovy,
+                near,
+                far,
+            } => ProjectionMode::PERSPECTIVE {
+                aspect,
+                fovy,
+                near: near * self.scale,
+                far: far * self.scale,
+            },
+            ProjectionMode::ORTHO {
+                width,
+                height,
+                near,
+                far,
+            } => ProjectionMode::ORTHO {
+                width: width * self.scale,
+                height: height * self.scale,
+                near: near * self.scale,
+                far: far * self.scale,
+            },
+            ProjectionMode::MATRIX {
+                inverse_view_proj,
+                perspective,
+                near_z,
+                near,
+                far,
+            } => ProjectionMode::MATRIX {
+                inverse_view_proj: Mat4::from_scale(Vec3::splat(self.scale))
+                    * Mat4::from_translation(-self.center)
+                    * inverse_view_proj,
+                perspective,
+                near_z,
+                near: near * self.scale,
+                far: far * self.scale,
+            },
+        };
+
+        Camera {
+            position: self.point(camera.position),
+            mode,
+            ..*camera
+        }
+    }
+}
@@ -0,0 +1,74 @@
+// timings of the last call to Tracer::update(), in seconds
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameStats {
+    pub update_time: f32,
+    // fraction of the frame budget spent in update(), 0 without budget
+    pub budget_used: f32,
+    // chunks added to the image by the last update
+    pub chunks: u32,
+}
+
+// adapts the number of chunks dispatched per update to the frame time of the host
+// and to the cost of the chunks
+#[derive(Clone, Copy, Debug)]
+pub struct FramePacing {
+    // target frame time in seconds
+    pub budget: f32,
+    chunks: u32,
+    min_chunks: u32,
+    max_chunks: u32,
+    // moving average of the time to compute a chunk, 0 until measured
+    chunk_time: f32,
+}
+
+impl FramePacing {
+    // frame times under this fraction of the budget allow more chunks
+    const HEADROOM: f32 = 0.8;
+    // weight of the last chunk in the average
+    const SMOOTHING: f32 = 0.2;
+    // 30 fps when the host doesn't give a budget
+    pub const DEFAULT_BUDGET: f32 = 1. / 30.;
+
+    pub fn new(budget: f32, max_chunks: u32) -> Self {
+        Self {
+            budget,
+            chunks: max_chunks.max(1),
+            min_chunks: 1,
+            max_chunks: max_chunks.max(1),
+            chunk_time: 0.,
+        }
+    }
+
+    pub fn min_chunks(mut self, min_chunks: u32) -> Self {
+        self.min_chunks = min_chunks.clamp(1, self.max_chunks);
+
+        self
+    }
+
+    // chunks fitting in the budget, each thread of the pool computing its own
+    pub fn chunks(&self) -> u32 {
+        if self.chunk_time <= 0. {
+            return self.chunks;
+        }
+
+        let fit = (self.budget / self.chunk_time) as u32 * self.max_chunks;
+
+        self.chunks.min(fit).max(self.min_chunks)
+    }
+
+    pub fn chunk_time(&mut self, elapsed: f32) {
+        self.chunk_time = if self.chunk_time <= 0. {
+            elapsed
+        } else {
+            self.chunk_time * (1. - Self::SMOOTHING) + elapsed * Self::SMOOTHING
+        };
+    }
+
+    pub fn frame_time(&mut self, delta: f32) {
+        if delta > self.budget {
+            self.chunks = (self.chunks / 2).max(self.min_chunks);
+        } else if delta < self.budget * Self::HEADROOM {
+            self.chunks = (self.chunks + 1).min(self.max_chunks);
+        }
+    }
+}
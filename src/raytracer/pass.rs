@@ -0,0 +1,15 @@
+#[derive(Clone, Copy, Debug)]
+pub struct PassStats {
+    pub pass: u32,
+    pub pixels: usize,
+    pub samples_per_pixel: u32,
+    pub elapsed: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PassControl {
+    CONTINUE,
+    STOP,
+}
+
+pub type PassHook = Box<dyn FnMut(&PassStats) -> PassControl + Send>;
@@ -0,0 +1,27 @@
+use std::sync::Arc;
+
+use crate::raytracer::{Color, Hit, Ray};
+
+// custom state carried along the path of a single sample, e.g. wavelength or
+// polarization, without changing the tracing loop
+pub trait Payload: Send {
+    // called at each surface hit, the weight scales the contribution of this
+    // surface and of the following bounces
+    fn hit(&mut self, _ray: &Ray, _hit: &Hit, _depth: u32) -> f32 {
+        1.
+    }
+
+    // the path leaves the scene
+    fn miss(&mut self, _ray: &Ray, _depth: u32) {}
+
+    // final color of the sample, before it is accumulated
+    fn finish(&mut self, color: Color) -> Color {
+        color
+    }
+}
+
+// no state, boxing it does not allocate
+impl Payload for () {}
+
+// creates the payload of a sample from the pixel index and the sample number
+pub type PayloadFactory = Arc<dyn Fn(usize, u32) -> Box<dyn Payload> + Send + Sync>;
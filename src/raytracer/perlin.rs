@@ -0,0 +1,100 @@
+use glam::Vec3;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+pub struct Perlin {
+    vectors: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    const POINT_COUNT: usize = 256;
+
+    // the same seed gives the same noise in each run
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let vectors = (0..Self::POINT_COUNT)
+            .map(|_| {
+                Vec3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+                .normalize()
+            })
+            .collect();
+
+        Self {
+            vectors,
+            perm_x: Self::permutation(&mut rng),
+            perm_y: Self::permutation(&mut rng),
+            perm_z: Self::permutation(&mut rng),
+        }
+    }
+
+    fn permutation<R: Rng>(rng: &mut R) -> Vec<usize> {
+        let mut perm = (0..Self::POINT_COUNT).collect::<Vec<usize>>();
+        perm.shuffle(rng);
+
+        perm
+    }
+
+    fn vector(&self, i: i32, j: i32, k: i32) -> Vec3 {
+        let mask = Self::POINT_COUNT as i32 - 1;
+
+        let idx = self.perm_x[(i & mask) as usize]
+            ^ self.perm_y[(j & mask) as usize]
+            ^ self.perm_z[(k & mask) as usize];
+
+        self.vectors[idx]
+    }
+
+    // -1..1
+    pub fn noise(&self, p: Vec3) -> f32 {
+        let floor = p.floor();
+        let f = p - floor;
+        let (i, j, k) = (floor.x as i32, floor.y as i32, floor.z as i32);
+
+        // hermite smoothing
+        let u = f * f * (3. - 2. * f);
+
+        let mut acc = 0.;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let weight = f - Vec3::new(di as f32, dj as f32, dk as f32);
+                    let (wi, wj, wk) = (di as f32, dj as f32, dk as f32);
+
+                    acc += (wi * u.x + (1. - wi) * (1. - u.x))
+                        * (wj * u.y + (1. - wj) * (1. - u.y))
+                        * (wk * u.z + (1. - wk) * (1. - u.z))
+                        * self.vector(i + di, j + dj, k + dk).dot(weight);
+                }
+            }
+        }
+
+        acc
+    }
+
+    pub fn turbulence(&self, p: Vec3, depth: u32) -> f32 {
+        let mut acc = 0.;
+        let mut p = p;
+        let mut weight = 1.;
+
+        for _ in 0..depth {
+            acc += weight * self.noise(p);
+            weight *= 0.5;
+            p *= 2.;
+        }
+
+        acc.abs()
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
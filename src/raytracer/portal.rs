@@ -0,0 +1,30 @@
+use glam::Vec3;
+
+// opening of an interior scene, e.g. a window, through which the background lights
+// the surfaces, the background is only sampled through the portals
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Portal {
+    pub corner: Vec3,
+    // edges of the parallelogram from the corner
+    pub u: Vec3,
+    pub v: Vec3,
+}
+
+impl Portal {
+    pub fn new(corner: Vec3, u: Vec3, v: Vec3) -> Self {
+        Self { corner, u, v }
+    }
+
+    pub fn area(&self) -> f32 {
+        self.u.cross(self.v).length()
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.u.cross(self.v).normalize()
+    }
+
+    // s and t in [0, 1]
+    pub fn sample(&self, s: f32, t: f32) -> Vec3 {
+        self.corner + self.u * s + self.v * t
+    }
+}
@@ -0,0 +1,673 @@
+use std::{io, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::raytracer::{aov::Aovs, blue_noise::BlueNoise, color::ColorExt, Color, Extent};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Tonemapper {
+    NONE,
+    REINHARD,
+    // Narkowicz fit of the ACES filmic curve
+    ACES,
+}
+
+impl Tonemapper {
+    fn apply(&self, x: f32) -> f32 {
+        match self {
+            Tonemapper::NONE => x,
+            Tonemapper::REINHARD => x / (1. + x),
+            Tonemapper::ACES => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Encoding {
+    LINEAR,
+    GAMMA,
+    SRGB,
+}
+
+impl Encoding {
+    const EXPONENT: f32 = 2.2;
+
+    pub(crate) fn apply(&self, x: f32) -> f32 {
+        match self {
+            Encoding::LINEAR => x,
+            Encoding::GAMMA => x.powf(1. / Self::EXPONENT),
+            Encoding::SRGB => {
+                if x <= 0.0031308 {
+                    12.92 * x
+                } else {
+                    1.055 * x.powf(1. / 2.4) - 0.055
+                }
+            }
+        }
+    }
+}
+
+// noise added before the quantization to 8 bits, breaks the banding of smooth gradients
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Dither {
+    #[default]
+    NONE,
+    // 4x4 bayer matrix
+    ORDERED,
+    BLUENOISE,
+}
+
+impl Dither {
+    const BAYER: [[f32; 4]; 4] = [
+        [0., 8., 2., 10.],
+        [12., 4., 14., 6.],
+        [3., 11., 1., 9.],
+        [15., 7., 13., 5.],
+    ];
+
+    // offsets of up to half a step of 8 bits, framebuffer in display values
+    pub fn apply(&self, extent: Extent, framebuffer: &mut [Color]) {
+        if *self == Dither::NONE {
+            return;
+        }
+
+        let width = extent.width as usize;
+
+        for (idx, color) in framebuffer.iter_mut().enumerate() {
+            let (x, y) = (idx % width, idx / width);
+
+            let threshold = match self {
+                Dither::NONE => 0.5,
+                Dither::ORDERED => (Self::BAYER[y % 4][x % 4] + 0.5) / 16.,
+                Dither::BLUENOISE => BlueNoise::tile().get(x, y),
+            };
+
+            let offset = |v: f32| (v + (threshold - 0.5) / 255.).clamp(0., 1.);
+
+            *color = Color::new(
+                offset(color.r),
+                offset(color.g),
+                offset(color.b),
+                offset(color.a),
+            );
+        }
+    }
+}
+
+// maps the linear radiance to display values
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Tonemap {
+    pub tonemapper: Tonemapper,
+    // exposure compensation in stops, applied before the tonemapper: the image is
+    // brighter by 2^compensation, as with the ev100 of the builder lowered by the
+    // same amount, but without restarting the render
+    #[serde(alias = "exposure")]
+    pub compensation: f32,
+    pub encoding: Encoding,
+    // only for the 8 bits exports
+    #[serde(default)]
+    pub dither: Dither,
+    // temperature in kelvin of the light shown as white, None for no correction
+    #[serde(default)]
+    pub white_balance: Option<f32>,
+}
+
+impl Default for Tonemap {
+    fn default() -> Self {
+        Self {
+            tonemapper: Tonemapper::NONE,
+            compensation: 0.,
+            encoding: Encoding::SRGB,
+            dither: Dither::NONE,
+            white_balance: None,
+        }
+    }
+}
+
+impl Tonemap {
+    pub fn new(tonemapper: Tonemapper) -> Self {
+        Self {
+            tonemapper,
+            ..Default::default()
+        }
+    }
+
+    pub fn compensation(mut self, stops: f32) -> Self {
+        self.compensation = stops;
+
+        self
+    }
+
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+
+        self
+    }
+
+    pub fn dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+
+        self
+    }
+
+    // e.g. 3200 for tungsten lights, the image gets bluer under the temperature
+    // of daylight and warmer above
+    pub fn white_balance(mut self, kelvin: f32) -> Self {
+        self.white_balance = Some(kelvin);
+
+        self
+    }
+
+    // gains of the channels, the luminance of white is kept
+    fn white_gains(&self) -> Color {
+        const DAYLIGHT: f32 = 6504.;
+
+        let Some(kelvin) = self.white_balance else {
+            return Color::WHITE;
+        };
+
+        let (white, light) = (
+            Color::from_temperature(DAYLIGHT),
+            Color::from_temperature(kelvin),
+        );
+        let gains = Color::new(white.r / light.r, white.g / light.g, white.b / light.b, 1.);
+
+        gains * (1. / gains.luminance())
+    }
+
+    pub fn apply(&self, framebuffer: &mut [Color]) {
+        let scale = 2_f32.powf(self.compensation);
+        let gains = self.white_gains();
+
+        let map = |x: f32, gain: f32| {
+            self.encoding.apply(
+                self.tonemapper
+                    .apply(x.max(0.) * gain * scale)
+                    .clamp(0., 1.),
+            )
+        };
+
+        for color in framebuffer.iter_mut() {
+            *color = Color::new(
+                map(color.r, gains.r),
+                map(color.g, gains.g),
+                map(color.b, gains.b),
+                color.a,
+            );
+        }
+    }
+}
+
+// lines drawn where the object or the depth changes between neighbour pixels
+#[derive(Clone, Copy, Debug)]
+pub struct Outline {
+    pub color: Color,
+    // in pixels
+    pub width: u32,
+    // relative depth change, disabled when <= 0
+    pub depth_threshold: f32,
+    pub object_edges: bool,
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        Self {
+            color: Color::BLACK,
+            width: 1,
+            depth_threshold: 0.1,
+            object_edges: true,
+        }
+    }
+}
+
+impl Outline {
+    pub fn new(color: Color, width: u32) -> Self {
+        Self {
+            color,
+            width,
+            ..Default::default()
+        }
+    }
+
+    pub fn depth_threshold(mut self, depth_threshold: f32) -> Self {
+        self.depth_threshold = depth_threshold;
+
+        self
+    }
+
+    pub fn object_edges(mut self, object_edges: bool) -> Self {
+        self.object_edges = object_edges;
+
+        self
+    }
+
+    // composite the outline over the image, blended by the alpha of the outline color
+    pub fn apply(&self, extent: Extent, framebuffer: &mut [Color], aovs: &Aovs) {
+        let (width, height) = (extent.width as i64, extent.height as i64);
+        let radius = self.width as i64;
+
+        let edges: Vec<usize> = (0..framebuffer.len())
+            .filter(|&idx| {
+                let x = idx as i64 % width;
+                let y = idx as i64 / width;
+
+                (-radius..=radius).any(|dy| {
+                    (-radius..=radius).any(|dx| {
+                        let (nx, ny) = (x + dx, y + dy);
+                        if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                            return false;
+                        }
+
+                        self.is_edge(aovs, idx, (nx + ny * width) as usize)
+                    })
+                })
+            })
+            .collect();
+
+        let alpha = self.color.a;
+        for idx in edges {
+            framebuffer[idx] = framebuffer[idx] * (1. - alpha) + self.color * alpha;
+        }
+    }
+
+    fn is_edge(&self, aovs: &Aovs, a: usize, b: usize) -> bool {
+        if self.object_edges && aovs.id[a] != aovs.id[b] {
+            return true;
+        }
+
+        if self.depth_threshold > 0. {
+            let (da, db) = (aovs.depth[a], aovs.depth[b]);
+            if da.is_finite() && db.is_finite() {
+                return (da - db).abs() / da.min(db) > self.depth_threshold;
+            }
+        }
+
+        false
+    }
+}
+
+// effect applied on the float framebuffer, after the accumulation
+pub trait PostEffect: Send + Sync {
+    fn apply(&self, extent: Extent, framebuffer: &mut [Color], aovs: &Aovs);
+}
+
+impl PostEffect for Tonemap {
+    fn apply(&self, _: Extent, framebuffer: &mut [Color], _: &Aovs) {
+        Tonemap::apply(self, framebuffer);
+    }
+}
+
+impl PostEffect for Dither {
+    fn apply(&self, extent: Extent, framebuffer: &mut [Color], _: &Aovs) {
+        Dither::apply(self, extent, framebuffer);
+    }
+}
+
+impl PostEffect for Outline {
+    fn apply(&self, extent: Extent, framebuffer: &mut [Color], aovs: &Aovs) {
+        Outline::apply(self, extent, framebuffer, aovs);
+    }
+}
+
+// darkens the corners
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vignette {
+    // darkening at the corners, between 0 and 1
+    pub strength: f32,
+    // distance from the center where the falloff starts, 1 is the corner
+    pub radius: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            strength: 0.5,
+            radius: 0.5,
+        }
+    }
+}
+
+impl Vignette {
+    pub fn new(strength: f32, radius: f32) -> Self {
+        Self { strength, radius }
+    }
+}
+
+impl PostEffect for Vignette {
+    fn apply(&self, extent: Extent, framebuffer: &mut [Color], _: &Aovs) {
+        let width = extent.width as usize;
+        let (cx, cy) = (extent.width as f32 / 2., extent.height as f32 / 2.);
+        let corner = (cx * cx + cy * cy).sqrt();
+
+        for (idx, color) in framebuffer.iter_mut().enumerate() {
+            let dx = (idx % width) as f32 + 0.5 - cx;
+            let dy = (idx / width) as f32 + 0.5 - cy;
+            let d = (dx * dx + dy * dy).sqrt() / corner;
+
+            let t = ((d - self.radius) / (1. - self.radius).max(1e-4)).clamp(0., 1.);
+            let factor = 1. - self.strength * t * t * (3. - 2. * t);
+
+            *color = Color::new(
+                color.r * factor,
+                color.g * factor,
+                color.b * factor,
+                color.a,
+            );
+        }
+    }
+}
+
+// glow around the bright areas, to be applied before the tone mapping
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bloom {
+    // radiance above which the pixels bleed
+    pub threshold: f32,
+    pub intensity: f32,
+    // in pixels
+    pub radius: u32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 1.,
+            intensity: 0.1,
+            radius: 8,
+        }
+    }
+}
+
+impl Bloom {
+    pub fn new(threshold: f32, intensity: f32, radius: u32) -> Self {
+        Self {
+            threshold,
+            intensity,
+            radius,
+        }
+    }
+
+    // box blur along one axis, stride 1 for the rows and width for the columns
+    fn blur(
+        &self,
+        input: &[Color],
+        len: usize,
+        count: usize,
+        stride: usize,
+        step: usize,
+    ) -> Vec<Color> {
+        let radius = self.radius as i64;
+        let mut output = vec![Color::new(0., 0., 0., 0.); input.len()];
+
+        for line in 0..count {
+            for i in 0..len as i64 {
+                let mut sum = Color::new(0., 0., 0., 0.);
+                for k in (i - radius).max(0)..=(i + radius).min(len as i64 - 1) {
+                    sum = sum + input[line * stride + k as usize * step];
+                }
+                output[line * stride + i as usize * step] = sum / (2 * radius + 1) as f32;
+            }
+        }
+
+        output
+    }
+}
+
+impl PostEffect for Bloom {
+    fn apply(&self, extent: Extent, framebuffer: &mut [Color], _: &Aovs) {
+        let (width, height) = (extent.width as usize, extent.height as usize);
+
+        let bright = framebuffer
+            .iter()
+            .map(|c| {
+                let excess = |v: f32| (v - self.threshold).max(0.);
+                Color::new(excess(c.r), excess(c.g), excess(c.b), 0.)
+            })
+            .collect::<Vec<Color>>();
+
+        let blurred = self.blur(&bright, width, height, width, 1);
+        let blurred = self.blur(&blurred, height, width, 1, width);
+
+        for (color, glow) in framebuffer.iter_mut().zip(blurred) {
+            *color = Color::new(
+                color.r + glow.r * self.intensity,
+                color.g + glow.g * self.intensity,
+                color.b + glow.b * self.intensity,
+                color.a,
+            );
+        }
+    }
+}
+
+// quantizes the display values to the nearest color of a palette, for stylized renders,
+// to be applied after the tone mapping
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "PaletteDesc")]
+pub struct Palette {
+    // display values
+    pub colors: Vec<[f32; 3]>,
+    pub dither: Dither,
+    // amplitude of the dither offsets, in display values
+    pub spread: f32,
+}
+
+// the palettes of the config files are checked as the ones built in code
+#[derive(Deserialize)]
+struct PaletteDesc {
+    colors: Vec<[f32; 3]>,
+    #[serde(default)]
+    dither: Dither,
+    #[serde(default = "Palette::default_spread")]
+    spread: f32,
+}
+
+impl TryFrom<PaletteDesc> for Palette {
+    type Error = io::Error;
+
+    fn try_from(desc: PaletteDesc) -> io::Result<Self> {
+        Ok(Self::with_colors(desc.colors)?
+            .dither(desc.dither)
+            .spread(desc.spread))
+    }
+}
+
+impl Palette {
+    fn default_spread() -> f32 {
+        0.125
+    }
+
+    pub fn new(colors: Vec<Color>) -> io::Result<Self> {
+        Self::with_colors(colors.iter().map(|c| [c.r, c.g, c.b]).collect())
+    }
+
+    fn with_colors(colors: Vec<[f32; 3]>) -> io::Result<Self> {
+        if colors.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "palette without colors",
+            ));
+        }
+
+        Ok(Self {
+            colors,
+            dither: Dither::NONE,
+            spread: Self::default_spread(),
+        })
+    }
+
+    pub fn dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+
+        self
+    }
+
+    pub fn spread(mut self, spread: f32) -> Self {
+        self.spread = spread;
+
+        self
+    }
+
+    fn nearest(&self, r: f32, g: f32, b: f32) -> [f32; 3] {
+        let distance = |c: &[f32; 3]| {
+            let (dr, dg, db) = (c[0] - r, c[1] - g, c[2] - b);
+            dr * dr + dg * dg + db * db
+        };
+
+        self.colors
+            .iter()
+            .copied()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .unwrap_or([r, g, b])
+    }
+
+    pub fn apply(&self, extent: Extent, framebuffer: &mut [Color]) {
+        let width = extent.width as usize;
+
+        for (idx, color) in framebuffer.iter_mut().enumerate() {
+            let (x, y) = (idx % width, idx / width);
+
+            let threshold = match self.dither {
+                Dither::NONE => 0.5,
+                Dither::ORDERED => (Dither::BAYER[y % 4][x % 4] + 0.5) / 16.,
+                Dither::BLUENOISE => BlueNoise::tile().get(x, y),
+            };
+            let offset = (threshold - 0.5) * self.spread;
+
+            let [r, g, b] = self.nearest(color.r + offset, color.g + offset, color.b + offset);
+            *color = Color::new(r, g, b, color.a);
+        }
+    }
+}
+
+impl PostEffect for Palette {
+    fn apply(&self, extent: Extent, framebuffer: &mut [Color], _: &Aovs) {
+        Palette::apply(self, extent, framebuffer);
+    }
+}
+
+// step of the post process chain, the built-in stages use the settings of the tracer
+#[derive(Clone)]
+pub enum PostStage {
+    TONEMAP,
+    OUTLINE,
+    DITHER,
+    PALETTE,
+    EFFECT(Arc<dyn PostEffect>),
+}
+
+impl PostStage {
+    pub fn effect(effect: impl PostEffect + 'static) -> Self {
+        PostStage::EFFECT(Arc::new(effect))
+    }
+
+    pub fn default_chain() -> Vec<PostStage> {
+        vec![
+            PostStage::TONEMAP,
+            PostStage::OUTLINE,
+            PostStage::DITHER,
+            PostStage::PALETTE,
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DenoiseMode {
+    // once the render is complete
+    FINAL,
+    // once per pass, the preview shows it until the next pass
+    PROGRESSIVE,
+}
+
+// edge-avoiding À-trous wavelet filter, guided by the normal and depth AOVs
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Atrous {
+    pub iterations: u32,
+    pub sigma_color: f32,
+    pub sigma_normal: f32,
+    pub sigma_depth: f32,
+}
+
+impl Default for Atrous {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            sigma_color: 0.5,
+            sigma_normal: 64.,
+            sigma_depth: 0.1,
+        }
+    }
+}
+
+impl Atrous {
+    const KERNEL: [f32; 5] = [1. / 16., 1. / 4., 3. / 8., 1. / 4., 1. / 16.];
+
+    pub fn apply(&self, extent: Extent, framebuffer: &[Color], aovs: &Aovs) -> Vec<Color> {
+        let (width, height) = (extent.width as i64, extent.height as i64);
+
+        let mut input = framebuffer.to_vec();
+        let mut output = framebuffer.to_vec();
+
+        for iteration in 0..self.iterations {
+            // holes between the taps double at each iteration
+            let step = 1_i64 << iteration;
+            // coarser levels carry less noise
+            let sigma_color = self.sigma_color * 2_f32.powi(-(iteration as i32));
+
+            for y in 0..height {
+                for x in 0..width {
+                    let idx = (x + y * width) as usize;
+
+                    let color = input[idx];
+                    let normal = aovs.normal[idx];
+                    let depth = aovs.depth[idx];
+
+                    let mut sum = Color::new(0., 0., 0., 0.);
+                    let mut weights = 0.;
+
+                    for (j, kj) in Self::KERNEL.iter().enumerate() {
+                        for (i, ki) in Self::KERNEL.iter().enumerate() {
+                            let nx = (x + (i as i64 - 2) * step).clamp(0, width - 1);
+                            let ny = (y + (j as i64 - 2) * step).clamp(0, height - 1);
+                            let n = (nx + ny * width) as usize;
+
+                            let sample = input[n];
+
+                            let dr = sample.r - color.r;
+                            let dg = sample.g - color.g;
+                            let db = sample.b - color.b;
+                            let w_color = (-(dr * dr + dg * dg + db * db)
+                                / (sigma_color * sigma_color))
+                                .exp();
+
+                            let w_normal =
+                                normal.dot(aovs.normal[n]).max(0.).powf(self.sigma_normal);
+
+                            let w_depth = if depth.is_finite() && aovs.depth[n].is_finite() {
+                                (-(depth - aovs.depth[n]).abs()
+                                    / (self.sigma_depth * step as f32 * depth))
+                                    .exp()
+                            } else if depth.is_finite() == aovs.depth[n].is_finite() {
+                                1.
+                            } else {
+                                0.
+                            };
+
+                            let w = kj * ki * w_color * w_normal * w_depth;
+                            sum = sum + sample * w;
+                            weights += w;
+                        }
+                    }
+
+                    output[idx] = if weights > 0. {
+                        Color::new(sum.r / weights, sum.g / weights, sum.b / weights, color.a)
+                    } else {
+                        color
+                    };
+                }
+            }
+
+            std::mem::swap(&mut input, &mut output);
+        }
+
+        input
+    }
+}
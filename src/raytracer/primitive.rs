@@ -0,0 +1,145 @@
+use glam::Vec3;
+
+use crate::raytracer::{
+    aabb::Aabb,
+    group::Group,
+    hit::SurfaceSample,
+    mesh::{LodMesh, Mesh},
+    svo::Svo,
+    volume::VoxelVolume,
+    Hit, Hitable, Material, Ray, Sphere,
+};
+
+// built-in models are dispatched with a match in the intersection loops,
+// other types go through the Hitable trait
+pub enum Primitive {
+    SPHERE(Sphere),
+    MESH(Mesh),
+    LOD(LodMesh),
+    SVO(Svo),
+    VOLUME(VoxelVolume),
+    GROUP(Group),
+    CUSTOM(Box<dyn Hitable + Sync + Send>),
+}
+
+impl Primitive {
+    fn hitable(&self) -> &dyn Hitable {
+        match self {
+            Primitive::SPHERE(sphere) => sphere,
+            Primitive::MESH(mesh) => mesh,
+            Primitive::LOD(lod) => lod,
+            Primitive::SVO(svo) => svo,
+            Primitive::VOLUME(volume) => volume,
+            Primitive::GROUP(group) => group,
+            Primitive::CUSTOM(custom) => custom.as_ref(),
+        }
+    }
+
+    // material of the models that have a single one
+    pub(crate) fn material(&self) -> Option<&Material> {
+        match self {
+            Primitive::SPHERE(sphere) => Some(sphere.material()),
+            Primitive::MESH(mesh) => Some(mesh.material()),
+            Primitive::LOD(lod) => Some(lod.material()),
+            Primitive::SVO(_)
+            | Primitive::VOLUME(_)
+            | Primitive::GROUP(_)
+            | Primitive::CUSTOM(_) => None,
+        }
+    }
+
+    // bytes of the name, the bounds and the material, for Tracer::scene_hash
+    pub(crate) fn fingerprint(&self) -> Vec<u8> {
+        let bounds = self.bounds();
+        let mut bytes = self.name().as_bytes().to_vec();
+        bytes.extend(
+            [bounds.min, bounds.max]
+                .iter()
+                .flat_map(|v| v.to_array())
+                .flat_map(|f| f.to_le_bytes()),
+        );
+
+        if let Some(material) = self.material() {
+            bytes.extend(material.fingerprint(&bounds));
+        }
+        if let Primitive::GROUP(group) = self {
+            for child in group.children() {
+                bytes.extend(child.fingerprint());
+            }
+        }
+
+        bytes
+    }
+}
+
+impl From<Box<dyn Hitable + Sync + Send>> for Primitive {
+    fn from(model: Box<dyn Hitable + Sync + Send>) -> Self {
+        Primitive::CUSTOM(model)
+    }
+}
+
+impl Hitable for Primitive {
+    fn name(&self) -> &str {
+        self.hitable().name()
+    }
+
+    fn hit(&self, ray: &Ray, min: f32, max: f32) -> Option<Hit> {
+        match self {
+            Primitive::SPHERE(sphere) => sphere.hit(ray, min, max),
+            Primitive::MESH(mesh) => mesh.hit(ray, min, max),
+            Primitive::LOD(lod) => lod.hit(ray, min, max),
+            Primitive::SVO(svo) => svo.hit(ray, min, max),
+            Primitive::VOLUME(volume) => volume.hit(ray, min, max),
+            Primitive::GROUP(group) => group.hit(ray, min, max),
+            Primitive::CUSTOM(custom) => custom.hit(ray, min, max),
+        }
+    }
+
+    fn hit_distance(&self, ray: &Ray, min: f32, max: f32) -> Option<f32> {
+        match self {
+            Primitive::SPHERE(sphere) => sphere.hit_distance(ray, min, max),
+            Primitive::MESH(mesh) => mesh.hit_distance(ray, min, max),
+            Primitive::LOD(lod) => lod.hit_distance(ray, min, max),
+            Primitive::SVO(svo) => svo.hit_distance(ray, min, max),
+            Primitive::VOLUME(volume) => volume.hit_distance(ray, min, max),
+            Primitive::GROUP(group) => group.hit_distance(ray, min, max),
+            Primitive::CUSTOM(custom) => custom.hit_distance(ray, min, max),
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.hitable().bounds()
+    }
+
+    fn sphere(&self) -> Option<(Vec3, f32)> {
+        self.hitable().sphere()
+    }
+
+    fn shadow_softness(&self) -> f32 {
+        self.hitable().shadow_softness()
+    }
+
+    fn sample_emitter(&self, u: f32, v: f32) -> Option<SurfaceSample> {
+        self.hitable().sample_emitter(u, v)
+    }
+
+    fn transmittance(&self, ray: &Ray, min: f32, max: f32) -> f32 {
+        self.hitable().transmittance(ray, min, max)
+    }
+
+    fn is_medium(&self) -> bool {
+        self.hitable().is_medium()
+    }
+
+    fn rescale(&mut self, center: Vec3, scale: f32) -> bool {
+        match self {
+            Primitive::SPHERE(sphere) => sphere.rescale(center, scale),
+            Primitive::MESH(mesh) => mesh.rescale(center, scale),
+            Primitive::LOD(lod) => lod.rescale(center, scale),
+            Primitive::SVO(svo) => svo.rescale(center, scale),
+            Primitive::VOLUME(volume) => volume.rescale(center, scale),
+            Primitive::GROUP(group) => group.rescale(center, scale),
+            Primitive::CUSTOM(custom) => custom.rescale(center, scale),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    f32::consts::PI,
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::raytracer::Color;
+
+// order 2 spherical harmonics, 9 rgb coefficients
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Probe {
+    pub position: [f32; 3],
+    pub sh: [[f32; 3]; 9],
+}
+
+impl Probe {
+    pub const COEFFICIENTS: usize = 9;
+
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position: position.to_array(),
+            sh: [[0.; 3]; Self::COEFFICIENTS],
+        }
+    }
+
+    // real sh basis evaluated in direction (normalized)
+    pub fn basis(direction: Vec3) -> [f32; 9] {
+        let (x, y, z) = (direction.x, direction.y, direction.z);
+
+        [
+            0.282095,
+            0.488603 * y,
+            0.488603 * z,
+            0.488603 * x,
+            1.092548 * x * y,
+            1.092548 * y * z,
+            0.315392 * (3. * z * z - 1.),
+            1.092548 * x * z,
+            0.546274 * (x * x - y * y),
+        ]
+    }
+
+    // accumulates one radiance sample, weight is the solid angle covered by the sample
+    pub fn add(&mut self, direction: Vec3, radiance: Color, weight: f32) {
+        for (coefficient, y) in self.sh.iter_mut().zip(Self::basis(direction)) {
+            coefficient[0] += radiance.r * y * weight;
+            coefficient[1] += radiance.g * y * weight;
+            coefficient[2] += radiance.b * y * weight;
+        }
+    }
+
+    pub fn radiance(&self, direction: Vec3) -> Color {
+        self.eval(direction, [1.; 3])
+    }
+
+    // cosine convolved radiance, i.e. the irradiance for a surface facing normal
+    pub fn irradiance(&self, normal: Vec3) -> Color {
+        // clamped cosine lobe per band
+        let band = [PI, 2. * PI / 3., PI / 4.];
+
+        self.eval(normal, band)
+    }
+
+    fn eval(&self, direction: Vec3, band: [f32; 3]) -> Color {
+        let mut rgb = [0.; 3];
+
+        for (i, (coefficient, y)) in self.sh.iter().zip(Self::basis(direction)).enumerate() {
+            let scale = match i {
+                0 => band[0],
+                1..=3 => band[1],
+                _ => band[2],
+            };
+
+            for c in 0..3 {
+                rgb[c] += coefficient[c] * y * scale;
+            }
+        }
+
+        Color::new(rgb[0].max(0.), rgb[1].max(0.), rgb[2].max(0.), 1.)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProbeSet {
+    pub probes: Vec<Probe>,
+}
+
+impl ProbeSet {
+    // little endian: probe count (u32), then position (3 f32) and sh (27 f32) of each probe
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(&(self.probes.len() as u32).to_le_bytes())?;
+        for probe in &self.probes {
+            for v in probe.position.iter().chain(probe.sh.iter().flatten()) {
+                writer.write_all(&v.to_le_bytes())?;
+            }
+        }
+
+        log::info!("Probes saved: {}", path);
+
+        Ok(())
+    }
+
+    pub fn save_json(&self, path: &str) -> std::io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+
+        log::info!("Probes saved: {}", path);
+
+        Ok(())
+    }
+}
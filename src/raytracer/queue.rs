@@ -0,0 +1,190 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::raytracer::{
+    buffer::{ImageBuffer, PixelSamples},
+    scene::{ChunkScratch, Scene},
+};
+
+// chunk waiting for a worker
+pub(crate) struct Job {
+    pub version: u64,
+    // 0 for the main camera, then the views
+    pub camera: usize,
+    // buffer of the camera, written by the worker
+    pub image_buffer: Arc<Mutex<ImageBuffer>>,
+    pub scene: Arc<Scene>,
+    pub chunk: Vec<usize>,
+    pub first_sample: u32,
+    pub rng_seed: u64,
+    // see Scene::preview_chunk
+    pub preview: bool,
+}
+
+impl Job {
+    pub fn run(&self, scratch: &mut ChunkScratch, result: &mut Vec<PixelSamples>) {
+        if self.preview {
+            self.scene.preview_chunk(&self.chunk, result);
+        } else {
+            self.scene.compute_chunk(
+                &self.chunk,
+                self.first_sample,
+                self.rng_seed,
+                scratch,
+                result,
+            );
+        }
+    }
+}
+
+// chunks shared by the workers, each one pulls the next chunk as soon as it is
+// done with the previous one so that cheap and expensive chunks even out
+#[derive(Default)]
+pub(crate) struct ChunkQueue {
+    jobs: Mutex<VecDeque<Job>>,
+    workers: AtomicUsize,
+    max_workers: AtomicUsize,
+    // chunks of an older version are dropped by the workers, see also
+    // ImageBuffer::is_current for the pixels reset on their own
+    version: AtomicU64,
+    progress: ChunkProgress,
+}
+
+// counters of the chunks completed by the workers since the last ChunkQueue::progress
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ChunkCounts {
+    // including the stale chunks
+    pub done: usize,
+    pub added: u32,
+    pub pixels: u64,
+    pub clamped: u32,
+    pub invalid: u32,
+    // seconds, summed over the chunks
+    pub time: f32,
+}
+
+#[derive(Default)]
+struct ChunkProgress {
+    done: AtomicUsize,
+    added: AtomicU32,
+    pixels: AtomicU64,
+    clamped: AtomicU32,
+    invalid: AtomicU32,
+    // microseconds
+    time: AtomicU64,
+}
+
+impl ChunkQueue {
+    pub fn push(&self, job: Job) {
+        self.jobs.lock().unwrap().push_back(job);
+    }
+
+    // returns the number of jobs dropped
+    pub fn clear(&self) -> usize {
+        self.jobs.lock().unwrap().drain(..).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.lock().unwrap().is_empty()
+    }
+
+    pub fn set_max_workers(&self, max_workers: usize) {
+        self.max_workers.store(max_workers, Ordering::Release);
+    }
+
+    // true if a new worker should be started
+    pub fn add_worker(&self) -> bool {
+        if self.is_empty()
+            || self.workers.load(Ordering::Acquire) >= self.max_workers.load(Ordering::Acquire)
+        {
+            return false;
+        }
+
+        self.workers.fetch_add(1, Ordering::AcqRel);
+
+        true
+    }
+
+    // None when the worker must stop: the queue is empty or there are too many workers
+    pub fn pop(&self) -> Option<Job> {
+        let mut jobs = self.jobs.lock().unwrap();
+
+        // the count changes under the lock, pushes made after this see the worker gone
+        if jobs.is_empty()
+            || self.workers.load(Ordering::Acquire) > self.max_workers.load(Ordering::Acquire)
+        {
+            self.workers.fetch_sub(1, Ordering::AcqRel);
+            return None;
+        }
+
+        jobs.pop_front()
+    }
+
+    // next job for a worker outside of the pool, without the worker count
+    pub fn take(&self) -> Option<Job> {
+        self.jobs.lock().unwrap().pop_front()
+    }
+
+    pub fn set_version(&self, version: u64) {
+        self.version.store(version, Ordering::Release);
+    }
+
+    // adds the samples of a chunk to the buffer of its camera, the chunks of a pass don't
+    // overlap and the lock only orders the writes with the resets: a reset comes after
+    // the version change, so the chunk is either dropped or wiped by the reset
+    pub fn complete(&self, job: &Job, result: &[PixelSamples], elapsed: f32) {
+        let progress = &self.progress;
+
+        let mut image_buffer = job.image_buffer.lock().unwrap();
+        if job.version >= self.version.load(Ordering::Acquire) {
+            let (mut clamped, mut invalid) = (0, 0);
+            for samples in result {
+                if !image_buffer.is_current(samples.idx, job.version) {
+                    continue;
+                }
+                clamped += samples.clamped;
+                invalid += samples.invalid;
+                image_buffer.add_samples(samples);
+            }
+            image_buffer.add_time(&job.chunk, elapsed);
+            drop(image_buffer);
+
+            progress.added.fetch_add(1, Ordering::Relaxed);
+            let pixels = result.iter().filter(|samples| !samples.splat).count();
+            progress.pixels.fetch_add(pixels as u64, Ordering::Relaxed);
+            progress.clamped.fetch_add(clamped, Ordering::Relaxed);
+            progress.invalid.fetch_add(invalid, Ordering::Relaxed);
+        } else {
+            drop(image_buffer);
+            log::debug!("Drop stale chunk (version {})", job.version);
+        }
+
+        progress
+            .time
+            .fetch_add((elapsed * 1e6) as u64, Ordering::Relaxed);
+        // last, the tracer counts the chunk in flight until its samples are added
+        progress.done.fetch_add(1, Ordering::Release);
+    }
+
+    // counts since the last call
+    pub fn progress(&self) -> ChunkCounts {
+        let progress = &self.progress;
+
+        // done first, the other counters may include chunks counted by the next call
+        let done = progress.done.swap(0, Ordering::Acquire);
+
+        ChunkCounts {
+            done,
+            added: progress.added.swap(0, Ordering::Relaxed),
+            pixels: progress.pixels.swap(0, Ordering::Relaxed),
+            clamped: progress.clamped.swap(0, Ordering::Relaxed),
+            invalid: progress.invalid.swap(0, Ordering::Relaxed),
+            time: progress.time.swap(0, Ordering::Relaxed) as f32 * 1e-6,
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use glam::Vec3;
 
-#[derive(Debug)]
+use crate::raytracer::sampling;
+
+#[derive(Clone, Copy, Debug)]
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
@@ -15,9 +17,6 @@ impl Ray {
     }
 
     pub fn reflect(&self, position: Vec3, normal: Vec3) -> Self {
-        Self::new(
-            position,
-            self.direction - 2. * normal.dot(self.direction) * normal,
-        )
+        Self::new(position, sampling::reflect(self.direction, normal))
     }
 }
@@ -0,0 +1,32 @@
+use crate::raytracer::Extent;
+
+// part of the image rendered up to its own number of samples, in pixels
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub name: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub target_samples: u32,
+}
+
+impl Region {
+    pub fn new(name: &str, x: u32, y: u32, width: u32, height: u32, target_samples: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            x,
+            y,
+            width,
+            height,
+            target_samples,
+        }
+    }
+
+    pub fn contains(&self, extent: Extent, idx: usize) -> bool {
+        let x = (idx % extent.width as usize) as u32;
+        let y = (idx / extent.width as usize) as u32;
+
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
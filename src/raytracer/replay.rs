@@ -0,0 +1,124 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+};
+
+use rand::{rngs::StdRng, Rng};
+use serde::{Deserialize, Serialize};
+
+// seeds drawn during a render, replaying them gives the same chunk order and
+// the same random samples
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RngRecording {
+    // chunk order of each reset and new pass
+    pub passes: Vec<u64>,
+    // first pixel and sampler seed of each chunk, in dispatch order
+    pub chunks: Vec<(usize, u64)>,
+}
+
+impl RngRecording {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+
+        log::info!(
+            "RNG recording saved: {} ({} passes, {} chunks)",
+            path,
+            self.passes.len(),
+            self.chunks.len()
+        );
+
+        Ok(())
+    }
+}
+
+// source of the seeds of the tracer
+#[derive(Clone, Debug, Default)]
+pub(crate) enum RngStream {
+    #[default]
+    LIVE,
+    // same seeds in each run, see TracerBuilder::deterministic
+    SEEDED(Box<StdRng>),
+    RECORD(RngRecording),
+    REPLAY {
+        recording: RngRecording,
+        pass: usize,
+        chunk: usize,
+    },
+}
+
+impl RngStream {
+    pub fn pass_seed(&mut self) -> u64 {
+        match self {
+            RngStream::LIVE => rand::thread_rng().gen(),
+            RngStream::SEEDED(rng) => rng.gen(),
+            RngStream::RECORD(recording) => {
+                let seed = rand::thread_rng().gen();
+                recording.passes.push(seed);
+
+                seed
+            }
+            RngStream::REPLAY {
+                recording, pass, ..
+            } => match recording.passes.get(*pass) {
+                Some(&seed) => {
+                    *pass += 1;
+
+                    seed
+                }
+                None => {
+                    log::warn!("Replay: no more passes recorded");
+                    rand::thread_rng().gen()
+                }
+            },
+        }
+    }
+
+    // first_pixel is used to detect when the replay diverges from the recording
+    pub fn chunk_seed(&mut self, first_pixel: usize) -> u64 {
+        match self {
+            RngStream::LIVE => rand::thread_rng().gen(),
+            RngStream::SEEDED(rng) => rng.gen(),
+            RngStream::RECORD(recording) => {
+                let seed = rand::thread_rng().gen();
+                recording.chunks.push((first_pixel, seed));
+
+                seed
+            }
+            RngStream::REPLAY {
+                recording, chunk, ..
+            } => match recording.chunks.get(*chunk) {
+                Some(&(pixel, seed)) => {
+                    if pixel != first_pixel {
+                        log::warn!(
+                            "Replay: chunk {} starts at pixel {}, recorded {}",
+                            chunk,
+                            first_pixel,
+                            pixel
+                        );
+                    }
+                    *chunk += 1;
+
+                    seed
+                }
+                None => {
+                    log::warn!("Replay: no more chunks recorded");
+                    rand::thread_rng().gen()
+                }
+            },
+        }
+    }
+
+    pub fn recording(&self) -> Option<&RngRecording> {
+        match self {
+            RngStream::LIVE | RngStream::SEEDED(_) => None,
+            RngStream::RECORD(recording) | RngStream::REPLAY { recording, .. } => Some(recording),
+        }
+    }
+}
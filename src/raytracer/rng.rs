@@ -0,0 +1,37 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// precomputed random values, cheaper than a generator call per sample
+#[derive(Default)]
+pub struct RngPool {
+    values: Vec<f32>,
+    idx: usize,
+}
+
+impl RngPool {
+    pub fn new(size: usize) -> Self {
+        let mut rng = rand::thread_rng();
+
+        Self {
+            values: (0..size.max(1)).map(|_| rng.gen::<f32>()).collect(),
+            idx: 0,
+        }
+    }
+
+    // same values for the same seed, to replay a render, the pool keeps its
+    // allocation
+    pub fn reseed(&mut self, size: usize, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        self.values.clear();
+        self.values
+            .extend((0..size.max(1)).map(|_| rng.gen::<f32>()));
+        self.idx = 0;
+    }
+
+    pub fn next(&mut self) -> f32 {
+        let value = self.values[self.idx];
+        self.idx = (self.idx + 1) % self.values.len();
+
+        value
+    }
+}
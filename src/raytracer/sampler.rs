@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+
+use crate::raytracer::{blue_noise::BlueNoise, rng::RngPool};
+
+// HALTON and SOBOL sample the whole path of a pixel: the dimensions after the
+// jitter drive the lights and the bounces, the other kinds leave them to the light
+// sampler
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SamplerKind {
+    RANDOM,
+    // the first 16 dimensions, owen scrambled sobol pairs beyond
+    HALTON,
+    // the (0, 2) sequence for the jitter, then owen scrambled and shuffled copies of
+    // it for each pair of dimensions
+    SOBOL,
+    BLUENOISE,
+}
+
+impl SamplerKind {
+    // the sequence has enough dimensions for the whole path
+    pub fn is_sequence(&self) -> bool {
+        matches!(self, SamplerKind::HALTON | SamplerKind::SOBOL)
+    }
+}
+
+// sample points for one pixel, one dimension is consumed by each call to next()
+pub struct Sampler {
+    kind: SamplerKind,
+    rng: RngPool,
+    width: u32,
+    seed: u32,
+    pixel: u32,
+    index: u32,
+    dimension: u32,
+}
+
+impl Sampler {
+    const PRIMES: [u32; 16] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53];
+    const GOLDEN_RATIO: f32 = 0.618034;
+
+    // width of the image, pixels are given as indexes
+    pub fn new(kind: SamplerKind, size: usize, width: u32, seed: u32) -> Self {
+        Self {
+            kind,
+            rng: RngPool::new(size),
+            width,
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    // values of the RANDOM sampler given by the pool instead of the thread
+    // generator, see into_rng to reuse it
+    pub fn with_rng(kind: SamplerKind, rng: RngPool, width: u32, seed: u32) -> Self {
+        Self {
+            kind,
+            rng,
+            width,
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    pub fn into_rng(self) -> RngPool {
+        self.rng
+    }
+
+    // index is the position of the sample in the sequence of the pixel
+    pub fn start(&mut self, pixel: usize, index: u32) {
+        self.start_at(pixel, index, 0);
+    }
+
+    // continues the sample after the dimensions already consumed, e.g. by the jitter
+    pub fn start_at(&mut self, pixel: usize, index: u32, dimension: u32) {
+        self.pixel = pixel as u32;
+        self.index = index;
+        self.dimension = dimension;
+    }
+
+    pub fn next(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        let value = match self.kind {
+            SamplerKind::RANDOM => return self.rng.next(),
+            SamplerKind::HALTON if dimension < Self::PRIMES.len() as u32 => {
+                Self::halton(self.index, dimension)
+            }
+            SamplerKind::SOBOL if dimension < 2 => Self::sobol(self.index, dimension),
+            SamplerKind::HALTON | SamplerKind::SOBOL => return self.padded_sobol(dimension),
+            SamplerKind::BLUENOISE => return self.blue_noise(dimension),
+        };
+
+        // Cranley-Patterson rotation decorrelates the pixels
+        (value + self.offset(self.pixel, dimension)).fract()
+    }
+
+    // each dimension reads the tile at a different offset, successive samples follow
+    // the golden ratio sequence so that the pattern stays blue over time
+    fn blue_noise(&self, dimension: u32) -> f32 {
+        let x = self.pixel % self.width;
+        let y = self.pixel / self.width;
+
+        let dx = (self.offset(dimension, 0) * BlueNoise::SIZE as f32) as u32;
+        let dy = (self.offset(dimension, 1) * BlueNoise::SIZE as f32) as u32;
+
+        let value = BlueNoise::tile().get((x + dx) as usize, (y + dy) as usize);
+
+        (value + self.index as f32 * Self::GOLDEN_RATIO).fract()
+    }
+
+    fn halton(index: u32, dimension: u32) -> f32 {
+        Self::radical_inverse(index, Self::PRIMES[dimension as usize])
+    }
+
+    // dimension is 0 or 1 of the (0, 2) Sobol sequence
+    fn sobol(index: u32, dimension: u32) -> f32 {
+        Self::to_unit(Self::sobol_bits(index, dimension))
+    }
+
+    fn sobol_bits(index: u32, dimension: u32) -> u32 {
+        if dimension == 0 {
+            return index.reverse_bits();
+        }
+
+        let mut result = 0u32;
+        let mut v = 1u32 << 31;
+        let mut i = index;
+
+        while i != 0 {
+            if i & 1 != 0 {
+                result ^= v;
+            }
+            v ^= v >> 1;
+            i >>= 1;
+        }
+
+        result
+    }
+
+    // each pair of dimensions is a (0, 2) sequence with its own shuffle of the
+    // indexes and owen scrambling of the values, by pixel, see Burley, Practical
+    // Hash-based Owen Scrambling
+    fn padded_sobol(&self, dimension: u32) -> f32 {
+        let pair = dimension / 2;
+        let index = Self::owen_scramble(self.index, self.hash(self.pixel, pair));
+        let bits = Self::sobol_bits(index, dimension % 2);
+
+        Self::to_unit(Self::owen_scramble(
+            bits,
+            self.hash(self.pixel ^ !dimension, pair),
+        ))
+    }
+
+    // nested uniform scrambling of the bits from the most significant one
+    fn owen_scramble(x: u32, seed: u32) -> u32 {
+        // Laine-Karras permutation of the reversed bits
+        let mut x = x.reverse_bits().wrapping_add(seed);
+        x ^= x.wrapping_mul(0x6c50b47c);
+        x ^= x.wrapping_mul(0xb82f1e52);
+        x ^= x.wrapping_mul(0xc7afe638);
+        x ^= x.wrapping_mul(0x8d22f6e6);
+
+        x.reverse_bits()
+    }
+
+    fn to_unit(bits: u32) -> f32 {
+        (bits as f32 / 4294967296.).min(1. - f32::EPSILON)
+    }
+
+    fn radical_inverse(index: u32, base: u32) -> f32 {
+        let inv_base = 1. / base as f32;
+
+        let mut result = 0.;
+        let mut scale = inv_base;
+        let mut i = index;
+
+        while i != 0 {
+            result += (i % base) as f32 * scale;
+            scale *= inv_base;
+            i /= base;
+        }
+
+        result
+    }
+
+    // stable for a given seed
+    fn offset(&self, a: u32, b: u32) -> f32 {
+        (self.hash(a, b) >> 8) as f32 / 16777216.
+    }
+
+    fn hash(&self, a: u32, b: u32) -> u32 {
+        let mut h = (a ^ self.seed)
+            .wrapping_mul(0x9e3779b9)
+            .wrapping_add(b.wrapping_mul(0x85ebca6b));
+        h ^= h >> 16;
+        h = h.wrapping_mul(0x7feb352d);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x846ca68b);
+        h ^= h >> 16;
+
+        h
+    }
+}
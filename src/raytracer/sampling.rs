@@ -0,0 +1,122 @@
+use std::f32::consts::PI;
+
+use glam::{Vec2, Vec3};
+
+// orthonormal basis around a normal, branchless construction from Duff et al. 2017
+#[derive(Clone, Copy, Debug)]
+pub struct Onb {
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    pub normal: Vec3,
+}
+
+impl Onb {
+    pub fn new(normal: Vec3) -> Self {
+        let sign = 1_f32.copysign(normal.z);
+        let a = -1. / (sign + normal.z);
+        let b = normal.x * normal.y * a;
+
+        Self {
+            tangent: Vec3::new(
+                1. + sign * normal.x * normal.x * a,
+                sign * b,
+                -sign * normal.x,
+            ),
+            bitangent: Vec3::new(b, sign + normal.y * normal.y * a, -normal.y),
+            normal,
+        }
+    }
+
+    // local coordinates have z along the normal
+    pub fn to_world(&self, v: Vec3) -> Vec3 {
+        v.x * self.tangent + v.y * self.bitangent + v.z * self.normal
+    }
+
+    // direction with a pdf of cos / PI around the normal, for u, v in [0, 1]
+    pub fn sample_cosine(&self, u: f32, v: f32) -> Vec3 {
+        self.to_world(cosine_hemisphere(u, v))
+    }
+
+    // uniform direction within half_angle of the normal
+    pub fn sample_cone(&self, u: f32, v: f32, half_angle: f32) -> Vec3 {
+        self.to_world(uniform_cone(u, v, half_angle.cos()))
+    }
+
+    pub fn to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            v.dot(self.tangent),
+            v.dot(self.bitangent),
+            v.dot(self.normal),
+        )
+    }
+}
+
+// the samplers map u, v in [0, 1] to the domain, the directions are in local
+// coordinates with z up, see Onb::to_world
+
+// pdf of cos / PI
+pub fn cosine_hemisphere(u: f32, v: f32) -> Vec3 {
+    let r = u.sqrt();
+    let phi = 2. * PI * v;
+
+    Vec3::new(r * phi.cos(), r * phi.sin(), (1. - u).max(0.).sqrt())
+}
+
+// pdf of 1 / (4 PI)
+pub fn uniform_sphere(u: f32, v: f32) -> Vec3 {
+    let z = 1. - 2. * u;
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * PI * v;
+
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// point on the unit disk, concentric mapping of Shirley and Chiu that keeps the
+// stratification of the samples
+pub fn uniform_disk(u: f32, v: f32) -> Vec2 {
+    let (a, b) = (2. * u - 1., 2. * v - 1.);
+    if a == 0. && b == 0. {
+        return Vec2::ZERO;
+    }
+
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4. * (b / a))
+    } else {
+        (b, PI / 2. - PI / 4. * (a / b))
+    };
+
+    Vec2::new(r * theta.cos(), r * theta.sin())
+}
+
+// pdf of 1 / (2 PI (1 - cos_max)), within the cone of the directions with
+// cos >= cos_max around z
+pub fn uniform_cone(u: f32, v: f32, cos_max: f32) -> Vec3 {
+    let z = 1. - u * (1. - cos_max);
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * PI * v;
+
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn uniform_cone_pdf(cos_max: f32) -> f32 {
+    1. / (2. * PI * (1. - cos_max))
+}
+
+// mirror direction of d around the normal
+pub fn reflect(d: Vec3, normal: Vec3) -> Vec3 {
+    d - 2. * normal.dot(d) * normal
+}
+
+// d and the normal face each other, eta is the ratio of the indices of refraction
+// of the incident and transmitted sides, None on total internal reflection
+pub fn refract(d: Vec3, normal: Vec3, eta: f32) -> Option<Vec3> {
+    let cos_i = (-d).dot(normal).min(1.);
+    let sin2_t = eta * eta * (1. - cos_i * cos_i).max(0.);
+    if sin2_t >= 1. {
+        return None;
+    }
+
+    let cos_t = (1. - sin2_t).sqrt();
+
+    Some(eta * d + (eta * cos_i - cos_t) * normal)
+}
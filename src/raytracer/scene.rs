@@ -0,0 +1,1007 @@
+use std::{collections::HashMap, f32::consts::PI, io, sync::Arc};
+
+use glam::{Vec2, Vec3};
+
+use crate::raytracer::{
+    aabb::Aabb,
+    accel::AccelData,
+    aov::AovSample,
+    buffer::PixelSamples,
+    camera::ProjectionMode,
+    color::ColorExt,
+    decal::Decal,
+    filter::PixelFilter,
+    hit::{Differentials, Hit, Hitable},
+    integrator::{Integrator, Toon},
+    light::{LightSampling, PointLight},
+    material::ResponseLut,
+    payload::{Payload, PayloadFactory},
+    portal::Portal,
+    primitive::Primitive,
+    rng::RngPool,
+    sampler::{Sampler, SamplerKind},
+    sampling::uniform_sphere,
+    scene_file::SceneFile,
+    stats::{self, StatCounters},
+    status::RenderControl,
+    Camera, Color, Extent, Ray, TracerBuilder,
+};
+
+// reads a toml scene description into a configured builder, the file is
+// watched and reloaded by the tracer when it changes
+pub async fn load(path: &str) -> io::Result<TracerBuilder> {
+    Ok(SceneFile::load(path)?.builder().await?.watch(path))
+}
+
+// see Tracer::save_scene for the current state of a tracer
+pub fn save(path: &str, scene: &SceneFile) -> io::Result<()> {
+    scene.save(path)
+}
+
+// buffers of a worker, reused from one chunk to the next
+#[derive(Default)]
+pub(crate) struct ChunkScratch {
+    rng: RngPool,
+    light_rng: RngPool,
+    rays: Vec<Ray>,
+    // position of the samples in their pixel
+    offsets: Vec<Vec2>,
+    // colors and weights spread by the pixel filter
+    splats: HashMap<usize, (Color, f32)>,
+}
+
+// immutable render state, shared with the worker threads
+#[derive(Clone)]
+pub(crate) struct Scene {
+    pub extent: Extent,
+    pub models: Arc<Vec<Arc<Primitive>>>,
+    pub accel: Arc<AccelData>,
+    pub lights: Arc<Vec<PointLight>>,
+    // models that can be sampled as lights, with their area
+    pub emitters: Arc<Vec<(usize, f32)>>,
+    // participating media, they attenuate the shadow rays
+    pub media: Arc<Vec<usize>>,
+    // material responses of the models for the fast preview, empty without it
+    pub responses: Arc<Vec<Option<ResponseLut>>>,
+    pub portals: Arc<Vec<Portal>>,
+    pub decals: Arc<Vec<Decal>>,
+    pub camera: Arc<Camera>,
+    pub background: fn(&Ray) -> Color,
+    pub n_rays: u32,
+    pub n_reflects: u32,
+    pub alpha_cutoff: f32,
+    pub epsilon: f32,
+    // offset of the shadow rays along the normal
+    pub shadow_bias: f32,
+    pub min_throughput: f32,
+    pub exposure: f32,
+    pub ambient: f32,
+    pub integrator: Integrator,
+    pub sampler: SamplerKind,
+    pub light_sampler: SamplerKind,
+    pub light_sampling: LightSampling,
+    pub seed: u32,
+    // some models override the shadow softness
+    pub soft_casters: bool,
+    pub control: Arc<RenderControl>,
+    // maximum luminance of a single sample
+    pub sample_clamp: Option<f32>,
+    pub filter: PixelFilter,
+    pub payload: Option<PayloadFactory>,
+    // traversal counts of the first sample in the aovs, see DebugView
+    pub debug_counts: bool,
+    // rays and hits of the render, see Tracer::stats
+    pub stats: Arc<StatCounters>,
+}
+
+impl Scene {
+    const PACKET_SIZE: usize = 64;
+
+    // first_sample is the number of samples already taken for each pixel,
+    // rng_seed makes the random samples of the chunk reproducible, the samples
+    // are written to result and scratch keeps the buffers between chunks
+    pub fn compute_chunk(
+        &self,
+        chunk: &[usize],
+        first_sample: u32,
+        rng_seed: u64,
+        scratch: &mut ChunkScratch,
+        result: &mut Vec<PixelSamples>,
+    ) {
+        result.clear();
+        scratch.splats.clear();
+        stats::discard();
+
+        let mut rng = std::mem::take(&mut scratch.rng);
+        rng.reseed(chunk.len(), rng_seed);
+        let mut sampler = Sampler::with_rng(self.sampler, rng, self.extent.width, self.seed);
+
+        // shadow rays, light selection and bounces, unless the sampler is a sequence
+        let mut light_rng = std::mem::take(&mut scratch.light_rng);
+        light_rng.reseed(chunk.len(), rng_seed.wrapping_add(1));
+        let mut light_sampler =
+            Sampler::with_rng(self.light_sampler, light_rng, self.extent.width, self.seed);
+
+        let near = self.camera.mode.near();
+        let far = self.camera.mode.far();
+
+        // primary rays of neighbour pixels are traced together
+        for packet in chunk.chunks(Self::PACKET_SIZE) {
+            if self.control.is_cancelled() {
+                break;
+            }
+
+            let start = result.len();
+            result.extend(packet.iter().map(|idx| PixelSamples::new(*idx)));
+            let pixels = &mut result[start..];
+
+            for sample in 0..self.n_rays {
+                let rays = &mut scratch.rays;
+                let offsets = &mut scratch.offsets;
+                rays.clear();
+                offsets.clear();
+                rays.extend(packet.iter().map(|idx| {
+                    sampler.start(*idx, first_sample + sample);
+                    let offset = Vec2::new(sampler.next(), sampler.next());
+                    offsets.push(offset);
+
+                    self.primary_ray(*idx, offset)
+                }));
+
+                // the rays of the first sample are traced one by one to count the
+                // traversal of each pixel
+                let counted = self.debug_counts && sample == 0;
+                let hits = if counted {
+                    Vec::new()
+                } else {
+                    self.accel.hit_packet(&self.models, rays, near, far)
+                };
+
+                for (k, (ray, pixel)) in rays.iter().zip(pixels.iter_mut()).enumerate() {
+                    let idx = pixel.idx;
+
+                    if counted {
+                        stats::start_sample();
+                    }
+                    let hit = match hits.get(k) {
+                        Some(hit) => *hit,
+                        None => self.accel.hit(&self.models, ray, near, far),
+                    };
+
+                    let hit = match hit {
+                        Some(hit) if hit.color.a < self.alpha_cutoff => {
+                            self.closest_hit(ray, self.next_t(&hit), far)
+                        }
+                        hit => hit,
+                    };
+
+                    stats::record_camera_ray(&hit);
+
+                    if sample == 0 {
+                        pixel.aov = AovSample::new(&hit);
+                    }
+
+                    // the sequences continue after the jitter
+                    let path_sampler = if self.sampler.is_sequence() {
+                        sampler.start_at(idx, first_sample + sample, 2);
+                        &mut sampler
+                    } else {
+                        light_sampler.start(idx, first_sample + sample);
+                        &mut light_sampler
+                    };
+
+                    let mut payload = match &self.payload {
+                        Some(factory) => factory(idx, first_sample + sample),
+                        None => Box::new(()),
+                    };
+
+                    let color = match self.integrator {
+                        Integrator::WHITTED => self.trace(ray, hit, path_sampler, payload.as_mut()),
+                        Integrator::PATH => self.path(ray, hit, path_sampler, payload.as_mut()),
+                        Integrator::TOON(toon) => self.toon(idx, ray, hit, &toon, path_sampler),
+                    };
+                    let color = payload.finish(color);
+
+                    if counted {
+                        pixel.aov.counts = stats::take_sample();
+                    }
+
+                    // a single bad sample would stay visible in the accumulation
+                    if !(color.r.is_finite() && color.g.is_finite() && color.b.is_finite()) {
+                        pixel.invalid += 1;
+                        continue;
+                    }
+
+                    let color = match self.sample_clamp {
+                        Some(max) if color.luminance() > max => {
+                            pixel.clamped += 1;
+                            color * (max / color.luminance())
+                        }
+                        _ => color,
+                    };
+
+                    if self.filter == PixelFilter::BOX {
+                        pixel.add(color);
+                    } else {
+                        pixel.add_statistics(color);
+                        self.splat(idx, offsets[k], color, &mut scratch.splats);
+                    }
+                }
+            }
+        }
+
+        // the weights of the pixels of the chunk are added to their samples, the
+        // others are sent as splats
+        if !scratch.splats.is_empty() {
+            let positions = result
+                .iter()
+                .enumerate()
+                .map(|(k, pixel)| (pixel.idx, k))
+                .collect::<HashMap<_, _>>();
+
+            for (&idx, &(color, weight)) in scratch.splats.iter() {
+                let pixel = match positions.get(&idx) {
+                    Some(&k) => &mut result[k],
+                    None => {
+                        let mut pixel = PixelSamples::new(idx);
+                        pixel.splat = true;
+                        result.push(pixel);
+                        result.last_mut().unwrap()
+                    }
+                };
+
+                pixel.color = pixel.color + color;
+                pixel.weight += weight;
+            }
+        }
+
+        scratch.rng = sampler.into_rng();
+        scratch.light_rng = light_sampler.into_rng();
+
+        self.stats.flush();
+    }
+
+    // one ray through the center of each pixel, shaded without shadows nor random
+    // samples, for a first image until the samples of compute_chunk replace it
+    pub fn preview_chunk(&self, chunk: &[usize], result: &mut Vec<PixelSamples>) {
+        result.clear();
+
+        let near = self.camera.mode.near();
+        let far = self.camera.mode.far();
+
+        for packet in chunk.chunks(Self::PACKET_SIZE) {
+            if self.control.is_cancelled() {
+                break;
+            }
+
+            let rays = packet
+                .iter()
+                .map(|idx| {
+                    let i = idx / self.extent.width as usize;
+                    let j = idx % self.extent.width as usize;
+
+                    self.pixel_ray(j as f32 + 0.5, i as f32 + 0.5)
+                })
+                .collect::<Vec<_>>();
+
+            let hits = self.accel.hit_packet(&self.models, &rays, near, far);
+
+            for ((idx, ray), hit) in packet.iter().zip(&rays).zip(hits) {
+                let mut pixel = PixelSamples::new(*idx);
+                pixel.aov = AovSample::new(&hit);
+                pixel.color = self.preview(ray, hit);
+                pixel.preview = true;
+
+                result.push(pixel);
+            }
+        }
+    }
+
+    // lights without shadows and the background in the mirror direction, the
+    // material response comes from its LUT when the model has one
+    fn preview(&self, ray: &Ray, hit: Option<Hit>) -> Color {
+        let Some(mut hit) = hit else {
+            return (self.background)(ray);
+        };
+
+        for decal in self.decals.iter() {
+            hit.color = decal.apply(&hit);
+        }
+
+        let (diffuse, reflect) = match self.responses.get(hit.id as usize) {
+            Some(Some(response)) => response.lookup(-ray.direction.dot(hit.normal)),
+            _ => {
+                let reflect = self.reflectance(ray, &hit);
+                (1. - reflect, reflect)
+            }
+        };
+
+        let mut light = Color::WHITE * self.ambient;
+        for l in self.lights.iter() {
+            let cos_theta = hit.normal.dot((l.position - hit.position).normalize());
+            if cos_theta > 0. {
+                let radiance = l.illuminance(hit.position) * cos_theta / PI;
+                light = light + l.color * (radiance * self.exposure);
+            }
+        }
+
+        let mirror = (self.background)(&ray.reflect(hit.position, hit.normal));
+
+        hit.color.modulate(light) * diffuse + mirror * reflect + hit.emission * self.exposure
+    }
+
+    // closest hit of the ray through the center of a pixel
+    pub fn pick(&self, x: u32, y: u32) -> Option<Hit> {
+        let ray = self.pixel_ray(x as f32 + 0.5, y as f32 + 0.5);
+
+        self.closest_hit(&ray, self.camera.mode.near(), self.camera.mode.far())
+    }
+
+    pub fn hit_rays(&self, rays: &[Ray]) -> Vec<Option<Hit>> {
+        let near = self.camera.mode.near();
+        let far = self.camera.mode.far();
+
+        self.accel
+            .hit_packet(&self.models, rays, near, far)
+            .into_iter()
+            .zip(rays)
+            .map(|(hit, ray)| match hit {
+                Some(hit) if hit.color.a < self.alpha_cutoff => {
+                    self.closest_hit(ray, self.next_t(&hit), far)
+                }
+                hit => hit,
+            })
+            .collect()
+    }
+
+    pub fn trace_rays(&self, rays: &[Ray]) -> Vec<Color> {
+        let mut sampler =
+            Sampler::new(self.light_sampler, rays.len(), rays.len() as u32, self.seed);
+
+        rays.iter()
+            .zip(self.hit_rays(rays))
+            .enumerate()
+            .map(|(i, (ray, hit))| {
+                sampler.start(i, 0);
+                self.trace(ray, hit, &mut sampler, &mut ())
+            })
+            .collect()
+    }
+
+    // light received by a surface facing normal, in exposed units, as the integrator
+    // would shade a white lambertian surface there, including the bounces with PATH
+    pub fn irradiance(&self, position: Vec3, normal: Vec3, sampler: &mut Sampler) -> Color {
+        let hit = Hit {
+            distance: 0.,
+            position,
+            normal,
+            uv: Vec2::ZERO,
+            color: Color::WHITE,
+            reflect: 0.,
+            clearcoat: None,
+            emission: Color::new(0., 0., 0., 0.),
+            differentials: Differentials::default(),
+            front_face: true,
+            medium: false,
+            id: 0,
+        };
+        // seen from above the surface
+        let ray = Ray::new(position + normal, -normal);
+
+        self.integrate(&ray, Some(hit), sampler)
+    }
+
+    // light arriving at the origin of the ray, from the first surface hit
+    pub fn radiance(&self, ray: &Ray, sampler: &mut Sampler) -> Color {
+        let hit = self.closest_hit(ray, self.camera.mode.near(), self.camera.mode.far());
+
+        self.integrate(ray, hit, sampler)
+    }
+
+    // the toon shading needs the neighbour pixels, the rays off the camera are traced
+    fn integrate(&self, ray: &Ray, hit: Option<Hit>, sampler: &mut Sampler) -> Color {
+        match self.integrator {
+            Integrator::PATH => self.path(ray, hit, sampler, &mut ()),
+            Integrator::WHITTED | Integrator::TOON(_) => self.trace(ray, hit, sampler, &mut ()),
+        }
+    }
+
+    pub fn is_visible(&self, from: Vec3, to: Vec3) -> bool {
+        let direction = to - from;
+        let ray = Ray::new(from, direction);
+
+        !self.is_occluded(&ray, self.camera.mode.near(), direction.length())
+    }
+
+    // offset in the pixel, from its top left corner
+    fn primary_ray(&self, idx: usize, offset: Vec2) -> Ray {
+        let i = idx / self.extent.width as usize;
+        let j = idx % self.extent.width as usize;
+
+        self.pixel_ray(j as f32 + offset.x, i as f32 + offset.y)
+    }
+
+    // adds the color of a sample to the pixels within the radius of the filter
+    fn splat(
+        &self,
+        idx: usize,
+        offset: Vec2,
+        color: Color,
+        splats: &mut HashMap<usize, (Color, f32)>,
+    ) {
+        let (width, height) = (self.extent.width as i32, self.extent.height as i32);
+        let x = (idx % self.extent.width as usize) as f32 + offset.x;
+        let y = (idx / self.extent.width as usize) as f32 + offset.y;
+        let radius = self.filter.radius();
+
+        let x_min = (x - 0.5 - radius).ceil() as i32;
+        let x_max = (x - 0.5 + radius).floor() as i32;
+        let y_min = (y - 0.5 - radius).ceil() as i32;
+        let y_max = (y - 0.5 + radius).floor() as i32;
+
+        for py in y_min.max(0)..=y_max.min(height - 1) {
+            for px in x_min.max(0)..=x_max.min(width - 1) {
+                let weight = self.filter.weight(x - px as f32 - 0.5, y - py as f32 - 0.5);
+                if weight == 0. {
+                    continue;
+                }
+
+                let splat = splats
+                    .entry((px + py * width) as usize)
+                    .or_insert((Color::new(0., 0., 0., 0.), 0.));
+                *splat = (splat.0 + color * weight, splat.1 + weight);
+            }
+        }
+    }
+
+    // x, y in pixel coordinates
+    fn pixel_ray(&self, x: f32, y: f32) -> Ray {
+        let ndc = Vec2::new(
+            2. * x / self.extent.width as f32 - 1.,
+            1. - 2. * y / self.extent.height as f32,
+        );
+        if let Some(ray) = self.camera.ray(ndc) {
+            return ray;
+        }
+
+        // the other cameras look along +z
+        let p = ndc * self.camera.mode.half_plane(self.extent.aspect());
+
+        match self.camera.mode {
+            ProjectionMode::ORTHO { .. } => Ray::new(self.camera.position + p.extend(0.), Vec3::Z),
+            _ => Ray::new(self.camera.position, p.extend(1.)),
+        }
+    }
+
+    // inverse of pixel_ray, None behind the camera
+    fn pixel_position(&self, p: Vec3) -> Option<Vec2> {
+        let (width, height) = (self.extent.width as f32, self.extent.height as f32);
+
+        let ndc = match self.camera.mode {
+            ProjectionMode::MATRIX {
+                inverse_view_proj, ..
+            } => {
+                let clip = inverse_view_proj.inverse() * p.extend(1.);
+                if clip.w <= 0. {
+                    return None;
+                }
+                Vec2::new(clip.x / clip.w, clip.y / clip.w)
+            }
+            mode => {
+                let d = p - self.camera.position;
+                if d.z <= 0. {
+                    return None;
+                }
+
+                let half = mode.half_plane(self.extent.aspect());
+                match mode {
+                    ProjectionMode::ORTHO { .. } => d.truncate() / half,
+                    _ => d.truncate() / d.z / half,
+                }
+            }
+        };
+
+        Some(Vec2::new(
+            (ndc.x + 1.) * width / 2.,
+            (1. - ndc.y) * height / 2.,
+        ))
+    }
+
+    // pixels covered by the box as x, y, width, height, None if it is partly behind
+    // the camera
+    pub fn screen_rect(&self, bounds: &Aabb) -> Option<(u32, u32, u32, u32)> {
+        let (min, max) = (bounds.min, bounds.max);
+
+        let mut lo = Vec2::splat(f32::INFINITY);
+        let mut hi = Vec2::splat(f32::NEG_INFINITY);
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            );
+            let p = self.pixel_position(corner)?;
+            lo = lo.min(p);
+            hi = hi.max(p);
+        }
+
+        let (width, height) = (self.extent.width as f32, self.extent.height as f32);
+        let x0 = lo.x.floor().clamp(0., width) as u32;
+        let y0 = lo.y.floor().clamp(0., height) as u32;
+        let x1 = hi.x.ceil().clamp(0., width) as u32;
+        let y1 = hi.y.ceil().clamp(0., height) as u32;
+
+        Some((x0, y0, x1 - x0, y1 - y0))
+    }
+
+    fn trace(
+        &self,
+        ray: &Ray,
+        hit: Option<Hit>,
+        sampler: &mut Sampler,
+        payload: &mut dyn Payload,
+    ) -> Color {
+        let bg: fn(&Ray) -> Color = self.background;
+
+        let far = self.camera.mode.far();
+
+        let mut color = Color::BLACK;
+        let mut throughput = 1.;
+
+        let mut ray = *ray;
+        let mut hit = hit;
+
+        for depth in 0..self.n_reflects {
+            let Some(mut surface) = hit else {
+                payload.miss(&ray, depth);
+                color = color + bg(&ray) * throughput;
+                break;
+            };
+
+            for decal in self.decals.iter() {
+                surface.color = decal.apply(&surface);
+            }
+
+            throughput *= payload.hit(&ray, &surface, depth);
+
+            let (local, reflectance) = self.shade(&ray, &surface, sampler);
+
+            color = color + local * throughput;
+            throughput *= reflectance;
+
+            if throughput < self.min_throughput || depth + 1 == self.n_reflects {
+                break;
+            }
+
+            ray = ray.reflect(surface.position, surface.normal);
+            hit = self.closest_hit(&ray, self.t_min(&surface), far);
+            stats::record_bounce(&hit);
+        }
+
+        color
+    }
+
+    // unidirectional path tracing, the emissive surfaces are found both by the light
+    // sampling and by the bounces, the two estimates are combined with the power
+    // heuristic
+    fn path(
+        &self,
+        ray: &Ray,
+        hit: Option<Hit>,
+        sampler: &mut Sampler,
+        payload: &mut dyn Payload,
+    ) -> Color {
+        let far = self.camera.mode.far();
+
+        let mut color = Color::BLACK;
+        let mut throughput = Color::WHITE;
+        // pdf of the last bounce if it was diffuse
+        let mut bsdf_pdf = None;
+
+        let mut ray = *ray;
+        let mut hit = hit;
+
+        for depth in 0..self.n_reflects {
+            let Some(mut surface) = hit else {
+                payload.miss(&ray, depth);
+                // the background of the diffuse bounces is already sampled through the portals
+                if bsdf_pdf.is_none() || self.portals.is_empty() {
+                    color = color + (self.background)(&ray).modulate(throughput);
+                }
+                break;
+            };
+
+            for decal in self.decals.iter() {
+                surface.color = decal.apply(&surface);
+            }
+
+            throughput = throughput * payload.hit(&ray, &surface, depth);
+
+            if surface.front_face {
+                let weight = match (bsdf_pdf, self.emitter_pdf(&ray, &surface)) {
+                    (Some(bsdf_pdf), Some(light_pdf)) => power_heuristic(bsdf_pdf, light_pdf),
+                    _ => 1.,
+                };
+                color = color + (surface.emission * (self.exposure * weight)).modulate(throughput);
+            }
+
+            if sampler.next() < self.reflectance(&ray, &surface) {
+                ray = ray.reflect(surface.position, surface.normal);
+                bsdf_pdf = None;
+            } else {
+                let light =
+                    self.direct_light(&surface, sampler) + self.emitter_light(&surface, sampler);
+                color = color + surface.color.modulate(light).modulate(throughput);
+
+                let direction = if surface.medium {
+                    uniform_sphere(sampler.next(), sampler.next())
+                } else {
+                    surface.onb().sample_cosine(sampler.next(), sampler.next())
+                };
+                ray = Ray::new(surface.position, direction);
+                bsdf_pdf = Some(scattering(&surface, ray.direction));
+                throughput = throughput.modulate(surface.color);
+            }
+
+            if throughput.luminance() < self.min_throughput || depth + 1 == self.n_reflects {
+                break;
+            }
+
+            hit = self.closest_hit(&ray, self.t_min(&surface), far);
+            stats::record_bounce(&hit);
+        }
+
+        color
+    }
+
+    // flat shaded bands, outlined where the surface differs from the next pixels
+    fn toon(
+        &self,
+        idx: usize,
+        ray: &Ray,
+        hit: Option<Hit>,
+        toon: &Toon,
+        sampler: &mut Sampler,
+    ) -> Color {
+        let near = self.camera.mode.near();
+        let far = self.camera.mode.far();
+
+        let i = idx / self.extent.width as usize;
+        let j = idx % self.extent.width as usize;
+
+        let right = self.closest_hit(&self.pixel_ray(j as f32 + 1.5, i as f32 + 0.5), near, far);
+        let below = self.closest_hit(&self.pixel_ray(j as f32 + 0.5, i as f32 + 1.5), near, far);
+
+        if toon.is_edge(&hit, &right) || toon.is_edge(&hit, &below) {
+            return Color::BLACK;
+        }
+
+        let Some(mut surface) = hit else {
+            return (self.background)(ray);
+        };
+
+        for decal in self.decals.iter() {
+            surface.color = decal.apply(&surface);
+        }
+
+        let intensity = toon.quantize(self.direct_light(&surface, sampler).luminance());
+
+        surface.color * (self.ambient + intensity).min(1.)
+    }
+
+    // returns the light emitted by the surface towards the ray and the weight of the reflection
+    fn shade(&self, ray: &Ray, hit: &Hit, sampler: &mut Sampler) -> (Color, f32) {
+        let light = self.direct_light(hit, sampler);
+        let diffuse = hit.color.modulate(light) + hit.color * self.ambient;
+
+        let mut local = diffuse * (1. - hit.reflect);
+        let mut reflectance = hit.reflect;
+
+        // the coat reflects in the same direction as the base layer
+        if let Some(clearcoat) = hit.clearcoat {
+            let f = clearcoat.reflectance(-ray.direction.dot(hit.normal));
+            local = local * (1. - f);
+            reflectance = reflectance * (1. - f) + f;
+        }
+
+        (local + hit.emission * self.exposure, reflectance)
+    }
+
+    // lambertian response to the visible lights, in exposed units, and to the
+    // background seen through the portals
+    fn direct_light(&self, hit: &Hit, sampler: &mut Sampler) -> Color {
+        let mut light_color = self.portal_light(hit, sampler);
+
+        if self.lights.is_empty() {
+            return light_color;
+        }
+
+        // the selected light stands for all of them
+        let (lights, weight) = match self.light_sampling {
+            LightSampling::ALL => (&self.lights[..], 1.),
+            LightSampling::ONE => {
+                let n = self.lights.len();
+                let i = ((sampler.next() * n as f32) as usize).min(n - 1);
+
+                (&self.lights[i..=i], n as f32)
+            }
+        };
+
+        for light in lights {
+            let offset = light.sample(sampler.next(), sampler.next()) - light.position;
+            let light_direction = light.position + offset - hit.position;
+            let scattering = scattering(hit, light_direction.normalize());
+            if scattering <= 0. {
+                continue;
+            }
+
+            // occluders behind the light don't cast shadows
+            let origin = self.shadow_origin(hit);
+            let shadow_direction = light.position + offset - origin;
+            let light_ray = Ray::new(origin, shadow_direction);
+            let (min, max) = (self.t_min(hit), shadow_direction.length());
+
+            let blocked = if self.soft_casters {
+                self.is_shadowed(hit, light, offset)
+            } else {
+                self.is_occluded(&light_ray, min, max)
+            };
+
+            if !blocked {
+                let transmittance = self.transmittance(&light_ray, min, max);
+                let radiance = light.illuminance(hit.position) * scattering * transmittance;
+                light_color = light_color + light.color * (radiance * self.exposure * weight);
+            }
+        }
+
+        light_color
+    }
+
+    // weight of the mirror reflection, the coat reflects in the same direction as
+    // the base layer
+    fn reflectance(&self, ray: &Ray, hit: &Hit) -> f32 {
+        match hit.clearcoat {
+            Some(clearcoat) => {
+                let f = clearcoat.reflectance(-ray.direction.dot(hit.normal));
+                hit.reflect * (1. - f) + f
+            }
+            None => hit.reflect,
+        }
+    }
+
+    // light of a point on an emissive surface, in exposed units, weighted against
+    // the bounces that would reach it
+    fn emitter_light(&self, hit: &Hit, sampler: &mut Sampler) -> Color {
+        let n = self.emitters.len();
+        if n == 0 {
+            return Color::BLACK;
+        }
+
+        let (idx, _) = self.emitters[((sampler.next() * n as f32) as usize).min(n - 1)];
+        let Some(sample) = self.models[idx].sample_emitter(sampler.next(), sampler.next()) else {
+            return Color::BLACK;
+        };
+
+        let origin = self.shadow_origin(hit);
+        let direction = sample.position - origin;
+        let distance = direction.length();
+        let direction = direction / distance;
+
+        let scattering = scattering(hit, direction);
+        let cos_light = -sample.normal.dot(direction);
+        if scattering <= 0. || cos_light <= 0. {
+            return Color::BLACK;
+        }
+
+        // stops short of the emitter
+        let ray = Ray::new(origin, direction);
+        let (min, max) = (self.t_min(hit), distance - self.t_min(hit));
+        if self.is_occluded(&ray, min, max) {
+            return Color::BLACK;
+        }
+
+        // the scattering is also the pdf of the bounces
+        let light_pdf = distance * distance / (cos_light * sample.area * n as f32);
+        let weight = power_heuristic(light_pdf, scattering);
+        let transmittance = self.transmittance(&ray, min, max);
+
+        sample.emission * (self.exposure * weight * scattering * transmittance / light_pdf)
+    }
+
+    // pdf in solid angle of the light sampling for the emitter hit by the ray
+    fn emitter_pdf(&self, ray: &Ray, hit: &Hit) -> Option<f32> {
+        let (_, area) = self
+            .emitters
+            .iter()
+            .find(|(idx, _)| *idx == hit.id as usize)?;
+        let cos_light = -ray.direction.dot(hit.normal);
+        if cos_light <= 0. {
+            return None;
+        }
+
+        Some(hit.distance * hit.distance / (cos_light * area * self.emitters.len() as f32))
+    }
+
+    // background seen through a portal picked by area
+    fn portal_light(&self, hit: &Hit, sampler: &mut Sampler) -> Color {
+        let total = self.portals.iter().map(|p| p.area()).sum::<f32>();
+        if total <= 0. {
+            return Color::BLACK;
+        }
+
+        let mut pick = sampler.next() * total;
+        let portal = self.portals.iter().find(|p| {
+            pick -= p.area();
+            pick <= 0.
+        });
+        let Some(portal) = portal.or(self.portals.last()) else {
+            return Color::BLACK;
+        };
+
+        let origin = self.shadow_origin(hit);
+        let direction = portal.sample(sampler.next(), sampler.next()) - origin;
+        let distance = direction.length();
+        let direction = direction / distance;
+
+        let scattering = scattering(hit, direction);
+        let cos_portal = portal.normal().dot(direction).abs();
+        if scattering <= 0. || cos_portal <= 0. {
+            return Color::BLACK;
+        }
+
+        // the background is beyond the portal
+        let ray = Ray::new(origin, direction);
+        if self.is_occluded(&ray, self.t_min(hit), f32::MAX) {
+            return Color::BLACK;
+        }
+        let transmittance = self.transmittance(&ray, self.t_min(hit), f32::MAX);
+
+        // the pdf of the direction is distance^2 / (cos_portal * total)
+        (self.background)(&ray)
+            * (scattering * transmittance * cos_portal * total / (distance * distance))
+    }
+
+    // each occluder sees the light with its radius scaled by its shadow softness
+    fn is_shadowed(&self, hit: &Hit, light: &PointLight, offset: Vec3) -> bool {
+        let position = self.shadow_origin(hit);
+        let near = self.t_min(hit);
+
+        let light_direction = light.position + offset - position;
+        let light_ray = Ray::new(position, light_direction);
+        let max = light_direction.length();
+
+        let mut min = near;
+
+        while let Some(occluder) = self.occluder(&light_ray, min, max) {
+            let model = &self.models[occluder.id as usize];
+            let softness = model.shadow_softness();
+
+            if softness == 1. {
+                return true;
+            }
+
+            let soft_direction = light.position + offset * softness - position;
+            let soft_ray = Ray::new(position, soft_direction);
+            let soft_max = soft_direction.length();
+
+            if model.hit_distance(&soft_ray, near, soft_max).is_some() {
+                return true;
+            }
+
+            min = self.next_t(&occluder);
+        }
+
+        false
+    }
+
+    // moved off the surface so that it doesn't shadow itself, the normal faces the
+    // light when the shadow ray is cast
+    fn shadow_origin(&self, hit: &Hit) -> Vec3 {
+        if hit.medium {
+            return hit.position;
+        }
+
+        hit.position + hit.normal * self.shadow_bias
+    }
+
+    // start of the rays leaving a surface, scaled with the magnitude of the coordinates
+    // and the distance travelled by the incoming ray since the error of the hit position
+    // grows with both
+    fn t_min(&self, hit: &Hit) -> f32 {
+        self.epsilon * (1. + hit.position.abs().max_element() + hit.distance)
+    }
+
+    // continues a ray past a surface it went through
+    fn next_t(&self, hit: &Hit) -> f32 {
+        hit.distance + self.t_min(hit)
+    }
+
+    fn closest_hit(&self, ray: &Ray, min: f32, max: f32) -> Option<Hit> {
+        let mut min = min;
+
+        loop {
+            let hit = self.accel.hit(&self.models, ray, min, max)?;
+
+            // cutout: skip the masked surface and keep going along the ray
+            if hit.color.a >= self.alpha_cutoff {
+                return Some(hit);
+            }
+
+            min = self.next_t(&hit);
+        }
+    }
+
+    fn is_occluded(&self, ray: &Ray, min: f32, max: f32) -> bool {
+        stats::record_shadow_ray();
+
+        if self.alpha_cutoff > 0. {
+            self.occluder(ray, min, max).is_some()
+        } else {
+            self.accel.occluded(&self.models, ray, min, max)
+        }
+    }
+
+    // closest surface along a shadow ray, the media only attenuate it
+    fn occluder(&self, ray: &Ray, min: f32, max: f32) -> Option<Hit> {
+        let mut min = min;
+
+        loop {
+            let hit = self.closest_hit(ray, min, max)?;
+            if !hit.medium {
+                return Some(hit);
+            }
+
+            min = self.next_t(&hit);
+        }
+    }
+
+    // product of the transmittance of the media along an unoccluded shadow ray
+    fn transmittance(&self, ray: &Ray, min: f32, max: f32) -> f32 {
+        self.media
+            .iter()
+            .map(|&idx| self.models[idx].transmittance(ray, min, max))
+            .product()
+    }
+}
+
+// models that can be sampled as lights, with their area
+pub(crate) fn emitters(models: &[Arc<Primitive>]) -> Vec<(usize, f32)> {
+    models
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, model)| model.sample_emitter(0.5, 0.5).map(|s| (idx, s.area)))
+        .collect()
+}
+
+// models that attenuate the shadow rays
+pub(crate) fn media(models: &[Arc<Primitive>]) -> Vec<usize> {
+    models
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, model)| model.is_medium().then_some(idx))
+        .collect()
+}
+
+// LUT of the material of each model, baked once for the preview
+pub(crate) fn responses(models: &[Arc<Primitive>]) -> Vec<Option<ResponseLut>> {
+    models
+        .iter()
+        .map(|model| model.material().map(ResponseLut::bake))
+        .collect()
+}
+
+// response of the surface to the light coming from direction, cosine weighted for
+// the lambertian surfaces and isotropic for the media, this is also the pdf of the
+// bounces
+fn scattering(hit: &Hit, direction: Vec3) -> f32 {
+    if hit.medium {
+        1. / (4. * PI)
+    } else {
+        hit.normal.dot(direction).max(0.) / PI
+    }
+}
+
+// weight of the estimate a against the estimate b for the same light
+fn power_heuristic(a: f32, b: f32) -> f32 {
+    a * a / (a * a + b * b)
+}
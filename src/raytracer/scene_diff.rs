@@ -0,0 +1,126 @@
+use std::fmt;
+
+use crate::raytracer::SceneFile;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    ADDED,
+    REMOVED,
+    CHANGED,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneItem {
+    CAMERA,
+    BACKGROUND,
+    RENDER,
+    MODEL,
+    LIGHT,
+    PORTAL,
+    MATERIAL,
+}
+
+// models and materials are matched by name, lights and portals by position in the file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SceneChange {
+    pub item: SceneItem,
+    pub name: String,
+    pub kind: ChangeKind,
+}
+
+// changes to go from a scene file to another, see Tracer::diff_scenes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SceneDiff {
+    pub changes: Vec<SceneChange>,
+}
+
+impl SceneDiff {
+    pub fn new(from: &SceneFile, to: &SceneFile) -> Self {
+        let mut diff = Self::default();
+
+        if from.camera != to.camera {
+            diff.push(SceneItem::CAMERA, "camera", ChangeKind::CHANGED);
+        }
+        if from.background != to.background {
+            diff.push(SceneItem::BACKGROUND, "background", ChangeKind::CHANGED);
+        }
+        if from.render != to.render {
+            diff.push(SceneItem::RENDER, "render", ChangeKind::CHANGED);
+        }
+
+        diff.named(SceneItem::MODEL, &from.primitives, &to.primitives, |p| {
+            p.name()
+        });
+        diff.indexed(SceneItem::LIGHT, &from.lights, &to.lights);
+        diff.indexed(SceneItem::PORTAL, &from.portals, &to.portals);
+        diff.named(SceneItem::MATERIAL, &from.materials, &to.materials, |m| {
+            &m.name
+        });
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn push(&mut self, item: SceneItem, name: &str, kind: ChangeKind) {
+        self.changes.push(SceneChange {
+            item,
+            name: name.to_string(),
+            kind,
+        });
+    }
+
+    fn named<T: PartialEq>(
+        &mut self,
+        item: SceneItem,
+        from: &[T],
+        to: &[T],
+        name: impl Fn(&T) -> &str,
+    ) {
+        for a in from {
+            match to.iter().find(|b| name(b) == name(a)) {
+                None => self.push(item, name(a), ChangeKind::REMOVED),
+                Some(b) if a != b => self.push(item, name(a), ChangeKind::CHANGED),
+                Some(_) => {}
+            }
+        }
+
+        for b in to {
+            if !from.iter().any(|a| name(a) == name(b)) {
+                self.push(item, name(b), ChangeKind::ADDED);
+            }
+        }
+    }
+
+    fn indexed<T: PartialEq>(&mut self, item: SceneItem, from: &[T], to: &[T]) {
+        for i in 0..from.len().max(to.len()) {
+            let kind = match (from.get(i), to.get(i)) {
+                (Some(a), Some(b)) if a != b => ChangeKind::CHANGED,
+                (Some(_), None) => ChangeKind::REMOVED,
+                (None, Some(_)) => ChangeKind::ADDED,
+                _ => continue,
+            };
+
+            self.push(item, &i.to_string(), kind);
+        }
+    }
+}
+
+// one line per change, e.g. "~ MATERIAL red"
+impl fmt::Display for SceneDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            let sign = match change.kind {
+                ChangeKind::ADDED => '+',
+                ChangeKind::REMOVED => '-',
+                ChangeKind::CHANGED => '~',
+            };
+
+            writeln!(f, "{} {:?} {}", sign, change.item, change.name)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,343 @@
+use std::{fs, io, sync::Arc};
+
+use glam::{UVec3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::raytracer::{
+    Aabb, Background, Camera, CheckerTexture, Color, LightPower, MarbleTexture, Material,
+    NoiseTexture, PointLight, Portal, Primitive, Sphere, Texture, TracerBuilder, TracerConfig,
+    VoxelVolume, WrapMode,
+};
+
+#[cfg(feature = "image")]
+use crate::raytracer::ImageTexture;
+
+// toml description of a scene, materials are referenced by name from the primitives
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SceneFile {
+    pub camera: CameraDesc,
+    #[serde(default)]
+    pub background: Option<Background>,
+    #[serde(default)]
+    pub lights: Vec<LightDesc>,
+    #[serde(default)]
+    pub portals: Vec<PortalDesc>,
+    #[serde(default)]
+    pub materials: Vec<MaterialDesc>,
+    #[serde(default)]
+    pub primitives: Vec<PrimitiveDesc>,
+    pub render: TracerConfig,
+}
+
+// angles in degrees
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraDesc {
+    pub position: [f32; 3],
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LightDesc {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+    pub lumens: f32,
+    #[serde(default)]
+    pub radius: f32,
+}
+
+// corner and the two edges from it
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PortalDesc {
+    pub corner: [f32; 3],
+    pub u: [f32; 3],
+    pub v: [f32; 3],
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MaterialDesc {
+    pub name: String,
+    pub albedo: TextureDesc,
+    pub reflect: f32,
+    // strength, ior
+    #[serde(default)]
+    pub clearcoat: Option<(f32, f32)>,
+    #[serde(default = "MaterialDesc::default_softness")]
+    pub shadow_softness: f32,
+    #[serde(default)]
+    pub emission: Option<EmissionDesc>,
+}
+
+// luminance in cd/m2
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum EmissionDesc {
+    COLOR { color: [f32; 3], luminance: f32 },
+    BLACKBODY { kelvin: f32, luminance: f32 },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum TextureDesc {
+    COLOR {
+        color: [f32; 3],
+    },
+    CHECKER {
+        even: [f32; 3],
+        odd: [f32; 3],
+        scale: f32,
+    },
+    NOISE {
+        color: [f32; 3],
+        scale: f32,
+        #[serde(default)]
+        seed: u64,
+    },
+    MARBLE {
+        color: [f32; 3],
+        scale: f32,
+        turbulence: f32,
+        #[serde(default)]
+        seed: u64,
+    },
+    IMAGE {
+        path: String,
+        wrap: WrapMode,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum PrimitiveDesc {
+    SPHERE {
+        name: String,
+        center: [f32; 3],
+        radius: f32,
+        material: String,
+    },
+    // raw little endian f32 densities, see VoxelVolume::load_raw
+    VOLUME {
+        name: String,
+        path: String,
+        size: [u32; 3],
+        min: [f32; 3],
+        max: [f32; 3],
+        sigma: f32,
+        albedo: [f32; 3],
+    },
+}
+
+impl SceneFile {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let data = fs::read_to_string(path)?;
+
+        toml::from_str(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let data = toml::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, data)?;
+
+        log::info!("Scene saved: {}", path);
+
+        Ok(())
+    }
+
+    pub async fn builder(&self) -> io::Result<TracerBuilder> {
+        let render = &self.render;
+
+        let mut builder = TracerBuilder::from_config(render).await.camera(
+            self.camera
+                .camera(render.width as f32 / render.height as f32),
+        );
+
+        if let Some(background) = self.background {
+            builder = builder.background_preset(background);
+        }
+
+        for light in self.lights() {
+            builder = builder.light(light);
+        }
+
+        for portal in self.portals() {
+            builder = builder.portal(portal);
+        }
+
+        for model in self.models()? {
+            builder = builder.model(model);
+        }
+
+        Ok(builder.file(self.clone()))
+    }
+
+    pub fn lights(&self) -> Vec<PointLight> {
+        self.lights.iter().map(|light| light.light()).collect()
+    }
+
+    pub fn portals(&self) -> Vec<Portal> {
+        self.portals.iter().map(|portal| portal.portal()).collect()
+    }
+
+    pub fn models(&self) -> io::Result<Vec<Primitive>> {
+        self.primitives
+            .iter()
+            .map(|primitive| match primitive {
+                PrimitiveDesc::SPHERE {
+                    name,
+                    center,
+                    radius,
+                    material,
+                } => Ok(Sphere::with_material(
+                    name,
+                    Vec3::from_array(*center),
+                    *radius,
+                    self.material(material)?,
+                )),
+                PrimitiveDesc::VOLUME {
+                    name,
+                    path,
+                    size,
+                    min,
+                    max,
+                    sigma,
+                    albedo,
+                } => Ok(VoxelVolume::load_raw(
+                    name,
+                    path,
+                    UVec3::from_array(*size),
+                    Aabb::new(Vec3::from_array(*min), Vec3::from_array(*max)),
+                )?
+                .sigma(*sigma)
+                .albedo(color(*albedo))
+                .into()),
+            })
+            .collect()
+    }
+
+    fn material(&self, name: &str) -> io::Result<Material> {
+        let Some(desc) = self.materials.iter().find(|m| m.name == name) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown material: {}", name),
+            ));
+        };
+
+        desc.material()
+    }
+}
+
+impl PrimitiveDesc {
+    pub fn name(&self) -> &str {
+        match self {
+            PrimitiveDesc::SPHERE { name, .. } | PrimitiveDesc::VOLUME { name, .. } => name,
+        }
+    }
+}
+
+impl CameraDesc {
+    pub fn camera(&self, aspect: f32) -> Camera {
+        Camera::perspective(
+            Vec3::from_array(self.position),
+            aspect,
+            self.fov.to_radians(),
+            self.near,
+            self.far,
+            self.yaw.to_radians(),
+            self.pitch.to_radians(),
+            Vec3::Y,
+        )
+    }
+}
+
+impl LightDesc {
+    pub fn light(&self) -> PointLight {
+        PointLight::new(
+            Vec3::from_array(self.position),
+            color(self.color),
+            LightPower::LUMENS(self.lumens),
+        )
+        .radius(self.radius)
+    }
+}
+
+impl PortalDesc {
+    pub fn portal(&self) -> Portal {
+        Portal::new(
+            Vec3::from_array(self.corner),
+            Vec3::from_array(self.u),
+            Vec3::from_array(self.v),
+        )
+    }
+}
+
+impl MaterialDesc {
+    fn default_softness() -> f32 {
+        1.
+    }
+
+    pub fn material(&self) -> io::Result<Material> {
+        let mut material = Material::new(self.albedo.texture()?, self.reflect)
+            .shadow_softness(self.shadow_softness);
+
+        if let Some((strength, ior)) = self.clearcoat {
+            material = material.clearcoat(strength, ior);
+        }
+
+        match self.emission {
+            Some(EmissionDesc::COLOR {
+                color: c,
+                luminance,
+            }) => {
+                material = material.emissive(color(c), luminance);
+            }
+            Some(EmissionDesc::BLACKBODY { kelvin, luminance }) => {
+                material = material.blackbody(kelvin, luminance);
+            }
+            None => (),
+        }
+
+        Ok(material)
+    }
+}
+
+impl TextureDesc {
+    pub fn texture(&self) -> io::Result<Arc<dyn Texture + Send + Sync>> {
+        let texture: Arc<dyn Texture + Send + Sync> = match self {
+            TextureDesc::COLOR { color: c } => Arc::new(color(*c)),
+            TextureDesc::CHECKER { even, odd, scale } => Arc::new(CheckerTexture::new(
+                Arc::new(color(*even)),
+                Arc::new(color(*odd)),
+                *scale,
+            )),
+            TextureDesc::NOISE {
+                color: c,
+                scale,
+                seed,
+            } => Arc::new(NoiseTexture::new(color(*c), *scale).seed(*seed)),
+            TextureDesc::MARBLE {
+                color: c,
+                scale,
+                turbulence,
+                seed,
+            } => Arc::new(MarbleTexture::new(color(*c), *scale, *turbulence).seed(*seed)),
+            #[cfg(feature = "image")]
+            TextureDesc::IMAGE { path, wrap } => Arc::new(ImageTexture::load(path, *wrap)?),
+            #[cfg(not(feature = "image"))]
+            TextureDesc::IMAGE { path, .. } => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("{}: image textures need the image feature", path),
+                ))
+            }
+        };
+
+        Ok(texture)
+    }
+}
+
+fn color(rgb: [f32; 3]) -> Color {
+    Color::new(rgb[0], rgb[1], rgb[2], 1.)
+}
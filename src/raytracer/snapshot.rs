@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use crate::raytracer::{Color, Extent};
+
+// accumulated radiance at a given point of the render, the pixels are shared
+// with the buffer until the next samples are added
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub name: String,
+    pub extent: Extent,
+    pub samples_per_pixel: u32,
+    // seconds of rendering when taken
+    pub render_time: f32,
+    framebuffer: Arc<Vec<Color>>,
+}
+
+impl Snapshot {
+    pub fn new(
+        name: &str,
+        extent: Extent,
+        samples_per_pixel: u32,
+        render_time: f32,
+        framebuffer: Arc<Vec<Color>>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            extent,
+            samples_per_pixel,
+            render_time,
+            framebuffer,
+        }
+    }
+
+    // linear radiance
+    pub fn framebuffer(&self) -> &[Color] {
+        &self.framebuffer
+    }
+}
@@ -1,33 +1,73 @@
-use glam::Vec3;
-use gobs::core::Color;
+use std::{f32::consts::PI, sync::Arc};
 
-use crate::raytracer::{Hit, Hitable, Ray};
+use glam::{Vec2, Vec3};
 
-#[derive(Clone, Debug)]
+use crate::raytracer::{
+    aabb::Aabb,
+    color::ColorExt,
+    hit::{Differentials, SurfaceSample},
+    sampling, Color, Hit, Hitable, Material, Primitive, Ray, Texture,
+};
+
+#[derive(Clone)]
 pub struct Sphere {
     name: String,
     center: Vec3,
     radius: f32,
-    color: Color,
-    reflect: f32,
+    material: Material,
 }
 
 impl Sphere {
-    pub fn new(
+    pub fn new(name: &str, center: Vec3, radius: f32, color: Color, reflect: f32) -> Primitive {
+        Self::textured(name, center, radius, Arc::new(color), reflect)
+    }
+
+    pub fn textured(
         name: &str,
         center: Vec3,
         radius: f32,
-        color: Color,
+        texture: Arc<dyn Texture + Send + Sync>,
         reflect: f32,
-    ) -> Box<dyn Hitable + Send + Sync> {
-        Box::new(Self {
+    ) -> Primitive {
+        Self::with_material(name, center, radius, Material::new(texture, reflect))
+    }
+
+    pub fn with_material(name: &str, center: Vec3, radius: f32, material: Material) -> Primitive {
+        Primitive::SPHERE(Self {
             name: name.to_string(),
             center,
             radius,
-            color,
-            reflect,
+            material,
         })
     }
+
+    pub(crate) fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn uv(normal: Vec3) -> Vec2 {
+        let u = 0.5 + normal.z.atan2(normal.x) / (2. * PI);
+        let v = 0.5 - normal.y.asin() / PI;
+
+        Vec2::new(u, v)
+    }
+
+    // derivatives of the position along the uv of Sphere::uv, the longitude is
+    // undefined at the poles
+    fn derivatives(&self, normal: Vec3) -> (Vec3, Vec3) {
+        let cos_lat = (normal.x * normal.x + normal.z * normal.z).sqrt().max(1e-6);
+
+        let dpdu = 2. * PI * self.radius * Vec3::new(-normal.z, 0., normal.x);
+        let dpdv = -PI
+            * self.radius
+            * Vec3::new(
+                -normal.y * normal.x / cos_lat,
+                cos_lat,
+                -normal.y * normal.z / cos_lat,
+            );
+
+        (dpdu, dpdv)
+    }
 }
 
 impl Hitable for Sphere {
@@ -35,6 +75,43 @@ impl Hitable for Sphere {
         &self.name
     }
 
+    fn bounds(&self) -> Aabb {
+        let radius = Vec3::splat(self.radius);
+
+        Aabb::new(self.center - radius, self.center + radius)
+    }
+
+    fn sphere(&self) -> Option<(Vec3, f32)> {
+        Some((self.center, self.radius))
+    }
+
+    fn sample_emitter(&self, u: f32, v: f32) -> Option<SurfaceSample> {
+        if self.material.emission.luminance() <= 0. {
+            return None;
+        }
+
+        let normal = sampling::uniform_sphere(u, v);
+
+        Some(SurfaceSample {
+            position: self.center + normal * self.radius,
+            normal,
+            emission: self.material.emission,
+            area: 4. * PI * self.radius * self.radius,
+        })
+    }
+
+    fn shadow_softness(&self) -> f32 {
+        self.material.shadow_softness
+    }
+
+    fn rescale(&mut self, center: Vec3, scale: f32) -> bool {
+        self.center = (self.center - center) * scale;
+        self.radius *= scale;
+        self.material.rescale(center, scale);
+
+        true
+    }
+
     fn hit_distance(&self, ray: &Ray, min: f32, max: f32) -> Option<f32> {
         let d = ray.origin - self.center;
 
@@ -67,13 +144,26 @@ impl Hitable for Sphere {
             Some(t) => {
                 let position = ray.origin + t * ray.direction;
                 let normal = (position - self.center).normalize();
-                Some(Hit {
-                    distance: t,
-                    position,
-                    normal,
-                    color: self.color,
-                    reflect: self.reflect,
-                })
+                let uv = Self::uv(normal);
+                let (dpdu, dpdv) = self.derivatives(normal);
+                Some(
+                    Hit {
+                        distance: t,
+                        position,
+                        normal,
+                        uv,
+                        color: self.material.albedo.sample(uv, position),
+                        reflect: self.material.reflect,
+                        clearcoat: self.material.clearcoat,
+                        emission: self.material.emission,
+                        differentials: Differentials::new(dpdu, dpdv)
+                            .normal(dpdu / self.radius, dpdv / self.radius),
+                        front_face: true,
+                        medium: false,
+                        id: 0,
+                    }
+                    .facing(ray),
+                )
             }
             None => None,
         }
@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use glam::{Vec3, Vec4};
+
+use crate::raytracer::{Hit, Hitable, Primitive, Ray};
+
+const LANES: usize = 4;
+
+// structure of arrays layout, spheres are intersected 4 at a time
+pub struct SphereSet {
+    indices: Vec<usize>,
+    cx: Vec<Vec4>,
+    cy: Vec<Vec4>,
+    cz: Vec<Vec4>,
+    r2: Vec<Vec4>,
+}
+
+impl SphereSet {
+    pub fn new(models: &[Arc<Primitive>]) -> Option<Self> {
+        let spheres = models
+            .iter()
+            .map(|m| m.sphere())
+            .collect::<Option<Vec<(Vec3, f32)>>>()?;
+
+        let groups = spheres.len().div_ceil(LANES);
+
+        let mut cx = vec![0.; groups * LANES];
+        let mut cy = vec![0.; groups * LANES];
+        let mut cz = vec![0.; groups * LANES];
+        // padding lanes can never be hit
+        let mut r2 = vec![f32::NEG_INFINITY; groups * LANES];
+
+        for (i, (center, radius)) in spheres.iter().enumerate() {
+            cx[i] = center.x;
+            cy[i] = center.y;
+            cz[i] = center.z;
+            r2[i] = radius * radius;
+        }
+
+        let pack = |v: Vec<f32>| {
+            v.chunks_exact(LANES)
+                .map(|c| Vec4::new(c[0], c[1], c[2], c[3]))
+                .collect::<Vec<Vec4>>()
+        };
+
+        log::debug!("Sphere set: {} spheres", spheres.len());
+
+        Some(Self {
+            indices: (0..spheres.len()).collect(),
+            cx: pack(cx),
+            cy: pack(cy),
+            cz: pack(cz),
+            r2: pack(r2),
+        })
+    }
+
+    // closest model index and distance
+    pub fn hit_distance(&self, ray: &Ray, min: f32, max: f32) -> Option<(usize, f32)> {
+        let (ox, oy, oz) = (
+            Vec4::splat(ray.origin.x),
+            Vec4::splat(ray.origin.y),
+            Vec4::splat(ray.origin.z),
+        );
+        let (dx, dy, dz) = (ray.direction.x, ray.direction.y, ray.direction.z);
+
+        let a = ray.direction.dot(ray.direction);
+        let vmin = Vec4::splat(min);
+        let vmax = Vec4::splat(max);
+        let none = Vec4::splat(f32::INFINITY);
+
+        let mut closest: Option<(usize, f32)> = None;
+        let mut closest_t = f32::INFINITY;
+
+        for g in 0..self.cx.len() {
+            let px = ox - self.cx[g];
+            let py = oy - self.cy[g];
+            let pz = oz - self.cz[g];
+
+            let h = px * dx + py * dy + pz * dz;
+            let c = px * px + py * py + pz * pz - self.r2[g];
+
+            let delta = h * h - a * c;
+            let valid = delta.cmpge(Vec4::ZERO);
+            let sqrt_delta = Vec4::from_array(delta.max(Vec4::ZERO).to_array().map(f32::sqrt));
+
+            let t1 = (-h - sqrt_delta) / a;
+            let t2 = (-h + sqrt_delta) / a;
+
+            let t1_ok = valid & t1.cmpge(vmin) & t1.cmple(vmax);
+            let t2_ok = valid & t2.cmpge(vmin) & t2.cmple(vmax);
+
+            let t = Vec4::select(t1_ok, t1, Vec4::select(t2_ok, t2, none));
+
+            for (lane, &t) in t.to_array().iter().enumerate() {
+                if t < closest_t {
+                    closest_t = t;
+                    closest = Some((self.indices[g * LANES + lane], t));
+                }
+            }
+        }
+
+        closest
+    }
+
+    pub fn hit(&self, models: &[Arc<Primitive>], ray: &Ray, min: f32, max: f32) -> Option<Hit> {
+        let (idx, _) = self.hit_distance(ray, min, max)?;
+
+        models[idx].hit(ray, min, max).map(|hit| hit.with_id(idx))
+    }
+}
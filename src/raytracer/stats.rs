@@ -0,0 +1,214 @@
+use std::cell::RefCell;
+#[cfg(feature = "stats")]
+use std::{
+    mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use crate::raytracer::{debug::TraversalCounts, hit::Hit};
+
+// rays traced since the start of the render, collected with the stats feature,
+// see Tracer::stats
+#[derive(Clone, Debug, Default)]
+pub struct RenderStats {
+    pub camera_rays: u64,
+    // reflected and diffuse rays after the camera rays
+    pub bounces: u64,
+    pub shadow_rays: u64,
+    // camera rays and bounces that hit a model
+    pub hits: u64,
+    // seconds
+    pub elapsed: f32,
+    // by model, in the order of the scene
+    pub model_hits: Vec<(String, u64)>,
+}
+
+impl RenderStats {
+    pub fn rays(&self) -> u64 {
+        self.camera_rays + self.bounces + self.shadow_rays
+    }
+
+    pub fn rays_per_second(&self) -> f32 {
+        if self.elapsed > 0. {
+            self.rays() as f32 / self.elapsed
+        } else {
+            0.
+        }
+    }
+
+    // bounces per camera ray
+    pub fn average_depth(&self) -> f32 {
+        self.bounces as f32 / self.camera_rays.max(1) as f32
+    }
+
+    // part of the shadow rays in all the rays
+    pub fn shadow_ratio(&self) -> f32 {
+        self.shadow_rays as f32 / self.rays().max(1) as f32
+    }
+}
+
+// counts of a worker thread: the rays, added to the counters of the scene at the
+// end of each chunk, and the traversals of the sample being counted for the debug
+// views, see start_sample
+#[derive(Clone, Debug, Default)]
+struct LocalCounts {
+    sample: Option<TraversalCounts>,
+    #[cfg(feature = "stats")]
+    rays: RayCounts,
+}
+
+#[cfg(feature = "stats")]
+#[derive(Clone, Debug, Default)]
+struct RayCounts {
+    camera_rays: u64,
+    bounces: u64,
+    shadow_rays: u64,
+    hits: u64,
+    model_hits: Vec<u64>,
+}
+
+#[cfg(feature = "stats")]
+impl RayCounts {
+    fn hit(&mut self, hit: &Option<Hit>) {
+        if let Some(hit) = hit {
+            let id = hit.id as usize;
+            if id >= self.model_hits.len() {
+                self.model_hits.resize(id + 1, 0);
+            }
+            self.model_hits[id] += 1;
+            self.hits += 1;
+        }
+    }
+}
+
+thread_local! {
+    static COUNTS: RefCell<LocalCounts> = RefCell::new(LocalCounts::default());
+}
+
+// shared by the scene and its workers
+#[derive(Debug, Default)]
+pub(crate) struct StatCounters {
+    #[cfg(feature = "stats")]
+    camera_rays: AtomicU64,
+    #[cfg(feature = "stats")]
+    bounces: AtomicU64,
+    #[cfg(feature = "stats")]
+    shadow_rays: AtomicU64,
+    #[cfg(feature = "stats")]
+    hits: AtomicU64,
+    #[cfg(feature = "stats")]
+    model_hits: Mutex<Vec<u64>>,
+}
+
+#[cfg(feature = "stats")]
+impl StatCounters {
+    pub fn reset(&self) {
+        self.camera_rays.store(0, Ordering::Relaxed);
+        self.bounces.store(0, Ordering::Relaxed);
+        self.shadow_rays.store(0, Ordering::Relaxed);
+        self.hits.store(0, Ordering::Relaxed);
+        self.model_hits.lock().unwrap().clear();
+    }
+
+    // the counts of the calling thread since the previous flush
+    pub fn flush(&self) {
+        let counts = COUNTS.with_borrow_mut(|counts| mem::take(&mut counts.rays));
+
+        self.camera_rays
+            .fetch_add(counts.camera_rays, Ordering::Relaxed);
+        self.bounces.fetch_add(counts.bounces, Ordering::Relaxed);
+        self.shadow_rays
+            .fetch_add(counts.shadow_rays, Ordering::Relaxed);
+        self.hits.fetch_add(counts.hits, Ordering::Relaxed);
+
+        if counts.hits > 0 {
+            let mut model_hits = self.model_hits.lock().unwrap();
+            if model_hits.len() < counts.model_hits.len() {
+                model_hits.resize(counts.model_hits.len(), 0);
+            }
+            for (total, n) in model_hits.iter_mut().zip(counts.model_hits) {
+                *total += n;
+            }
+        }
+    }
+
+    pub fn snapshot(&self, names: &[&str], elapsed: f32) -> RenderStats {
+        let model_hits = self.model_hits.lock().unwrap();
+
+        RenderStats {
+            camera_rays: self.camera_rays.load(Ordering::Relaxed),
+            bounces: self.bounces.load(Ordering::Relaxed),
+            shadow_rays: self.shadow_rays.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+            elapsed,
+            model_hits: names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (name.to_string(), model_hits.get(i).copied().unwrap_or(0)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(not(feature = "stats"))]
+impl StatCounters {
+    pub fn reset(&self) {}
+
+    pub fn flush(&self) {}
+}
+
+// the ray counts of the thread are dropped, e.g. rays traced outside of a chunk
+pub(crate) fn discard() {
+    #[cfg(feature = "stats")]
+    COUNTS.with_borrow_mut(|counts| counts.rays = RayCounts::default());
+}
+
+// counts the traversals of the worker thread until take_sample, see
+// Scene::debug_counts
+pub(crate) fn start_sample() {
+    COUNTS.with_borrow_mut(|counts| counts.sample = Some(TraversalCounts::default()));
+}
+
+pub(crate) fn take_sample() -> TraversalCounts {
+    COUNTS.with_borrow_mut(|counts| counts.sample.take().unwrap_or_default())
+}
+
+// called once per traversal, the counts of a traversal are kept in locals
+pub(crate) fn record_traversal(nodes: u32, tests: u32) {
+    COUNTS.with_borrow_mut(|counts| {
+        if let Some(sample) = &mut counts.sample {
+            sample.nodes = sample.nodes.saturating_add(nodes);
+            sample.tests = sample.tests.saturating_add(tests);
+        }
+    });
+}
+
+pub(crate) fn record_camera_ray(_hit: &Option<Hit>) {
+    #[cfg(feature = "stats")]
+    COUNTS.with_borrow_mut(|counts| {
+        counts.rays.camera_rays += 1;
+        counts.rays.hit(_hit);
+    });
+}
+
+pub(crate) fn record_bounce(_hit: &Option<Hit>) {
+    COUNTS.with_borrow_mut(|counts| {
+        if let Some(sample) = &mut counts.sample {
+            sample.bounces = sample.bounces.saturating_add(1);
+        }
+
+        #[cfg(feature = "stats")]
+        {
+            counts.rays.bounces += 1;
+            counts.rays.hit(_hit);
+        }
+    });
+}
+
+pub(crate) fn record_shadow_ray() {
+    #[cfg(feature = "stats")]
+    COUNTS.with_borrow_mut(|counts| counts.rays.shadow_rays += 1);
+}
@@ -0,0 +1,93 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+// pixels are counted once per pass, i.e. every n_rays samples
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderProgress {
+    pub pixels_done: u64,
+    // None without target samples
+    pub total: Option<u64>,
+    pub samples_per_pixel: u32,
+    // seconds
+    pub elapsed: f32,
+    pub eta: Option<f32>,
+}
+
+impl RenderProgress {
+    // 0..1, None without target samples
+    pub fn ratio(&self) -> Option<f32> {
+        self.total
+            .map(|total| (self.pixels_done as f32 / total.max(1) as f32).min(1.))
+    }
+}
+
+// the progress and the number of chunks added since the previous call
+pub type ProgressHook = Box<dyn FnMut(&RenderProgress, u32) + Send>;
+
+// can be shared with other threads, e.g. to cancel the render from a close handler
+#[derive(Debug, Default)]
+pub struct RenderControl {
+    paused: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+impl RenderControl {
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    // the chunks being computed are dropped, the accumulated image is kept
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn clear(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+        self.cancelled.store(false, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RenderStatus {
+    pass: AtomicU32,
+    samples_per_pixel: AtomicU32,
+    version: AtomicU64,
+    complete: AtomicBool,
+}
+
+impl RenderStatus {
+    pub fn pass(&self) -> u32 {
+        self.pass.load(Ordering::Relaxed)
+    }
+
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel.load(Ordering::Relaxed)
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Relaxed)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn update(&self, pass: u32, samples_per_pixel: u32, version: u64, complete: bool) {
+        self.pass.store(pass, Ordering::Relaxed);
+        self.samples_per_pixel
+            .store(samples_per_pixel, Ordering::Relaxed);
+        self.version.store(version, Ordering::Relaxed);
+        self.complete.store(complete, Ordering::Relaxed);
+    }
+}
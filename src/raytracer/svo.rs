@@ -0,0 +1,310 @@
+use glam::{Vec2, Vec3};
+
+use crate::raytracer::{aabb::Aabb, hit::Differentials, Hit, Hitable, Material, Primitive, Ray};
+
+// dense voxel array used to build the octree, voxels reference a material in the palette
+pub struct VoxelGrid {
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    voxels: Vec<Option<u16>>,
+}
+
+impl VoxelGrid {
+    pub fn new(width: u32, height: u32, depth: u32) -> Self {
+        Self {
+            width,
+            height,
+            depth,
+            voxels: vec![None; (width * height * depth) as usize],
+        }
+    }
+
+    pub fn from_voxels(width: u32, height: u32, depth: u32, voxels: Vec<Option<u16>>) -> Self {
+        assert_eq!(voxels.len(), (width * height * depth) as usize);
+
+        Self {
+            width,
+            height,
+            depth,
+            voxels,
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32, z: u32) -> Option<u16> {
+        if x >= self.width || y >= self.height || z >= self.depth {
+            return None;
+        }
+
+        self.voxels[(x + y * self.width + z * self.width * self.height) as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, z: u32, material: Option<u16>) {
+        assert!(x < self.width && y < self.height && z < self.depth);
+
+        self.voxels[(x + y * self.width + z * self.width * self.height) as usize] = material;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SvoNode {
+    EMPTY,
+    SOLID(u16),
+    // index of the first of 8 contiguous children
+    BRANCH(u32),
+}
+
+pub struct Svo {
+    name: String,
+    origin: Vec3,
+    voxel_size: f32,
+    // number of voxels along one side of the root node
+    size: u32,
+    root: SvoNode,
+    nodes: Vec<SvoNode>,
+    palette: Vec<Material>,
+}
+
+impl Svo {
+    pub fn new(
+        name: &str,
+        grid: &VoxelGrid,
+        origin: Vec3,
+        voxel_size: f32,
+        palette: Vec<Material>,
+    ) -> Primitive {
+        Primitive::SVO(Self::build(name, grid, origin, voxel_size, palette))
+    }
+
+    pub fn build(
+        name: &str,
+        grid: &VoxelGrid,
+        origin: Vec3,
+        voxel_size: f32,
+        palette: Vec<Material>,
+    ) -> Self {
+        // the voxels reference the palette when hit
+        if let Some(&material) = grid.voxels.iter().flatten().max() {
+            assert!(
+                (material as usize) < palette.len(),
+                "SVO {}: material {} out of a palette of {}",
+                name,
+                material,
+                palette.len()
+            );
+        }
+
+        let size = grid
+            .width
+            .max(grid.height)
+            .max(grid.depth)
+            .next_power_of_two();
+
+        let mut nodes = Vec::new();
+        let root = Self::build_node(grid, 0, 0, 0, size, &mut nodes);
+
+        log::info!("SVO {}: {} voxels wide, {} nodes", name, size, nodes.len());
+
+        Self {
+            name: name.to_string(),
+            origin,
+            voxel_size,
+            size,
+            root,
+            nodes,
+            palette,
+        }
+    }
+
+    fn build_node(
+        grid: &VoxelGrid,
+        x: u32,
+        y: u32,
+        z: u32,
+        size: u32,
+        nodes: &mut Vec<SvoNode>,
+    ) -> SvoNode {
+        if size == 1 {
+            return match grid.get(x, y, z) {
+                Some(material) => SvoNode::SOLID(material),
+                None => SvoNode::EMPTY,
+            };
+        }
+
+        let half = size / 2;
+
+        let children: Vec<SvoNode> = (0..8)
+            .map(|i| {
+                Self::build_node(
+                    grid,
+                    x + (i & 1) * half,
+                    y + ((i >> 1) & 1) * half,
+                    z + ((i >> 2) & 1) * half,
+                    half,
+                    nodes,
+                )
+            })
+            .collect();
+
+        // collapse uniform regions into a single leaf
+        if let SvoNode::EMPTY | SvoNode::SOLID(_) = children[0] {
+            if children.iter().all(|c| *c == children[0]) {
+                return children[0];
+            }
+        }
+
+        let first = nodes.len() as u32;
+        nodes.extend(children);
+
+        SvoNode::BRANCH(first)
+    }
+
+    // returns the distance, entry normal and material of the closest solid voxel
+    fn traverse(
+        &self,
+        node: SvoNode,
+        bounds: &Aabb,
+        ray: &Ray,
+        min: f32,
+        max: f32,
+    ) -> Option<(f32, Vec3, u16)> {
+        match node {
+            SvoNode::EMPTY => None,
+            SvoNode::SOLID(material) => {
+                let (t, _) = bounds.hit(ray, min, max)?;
+
+                Some((t, Self::entry_normal(bounds, ray), material))
+            }
+            SvoNode::BRANCH(first) => {
+                let half = 0.5 * bounds.extent();
+
+                let mut children = [(0., 0, Aabb::empty()); 8];
+                let mut count = 0;
+
+                for i in 0..8 {
+                    let child = self.nodes[first as usize + i];
+                    if child == SvoNode::EMPTY {
+                        continue;
+                    }
+
+                    let offset =
+                        Vec3::new((i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32)
+                            * half;
+                    let child_bounds = Aabb::new(bounds.min + offset, bounds.min + offset + half);
+
+                    if let Some((t, _)) = child_bounds.hit(ray, min, max) {
+                        children[count] = (t, i, child_bounds);
+                        count += 1;
+                    }
+                }
+
+                // children are disjoint, the first one entered holds the closest hit
+                let children = &mut children[..count];
+                children.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                children.iter().find_map(|(_, i, child_bounds)| {
+                    self.traverse(self.nodes[first as usize + i], child_bounds, ray, min, max)
+                })
+            }
+        }
+    }
+
+    fn entry_normal(bounds: &Aabb, ray: &Ray) -> Vec3 {
+        let inv = ray.direction.recip();
+
+        let t0 = (bounds.min - ray.origin) * inv;
+        let t1 = (bounds.max - ray.origin) * inv;
+        let t_near = t0.min(t1);
+
+        if t_near.x >= t_near.y && t_near.x >= t_near.z {
+            Vec3::new(-ray.direction.x.signum(), 0., 0.)
+        } else if t_near.y >= t_near.z {
+            Vec3::new(0., -ray.direction.y.signum(), 0.)
+        } else {
+            Vec3::new(0., 0., -ray.direction.z.signum())
+        }
+    }
+
+    // face local coordinates in voxel units
+    fn uv(&self, position: Vec3, normal: Vec3) -> Vec2 {
+        let p = (position - self.origin) / self.voxel_size;
+
+        let uv = if normal.x != 0. {
+            Vec2::new(p.z, p.y)
+        } else if normal.y != 0. {
+            Vec2::new(p.x, p.z)
+        } else {
+            Vec2::new(p.x, p.y)
+        };
+
+        uv - uv.floor()
+    }
+
+    // the uvs are planar projections, see Svo::uv
+    fn derivatives(&self, normal: Vec3) -> (Vec3, Vec3) {
+        let (u, v) = if normal.x != 0. {
+            (Vec3::Z, Vec3::Y)
+        } else if normal.y != 0. {
+            (Vec3::X, Vec3::Z)
+        } else {
+            (Vec3::X, Vec3::Y)
+        };
+
+        (u * self.voxel_size, v * self.voxel_size)
+    }
+}
+
+impl Hitable for Svo {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::new(
+            self.origin,
+            self.origin + Vec3::splat(self.size as f32 * self.voxel_size),
+        )
+    }
+
+    fn rescale(&mut self, center: Vec3, scale: f32) -> bool {
+        self.origin = (self.origin - center) * scale;
+        self.voxel_size *= scale;
+        for material in self.palette.iter_mut() {
+            material.rescale(center, scale);
+        }
+
+        true
+    }
+
+    fn hit_distance(&self, ray: &Ray, min: f32, max: f32) -> Option<f32> {
+        self.traverse(self.root, &self.bounds(), ray, min, max)
+            .map(|(t, _, _)| t)
+    }
+
+    fn hit(&self, ray: &Ray, min: f32, max: f32) -> Option<Hit> {
+        let (t, normal, material) = self.traverse(self.root, &self.bounds(), ray, min, max)?;
+
+        let material = &self.palette[material as usize];
+        let position = ray.origin + t * ray.direction;
+        let uv = self.uv(position, normal);
+        let (dpdu, dpdv) = self.derivatives(normal);
+
+        Some(
+            Hit {
+                distance: t,
+                position,
+                normal,
+                uv,
+                color: material.albedo.sample(uv, position),
+                reflect: material.reflect,
+                clearcoat: material.clearcoat,
+                emission: material.emission,
+                differentials: Differentials::new(dpdu, dpdv),
+                front_face: true,
+                medium: false,
+                id: 0,
+            }
+            .facing(ray),
+        )
+    }
+}
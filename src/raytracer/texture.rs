@@ -0,0 +1,237 @@
+use std::sync::Arc;
+
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::raytracer::{perlin::Perlin, Color};
+
+pub trait Texture {
+    fn sample(&self, uv: Vec2, position: Vec3) -> Color;
+
+    // the color of a texture that doesn't vary over the surface
+    fn solid(&self) -> Option<Color> {
+        None
+    }
+}
+
+impl Texture for Color {
+    fn sample(&self, _uv: Vec2, _position: Vec3) -> Color {
+        *self
+    }
+
+    fn solid(&self) -> Option<Color> {
+        Some(*self)
+    }
+}
+
+// sampled at the position in the units of the builder, for the models of a
+// normalized scene, see TracerBuilder::normalize_scale
+pub(crate) struct RescaledTexture {
+    texture: Arc<dyn Texture + Send + Sync>,
+    center: Vec3,
+    scale: f32,
+}
+
+impl RescaledTexture {
+    // the models were moved by (p - center) * scale
+    pub fn new(texture: Arc<dyn Texture + Send + Sync>, center: Vec3, scale: f32) -> Self {
+        Self {
+            texture,
+            center,
+            scale,
+        }
+    }
+}
+
+impl Texture for RescaledTexture {
+    fn sample(&self, uv: Vec2, position: Vec3) -> Color {
+        self.texture.sample(uv, position / self.scale + self.center)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WrapMode {
+    REPEAT,
+    CLAMP,
+    MIRROR,
+}
+
+impl WrapMode {
+    fn apply(&self, coord: i64, size: i64) -> i64 {
+        match self {
+            WrapMode::REPEAT => coord.rem_euclid(size),
+            WrapMode::CLAMP => coord.clamp(0, size - 1),
+            WrapMode::MIRROR => {
+                let coord = coord.rem_euclid(2 * size);
+                if coord >= size {
+                    2 * size - 1 - coord
+                } else {
+                    coord
+                }
+            }
+        }
+    }
+}
+
+pub struct ImageTexture {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    // panics on an empty image, there is no texel to wrap to
+    pub fn new(width: u32, height: u32, pixels: Vec<Color>, wrap: WrapMode) -> Self {
+        assert!(width > 0 && height > 0, "empty texture");
+        assert_eq!(pixels.len(), width as usize * height as usize);
+
+        Self {
+            width,
+            height,
+            pixels,
+            wrap,
+        }
+    }
+
+    #[cfg(feature = "image")]
+    pub fn load(path: &str, wrap: WrapMode) -> std::io::Result<Self> {
+        let img = image::open(path)
+            .map_err(std::io::Error::other)?
+            .to_rgba32f();
+
+        let (width, height) = (img.width(), img.height());
+        if width == 0 || height == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: empty texture", path),
+            ));
+        }
+
+        let pixels = img
+            .into_raw()
+            .chunks_exact(4)
+            .map(|c| Color::new(c[0], c[1], c[2], c[3]))
+            .collect();
+
+        log::info!("Texture loaded: {} ({}x{})", path, width, height);
+
+        Ok(Self::new(width, height, pixels, wrap))
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let x = self.wrap.apply(x, self.width as i64);
+        let y = self.wrap.apply(y, self.height as i64);
+
+        self.pixels[(x + y * self.width as i64) as usize]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn sample(&self, uv: Vec2, _position: Vec3) -> Color {
+        // texel centers are at half coordinates
+        let x = uv.x * self.width as f32 - 0.5;
+        let y = uv.y * self.height as f32 - 0.5;
+
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(x0, y0) * (1. - tx) + self.texel(x0 + 1, y0) * tx;
+        let bottom = self.texel(x0, y0 + 1) * (1. - tx) + self.texel(x0 + 1, y0 + 1) * tx;
+
+        top * (1. - ty) + bottom * ty
+    }
+}
+
+pub struct CheckerTexture {
+    even: Arc<dyn Texture + Send + Sync>,
+    odd: Arc<dyn Texture + Send + Sync>,
+    scale: f32,
+}
+
+impl CheckerTexture {
+    pub fn new(
+        even: Arc<dyn Texture + Send + Sync>,
+        odd: Arc<dyn Texture + Send + Sync>,
+        scale: f32,
+    ) -> Self {
+        Self { even, odd, scale }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn sample(&self, uv: Vec2, position: Vec3) -> Color {
+        let cell = (position / self.scale).floor();
+
+        if (cell.x as i64 + cell.y as i64 + cell.z as i64).rem_euclid(2) == 0 {
+            self.even.sample(uv, position)
+        } else {
+            self.odd.sample(uv, position)
+        }
+    }
+}
+
+pub struct NoiseTexture {
+    noise: Perlin,
+    color: Color,
+    scale: f32,
+}
+
+impl NoiseTexture {
+    pub fn new(color: Color, scale: f32) -> Self {
+        Self {
+            noise: Perlin::new(0),
+            color,
+            scale,
+        }
+    }
+
+    // another pattern, the default seed is 0
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.noise = Perlin::new(seed);
+
+        self
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn sample(&self, _uv: Vec2, position: Vec3) -> Color {
+        self.color * (0.5 * (1. + self.noise.noise(position * self.scale)))
+    }
+}
+
+pub struct MarbleTexture {
+    noise: Perlin,
+    color: Color,
+    scale: f32,
+    turbulence: f32,
+}
+
+impl MarbleTexture {
+    const TURBULENCE_DEPTH: u32 = 7;
+
+    pub fn new(color: Color, scale: f32, turbulence: f32) -> Self {
+        Self {
+            noise: Perlin::new(0),
+            color,
+            scale,
+            turbulence,
+        }
+    }
+
+    // another pattern, the default seed is 0
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.noise = Perlin::new(seed);
+
+        self
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn sample(&self, _uv: Vec2, position: Vec3) -> Color {
+        let turbulence = self.noise.turbulence(position, Self::TURBULENCE_DEPTH);
+
+        self.color * (0.5 * (1. + (self.scale * position.z + self.turbulence * turbulence).sin()))
+    }
+}
@@ -0,0 +1,37 @@
+use std::time::Instant;
+
+pub struct Timer {
+    last: Instant,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Self {
+            last: Instant::now(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.last = Instant::now();
+    }
+
+    // seconds since the last call to delta() or reset()
+    pub fn elapsed(&self) -> f32 {
+        self.last.elapsed().as_secs_f32()
+    }
+
+    // seconds since the last call
+    pub fn delta(&mut self) -> f32 {
+        let now = Instant::now();
+        let delta = now.duration_since(self.last).as_secs_f32();
+        self.last = now;
+
+        delta
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,68 @@
+use std::fmt;
+
+// compute time of the chunks since the start of the render, spread over their
+// pixels and summed by tile, see Tracer::tile_timings
+#[derive(Clone, Debug, Default)]
+pub struct TileTimings {
+    // in pixels, the tiles of the last row and column can be smaller
+    pub tile_size: u32,
+    pub tiles_x: u32,
+    pub tiles_y: u32,
+    // seconds, row by row
+    pub times: Vec<f32>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimingSummary {
+    // position of the tile, in tiles
+    pub slowest: (u32, u32),
+    // seconds
+    pub max: f32,
+    pub median: f32,
+    pub p99: f32,
+    pub total: f32,
+}
+
+impl TileTimings {
+    // time of the tile at x, y in tiles
+    pub fn time(&self, x: u32, y: u32) -> f32 {
+        self.times[(x + y * self.tiles_x) as usize]
+    }
+
+    // None before the first chunk
+    pub fn summary(&self) -> Option<TimingSummary> {
+        let total = self.times.iter().sum::<f32>();
+        if total <= 0. {
+            return None;
+        }
+
+        let (slowest, max) = self
+            .times
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+
+        let mut sorted = self.times.clone();
+        sorted.sort_by(f32::total_cmp);
+        let percentile = |p: f32| sorted[((sorted.len() - 1) as f32 * p).round() as usize];
+
+        Some(TimingSummary {
+            slowest: (slowest as u32 % self.tiles_x, slowest as u32 / self.tiles_x),
+            max,
+            median: percentile(0.5),
+            p99: percentile(0.99),
+            total,
+        })
+    }
+}
+
+impl fmt::Display for TimingSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "slowest tile {:?} {:.3}s, median {:.3}s, p99 {:.3}s, total {:.2}s",
+            self.slowest, self.max, self.median, self.p99, self.total
+        )
+    }
+}
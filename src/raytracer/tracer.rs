@@ -1,181 +1,2105 @@
-use glam::Vec3;
-use rayon::prelude::*;
-
-use gobs::{
-    core::{
-        entity::{camera::Camera, light::Light},
-        Color,
-    },
-    render::ImageExtent2D,
-    utils::{rng::RngPool, timer::Timer},
+use std::{
+    collections::HashMap,
+    f32::consts::PI,
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
+use glam::Vec3;
+use rand::{rngs::StdRng, SeedableRng};
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use thread_priority::{set_current_thread_priority, ThreadPriority};
+
 use crate::raytracer::{
-    buffer::{ChunkStrategy, ImageBuffer},
-    hit::Hitable,
-    Ray,
+    aabb::Aabb,
+    accel::AccelKind,
+    aov::Aovs,
+    backend::Backend,
+    background::Background,
+    bake::Lightmap,
+    buffer::{ChunkStrategy, ImageBuffer, ImageOrigin, PreviewMode},
+    checkpoint::Checkpoint,
+    config::TracerConfig,
+    debug::DebugView,
+    decal::Decal,
+    distributed::Coordinator,
+    downsample::DownsampleFilter,
+    export::{self, ExportOptions, RenderMetadata},
+    filter::PixelFilter,
+    group::{Group, Transform},
+    hit::{Hit, Hitable},
+    integrator::Integrator,
+    light::{ev100_to_exposure, LightSampling, PointLight},
+    material::ResponseLut,
+    mesh::Mesh,
+    normalize::Normalization,
+    pacing::{FramePacing, FrameStats},
+    pass::{PassControl, PassHook, PassStats},
+    payload::{Payload, PayloadFactory},
+    portal::Portal,
+    post::{Atrous, DenoiseMode, Outline, Palette, PostEffect, PostStage, Tonemap},
+    primitive::Primitive,
+    probe::{Probe, ProbeSet},
+    queue::{ChunkQueue, Job},
+    region::Region,
+    replay::{RngRecording, RngStream},
+    sampler::{Sampler, SamplerKind},
+    scene::{self, ChunkScratch, Scene},
+    scene_diff::SceneDiff,
+    scene_file::{PrimitiveDesc, SceneFile},
+    snapshot::Snapshot,
+    stats::StatCounters,
+    status::{ProgressHook, RenderControl, RenderProgress, RenderStatus},
+    timer::Timer,
+    timing::TileTimings,
+    watcher::FileWatcher,
+    Camera, Color, Extent, ProjectionMode, Ray,
 };
 
+#[cfg(feature = "image")]
+use std::{fs::File, io::BufWriter};
+
+#[cfg(feature = "image")]
+use image::{codecs::hdr::HdrEncoder, ImageFormat, Rgb, RgbImage, Rgba32FImage};
+
+#[cfg(feature = "gpu")]
+use crate::raytracer::gpu::{Gpu, GpuPass, GpuStatus};
+#[cfg(feature = "video")]
+use crate::raytracer::video::VideoEncoder;
+#[cfg(feature = "stats")]
+use crate::raytracer::RenderStats;
+
+// image of a camera, the workers add the samples of their chunks to it
+type SharedBuffer = Arc<Mutex<ImageBuffer>>;
+// denoised image of each camera, 0 for the main one, with the pass and the version
+// it was made for
+type DenoiseCache = HashMap<usize, ((u32, u64), Arc<Vec<Color>>)>;
+
+// chunk of a camera, with the pass of its samples for a reset region, see
+// ImageBuffer::get_chunk
+// index of the camera, 0 for the main one and the views after it
+type CameraChunk = (usize, SharedBuffer, Arc<Scene>, (Vec<usize>, Option<u32>));
+// with the first sample of the pixels
+type SampledChunk = (usize, SharedBuffer, Arc<Scene>, Vec<usize>, u32);
+
+// additional camera, rendered with the same passes as the main one
+struct View {
+    name: String,
+    scene: Arc<Scene>,
+    image_buffer: SharedBuffer,
+}
+
 pub struct Tracer {
-    image_buffer: ImageBuffer,
-    models: Vec<Box<dyn Hitable + Sync + Send>>,
-    lights: Vec<Light>,
-    camera: Camera,
-    background: fn(&Ray) -> Color,
-    n_rays: u32,
-    n_reflects: u32,
+    scene: Arc<Scene>,
+    image_buffer: SharedBuffer,
+    views: Vec<View>,
+    normalization: Option<Normalization>,
+    status: Arc<RenderStatus>,
     n_threads: u32,
+    pool: ThreadPool,
+    queue: Arc<ChunkQueue>,
+    // chunks queued or being computed
+    in_flight: usize,
+    target_samples: Option<u32>,
+    // seconds
+    time_limit: Option<f32>,
+    timed_out: bool,
+    regions: Vec<Region>,
+    ev100: f32,
+    strategy: ChunkStrategy,
+    accel_kind: AccelKind,
+    tonemap: Tonemap,
+    outline: Option<Outline>,
+    palette: Option<Palette>,
+    post_chain: Vec<PostStage>,
+    origin: ImageOrigin,
+    // the image is rendered at supersample times the size of the output
+    supersample: u32,
+    downsample: DownsampleFilter,
+    file: Option<SceneFile>,
+    watcher: Option<FileWatcher>,
+    denoised: Option<Vec<Color>>,
+    denoiser: Option<(Atrous, DenoiseMode)>,
+    // see DenoiseMode::PROGRESSIVE
+    progressive: Mutex<DenoiseCache>,
+    #[cfg(feature = "oidn")]
+    auto_denoise: bool,
+    preview_mode: PreviewMode,
+    debug_view: DebugView,
+    preview_changed: bool,
+    // see dirty_tiles
+    full_refresh: bool,
+    fast_preview: bool,
+    // the pass before the first one, see Scene::preview_chunk
+    preview_pass: bool,
+    version: u64,
+    pre_pass_hooks: Vec<PassHook>,
+    post_pass_hooks: Vec<PassHook>,
+    pass: u32,
+    pass_started: bool,
+    stopped: bool,
+    paused: bool,
+    // time spent in the current pass before a pause
+    pass_time: f32,
     changed: bool,
+    pass_pixels: u64,
+    progress_hook: Option<ProgressHook>,
+    snapshots: Vec<Snapshot>,
+    frame_budget: Option<f32>,
+    pacing: Option<FramePacing>,
+    // the interval between two updates is used as the frame time
+    adaptive_threads: bool,
+    last_update: Option<Instant>,
+    frame_stats: FrameStats,
+    rng_stream: RngStream,
     timer: Timer,
+    render_time: f32,
+    // restored on the first reset
+    resume: Option<Checkpoint>,
+    coordinator: Option<Coordinator>,
+    #[cfg(feature = "gpu")]
+    gpu: Option<Gpu>,
+    // the pass being rendered by the gpu and its pixels, counted in flight
+    #[cfg(feature = "gpu")]
+    gpu_pass: Option<(GpuPass, Vec<usize>)>,
+    // timelapse of the passes, see record_video
+    #[cfg(feature = "video")]
+    video: Option<VideoEncoder>,
 }
 
 impl Tracer {
-    pub fn extent(&self) -> ImageExtent2D {
-        self.image_buffer.extent
+    const BAKE_CHUNK: usize = 1024;
+    // queued chunks per thread
+    const QUEUE_DEPTH: usize = 4;
+
+    // size of the output images, see TracerBuilder::supersample
+    pub fn extent(&self) -> Extent {
+        Extent::new(
+            self.scene.extent.width / self.supersample,
+            self.scene.extent.height / self.supersample,
+        )
     }
 
-    pub fn framebuffer(&self) -> &[Color] {
-        &self.image_buffer.framebuffer
+    // the buffers and chunks are created again for the new size with the same
+    // settings and the render restarts, the cameras follow the new aspect
+    pub fn resize(&mut self, extent: Extent) {
+        if extent == self.extent() || extent.size() == 0 {
+            return;
+        }
+
+        log::info!("Resize: {}x{}", extent.width, extent.height);
+
+        let s = self.supersample;
+        let render_extent = Extent::new(extent.width * s, extent.height * s);
+        let aspect = extent.width as f32 / extent.height as f32;
+
+        let mut scene = Scene::clone(&self.scene);
+        scene.extent = render_extent;
+        scene.camera = Arc::new(scene.camera.with_aspect(aspect));
+        self.scene = Arc::new(scene);
+
+        // the chunks in flight write to the old buffers and are dropped, the new
+        // ones are reset again with the seeds of the restart
+        let strategy = self.strategy;
+        let new_buffer = || {
+            let mut image_buffer = ImageBuffer::new(render_extent, strategy);
+            image_buffer.reset(0);
+            Arc::new(Mutex::new(image_buffer))
+        };
+
+        self.image_buffer = new_buffer();
+        for view in self.views.iter_mut() {
+            let mut scene = Scene::clone(&self.scene);
+            scene.camera = Arc::new(view.scene.camera.with_aspect(aspect));
+            view.scene = Arc::new(scene);
+            view.image_buffer = new_buffer();
+        }
+
+        self.full_refresh = true;
+        self.invalidate();
     }
 
-    pub fn bytes(&self) -> Vec<u8> {
-        self.image_buffer.bytes()
+    pub fn supersample(&self) -> u32 {
+        self.supersample
     }
 
-    pub fn reset(&mut self) {
-        self.image_buffer.reset();
+    fn downsample(&self, image: &[Color]) -> Vec<Color> {
+        self.downsample
+            .apply(self.scene.extent, self.supersample, image)
     }
 
-    pub fn update(&mut self) -> bool {
-        if self.changed {
-            self.reset();
-            self.timer.reset();
+    // accumulated image with the post effects applied
+    pub fn framebuffer(&self) -> Vec<Color> {
+        let (framebuffer, aovs) = Self::snapshot_buffer(&self.image_buffer);
+
+        match &self.denoised {
+            Some(denoised) => self.output(denoised, &aovs),
+            None => self.output(&self.progressive(0, framebuffer, &aovs), &aovs),
         }
+    }
+
+    // image of a camera added with TracerBuilder::add_camera, with the post effects applied
+    pub fn framebuffer_for(&self, name: &str) -> Option<Vec<Color>> {
+        let idx = self.views.iter().position(|view| view.name == name)?;
+        let (framebuffer, aovs) = Self::snapshot_buffer(&self.views[idx].image_buffer);
+
+        Some(self.output(&self.progressive(idx + 1, framebuffer, &aovs), &aovs))
+    }
 
-        let result = !self.image_buffer.is_complete();
+    // the work on the image is done out of the lock, the workers keep adding samples
+    fn snapshot_buffer(buffer: &SharedBuffer) -> (Arc<Vec<Color>>, Aovs) {
+        let image_buffer = buffer.lock().unwrap();
 
-        if !self.image_buffer.is_complete() {
-            self.update_buffer();
+        (image_buffer.framebuffer.clone(), image_buffer.aovs.clone())
+    }
+
+    // the PROGRESSIVE denoise runs once per pass, the image of the first call of the
+    // pass is kept until the next one
+    fn progressive(
+        &self,
+        camera: usize,
+        framebuffer: Arc<Vec<Color>>,
+        aovs: &Aovs,
+    ) -> Arc<Vec<Color>> {
+        let Some((atrous, DenoiseMode::PROGRESSIVE)) = &self.denoiser else {
+            return framebuffer;
+        };
 
-            if self.image_buffer.is_complete() {
-                log::info!("Rendering time: {:.2}s", self.timer.delta());
+        let key = (self.pass, self.version);
+        if let Some((cached, denoised)) = self.progressive.lock().unwrap().get(&camera) {
+            if *cached == key {
+                return denoised.clone();
             }
         }
 
-        self.changed = false;
+        let denoised = Arc::new(atrous.apply(self.scene.extent, &framebuffer, aovs));
+        self.progressive
+            .lock()
+            .unwrap()
+            .insert(camera, (key, denoised.clone()));
 
-        result
+        denoised
     }
 
-    fn update_buffer(&mut self) {
-        let chunks: Vec<Vec<usize>> = (0..self.n_threads)
-            .filter_map(|_| match self.image_buffer.is_complete() {
-                true => None,
-                false => Some(self.image_buffer.get_chunk()),
-            })
+    pub fn camera_names(&self) -> Vec<&str> {
+        self.views.iter().map(|view| view.name.as_str()).collect()
+    }
+
+    // the radiance is downsampled first, the post effects run at the size of the
+    // output, e.g. the palette and the dither are not averaged
+    fn output(&self, framebuffer: &[Color], aovs: &Aovs) -> Vec<Color> {
+        let mut output = self.downsample(framebuffer);
+
+        if self.supersample > 1 {
+            let aovs = aovs.downsample(self.scene.extent, self.supersample);
+            self.post_process(&mut output, &aovs);
+        } else {
+            self.post_process(&mut output, aovs);
+        }
+
+        output
+    }
+
+    // at the size of the output
+    fn post_process(&self, framebuffer: &mut [Color], aovs: &Aovs) {
+        let extent = self.extent();
+
+        for stage in &self.post_chain {
+            match stage {
+                PostStage::TONEMAP => self.tonemap.apply(framebuffer),
+                PostStage::OUTLINE => {
+                    if let Some(outline) = &self.outline {
+                        outline.apply(extent, framebuffer, aovs);
+                    }
+                }
+                PostStage::DITHER => self.tonemap.dither.apply(extent, framebuffer),
+                PostStage::PALETTE => {
+                    if let Some(palette) = &self.palette {
+                        palette.apply(extent, framebuffer);
+                    }
+                }
+                PostStage::EFFECT(effect) => effect.apply(extent, framebuffer, aovs),
+            }
+        }
+    }
+
+    pub fn palette(&self) -> Option<&Palette> {
+        self.palette.as_ref()
+    }
+
+    pub fn set_palette(&mut self, palette: Option<Palette>) {
+        self.palette = palette;
+        self.preview_changed = true;
+    }
+
+    pub fn post_chain(&self) -> &[PostStage] {
+        &self.post_chain
+    }
+
+    pub fn set_post_chain(&mut self, post_chain: Vec<PostStage>) {
+        self.post_chain = post_chain;
+        self.preview_changed = true;
+    }
+
+    // linear radiance, before any post effect
+    pub fn hdr_framebuffer(&self) -> Vec<Color> {
+        self.downsample(&self.image_buffer.lock().unwrap().framebuffer)
+    }
+
+    // keeps the current accumulation under name, replacing a previous snapshot
+    // with the same name
+    pub fn snapshot(&mut self, name: &str) {
+        let mut framebuffer = self.image_buffer.lock().unwrap().framebuffer.clone();
+        if self.supersample > 1 {
+            framebuffer = Arc::new(self.downsample(&framebuffer));
+        }
+
+        let snapshot = Snapshot::new(
+            name,
+            self.extent(),
+            self.samples_per_pixel(),
+            self.render_time,
+            framebuffer,
+        );
+
+        log::info!(
+            "Snapshot {}: {} spp, {:.2}s",
+            name,
+            snapshot.samples_per_pixel,
+            snapshot.render_time
+        );
+
+        self.snapshots.retain(|s| s.name != name);
+        self.snapshots.push(snapshot);
+    }
+
+    pub fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    pub fn get_snapshot(&self, name: &str) -> Option<&Snapshot> {
+        self.snapshots.iter().find(|s| s.name == name)
+    }
+
+    pub fn remove_snapshot(&mut self, name: &str) -> Option<Snapshot> {
+        let idx = self.snapshots.iter().position(|s| s.name == name)?;
+
+        Some(self.snapshots.remove(idx))
+    }
+
+    // snapshot with the current tone mapping, for side by side display
+    pub fn snapshot_framebuffer(&self, name: &str) -> Option<Vec<Color>> {
+        let mut framebuffer = self.get_snapshot(name)?.framebuffer().to_vec();
+        self.tonemap.apply(&mut framebuffer);
+
+        Some(framebuffer)
+    }
+
+    // alpha is dropped, not all formats support it
+    fn rgb_bytes(&self) -> Vec<u8> {
+        self.bytes()
+            .chunks_exact(4)
+            .flat_map(|c| [c[0], c[1], c[2]])
+            .collect()
+    }
+
+    // tonemapped 8 bits image, the format is given by the extension, only ppm without
+    // the image feature
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let extent = self.extent();
+        let rgb = self.rgb_bytes();
+
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+
+        match extension.as_deref() {
+            Some("ppm") => export::write_ppm(path, extent, &rgb)?,
+            #[cfg(feature = "image")]
+            _ => {
+                let img = RgbImage::from_raw(extent.width, extent.height, rgb).unwrap();
+                img.save(path).map_err(io::Error::other)?;
+            }
+            #[cfg(not(feature = "image"))]
+            _ => return Err(export::unsupported(path)),
+        }
+
+        log::info!("Image saved: {}", path);
+
+        Ok(())
+    }
+
+    // tonemapped png with the next free name of the options, returns its path
+    pub fn export(&self, options: &ExportOptions) -> io::Result<PathBuf> {
+        let path = export::write_png(options, self.extent(), &self.rgb_bytes(), &self.metadata())?;
+
+        log::info!("Image exported: {}", path.display());
+
+        Ok(path)
+    }
+
+    pub fn metadata(&self) -> RenderMetadata {
+        RenderMetadata {
+            samples_per_pixel: self.samples_per_pixel(),
+            render_time: self.progress().elapsed,
+            seed: self.scene.seed,
+            scene_hash: self.scene_hash(),
+        }
+    }
+
+    // settings, camera, lights and models with their materials, the same for two
+    // renders of the same scene
+    pub fn scene_hash(&self) -> u64 {
+        let mut bytes = format!("{:?} {:?}", self.config(), self.camera()).into_bytes();
+
+        // the description of the file names the textures and meshes it loads
+        if let Some(file) = &self.file {
+            match toml::to_string(file) {
+                Ok(description) => bytes.extend(description.bytes()),
+                Err(e) => log::warn!("Cannot hash the scene file: {}", e),
+            }
+        }
+
+        for light in self.scene.lights.iter() {
+            bytes.extend(light.fingerprint());
+        }
+
+        for model in self.scene.models.iter() {
+            bytes.extend(model.fingerprint());
+        }
+
+        export::hash(bytes)
+    }
+
+    #[cfg(feature = "image")]
+    pub fn save_exr(&self, path: &str) -> io::Result<()> {
+        let extent = self.extent();
+
+        let data = self
+            .origin
+            .orient(extent, &self.hdr_framebuffer())
+            .iter()
+            .flat_map(|c| [c.r, c.g, c.b, c.a])
             .collect();
 
-        let results: Vec<Vec<(usize, Color)>> = if self.n_threads > 1 {
-            chunks
+        let img = Rgba32FImage::from_raw(extent.width, extent.height, data).unwrap();
+        img.save_with_format(path, ImageFormat::OpenExr)
+            .map_err(io::Error::other)?;
+
+        log::info!("EXR saved: {}", path);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "image")]
+    pub fn save_hdr(&self, path: &str) -> io::Result<()> {
+        let extent = self.extent();
+
+        let pixels = self
+            .origin
+            .orient(extent, &self.hdr_framebuffer())
+            .iter()
+            .map(|c| Rgb([c.r, c.g, c.b]))
+            .collect::<Vec<_>>();
+
+        let writer = BufWriter::new(File::create(path)?);
+        HdrEncoder::new(writer)
+            .encode(&pixels, extent.width as usize, extent.height as usize)
+            .map_err(io::Error::other)?;
+
+        log::info!("HDR saved: {}", path);
+
+        Ok(())
+    }
+
+    // transform from the units of the builder to the units of the render, with
+    // TracerBuilder::normalize_scale
+    pub fn normalization(&self) -> Option<Normalization> {
+        self.normalization
+    }
+
+    fn to_scene(&self, p: Vec3) -> Vec3 {
+        match &self.normalization {
+            Some(normalization) => normalization.point(p),
+            None => p,
+        }
+    }
+
+    fn to_builder(&self, p: Vec3) -> Vec3 {
+        match &self.normalization {
+            Some(normalization) => normalization.world_point(p),
+            None => p,
+        }
+    }
+
+    // queries against the scene, independent of the progressive render
+    pub fn trace_rays(&self, rays: &[Ray]) -> Vec<Color> {
+        match &self.normalization {
+            Some(normalization) => self.scene.trace_rays(
+                &rays
+                    .iter()
+                    .map(|r| normalization.ray(r))
+                    .collect::<Vec<_>>(),
+            ),
+            None => self.scene.trace_rays(rays),
+        }
+    }
+
+    pub fn hit_rays(&self, rays: &[Ray]) -> Vec<Option<Hit>> {
+        match &self.normalization {
+            Some(normalization) => self
+                .scene
+                .hit_rays(
+                    &rays
+                        .iter()
+                        .map(|r| normalization.ray(r))
+                        .collect::<Vec<_>>(),
+                )
+                .into_iter()
+                .map(|hit| hit.map(|hit| normalization.world_hit(&hit)))
+                .collect(),
+            None => self.scene.hit_rays(rays),
+        }
+    }
+
+    pub fn is_visible(&self, from: Vec3, to: Vec3) -> bool {
+        self.scene
+            .is_visible(self.to_scene(from), self.to_scene(to))
+    }
+
+    // irradiance over the texture coordinates of the mesh, for use as a lightmap
+    pub fn bake(&self, mesh: &Mesh, width: u32, height: u32, samples: u32) -> Lightmap {
+        let texels = mesh.texels(width, height);
+
+        log::info!("Baking {} texels ({} samples)", texels.len(), samples);
+
+        let scene = &self.scene;
+        let seed = scene.seed;
+
+        // on the threads of the tracer, not the global pool of the host
+        let baked = self.pool.install(|| {
+            texels
+                .par_chunks(Self::BAKE_CHUNK)
+                .map(|chunk| {
+                    let mut sampler = Sampler::new(scene.light_sampler, chunk.len(), width, seed);
+
+                    chunk
+                        .iter()
+                        .map(|&(idx, position, normal)| {
+                            let mut irradiance = Color::BLACK;
+                            for sample in 0..samples {
+                                sampler.start(idx, sample);
+                                irradiance =
+                                    irradiance + scene.irradiance(position, normal, &mut sampler);
+                            }
+
+                            (idx, irradiance / samples.max(1) as f32)
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let size = (width * height) as usize;
+        let mut lightmap = Lightmap::new(width, height, vec![Color::BLACK; size]);
+        let mut covered = vec![false; size];
+
+        for (idx, irradiance) in baked.into_iter().flatten() {
+            lightmap.texels[idx] = irradiance;
+            covered[idx] = true;
+        }
+
+        lightmap.dilate(&covered, 2);
+
+        lightmap
+    }
+
+    // sh projection of the radiance seen from each position, sampled over the sphere
+    pub fn bake_probes(&self, positions: &[Vec3], samples: u32) -> ProbeSet {
+        log::info!("Baking {} probes ({} samples)", positions.len(), samples);
+
+        let scene = &self.scene;
+        let weight = 4. * PI / samples.max(1) as f32;
+
+        // probes keep the position given, the rays start from the render units
+        let points = positions
+            .iter()
+            .map(|&p| (p, self.to_scene(p)))
+            .collect::<Vec<_>>();
+
+        let probes = self.pool.install(|| {
+            points
                 .par_iter()
-                .map(|chunk| self.compute_chunk(&chunk))
+                .enumerate()
+                .map(|(idx, &(position, origin))| {
+                    let mut sampler = Sampler::new(scene.sampler, 1, 1, scene.seed);
+                    let mut probe = Probe::new(position);
+
+                    for sample in 0..samples {
+                        sampler.start(idx, sample);
+
+                        // uniform direction on the sphere
+                        let z = 1. - 2. * sampler.next();
+                        let r = (1. - z * z).max(0.).sqrt();
+                        let phi = 2. * PI * sampler.next();
+                        let direction = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+
+                        let ray = Ray::new(origin, direction);
+                        let radiance = scene.radiance(&ray, &mut sampler);
+                        probe.add(direction, radiance, weight);
+                    }
+
+                    probe
+                })
                 .collect()
-        } else {
-            chunks
+        });
+
+        ProbeSet { probes }
+    }
+
+    pub fn preview(&self) -> Vec<Color> {
+        if self.debug_view != DebugView::NONE {
+            let debug_view = self
+                .image_buffer
+                .lock()
+                .unwrap()
+                .debug_view(self.debug_view);
+
+            return self.downsample(&debug_view);
+        }
+
+        match self.preview_mode {
+            PreviewMode::COLOR => self.framebuffer(),
+            mode => self.downsample(&self.image_buffer.lock().unwrap().preview(mode)),
+        }
+    }
+
+    // tiles of the preview changed since the last call as x, y, width, height, to
+    // update a texture in place after update() returns true, all of them when the
+    // preview settings change or when a pixel also changes its neighbours
+    pub fn dirty_tiles(&mut self) -> Vec<(u32, u32, u32, u32)> {
+        let mut image_buffer = self.image_buffer.lock().unwrap();
+
+        let local = self.preview_mode == PreviewMode::COLOR
+            && self.debug_view == DebugView::NONE
+            && self.denoised.is_none()
+            && self.denoiser.is_none()
+            && self.outline.is_none()
+            && !self
+                .post_chain
                 .iter()
-                .map(|chunk| self.compute_chunk(&chunk))
-                .collect()
-        };
+                .any(|stage| matches!(stage, PostStage::EFFECT(_)));
 
-        for result in results {
-            for (idx, c) in result {
-                self.image_buffer.update_pixel(idx, c);
-            }
+        // the mitchell filter also reaches the neighbouring tiles
+        let local = local && (self.supersample == 1 || self.downsample == DownsampleFilter::BOX);
+
+        if std::mem::take(&mut self.full_refresh) || !local {
+            image_buffer.mark_all_dirty();
         }
+
+        let s = self.supersample;
+        image_buffer
+            .take_dirty_tiles()
+            .into_iter()
+            .map(|(x, y, width, height)| {
+                (
+                    x / s,
+                    y / s,
+                    (x + width).div_ceil(s).min(self.scene.extent.width / s) - x / s,
+                    (y + height).div_ceil(s).min(self.scene.extent.height / s) - y / s,
+                )
+            })
+            .collect()
     }
 
-    pub fn compute_chunk(&self, chunk: &[usize]) -> Vec<(usize, Color)> {
-        let mut result = Vec::new();
+    // can be polled from any thread while rendering
+    pub fn status(&self) -> Arc<RenderStatus> {
+        self.status.clone()
+    }
+
+    // pause, resume or cancel from any thread
+    pub fn control(&self) -> Arc<RenderControl> {
+        self.scene.control.clone()
+    }
+
+    // update() does nothing until resume()
+    pub fn pause(&self) {
+        self.scene.control.pause();
+    }
+
+    pub fn resume(&self) {
+        self.scene.control.resume();
+    }
+
+    // stops the render without waiting for the chunks in flight, a new render
+    // starts on the next change of the scene
+    pub fn cancel(&self) {
+        self.scene.control.cancel();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.scene.control.is_paused()
+    }
+
+    pub fn preview_mode(&self) -> PreviewMode {
+        self.preview_mode
+    }
+
+    pub fn debug_view(&self) -> DebugView {
+        self.debug_view
+    }
 
-        let mut rng = RngPool::new(chunk.len());
+    // shown by preview() instead of the image, the render restarts when the traversal
+    // counts are enabled or disabled
+    pub fn set_debug_view(&mut self, view: DebugView) {
+        log::info!("Debug view: {:?}", view);
+        self.debug_view = view;
+        self.preview_changed = true;
 
-        for idx in chunk {
-            let c = self.compute_pixel(*idx, &mut rng);
+        let debug_counts = matches!(
+            view,
+            DebugView::NODES | DebugView::TESTS | DebugView::BOUNCES
+        );
+        if debug_counts != self.scene.debug_counts {
+            let mut scene = Scene::clone(&self.scene);
+            scene.debug_counts = debug_counts;
+            self.scene = Arc::new(scene);
+            self.update_views();
 
-            result.push((*idx, c));
+            self.invalidate();
         }
+    }
 
-        result
+    pub fn set_preview_mode(&mut self, mode: PreviewMode) {
+        log::info!("Preview mode: {:?}", mode);
+        self.preview_mode = mode;
+        self.preview_changed = true;
+    }
+
+    pub fn cycle_preview_mode(&mut self) {
+        self.set_preview_mode(self.preview_mode.next());
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        let framebuffer = self.origin.orient(self.extent(), &self.framebuffer());
+
+        ImageBuffer::to_bytes(&framebuffer)
+    }
+
+    pub fn config(&self) -> TracerConfig {
+        TracerConfig {
+            width: self.extent().width,
+            height: self.extent().height,
+            rays: self.scene.n_rays,
+            reflects: self.scene.n_reflects,
+            threads: self.n_threads,
+            target_samples: self.target_samples,
+            time_limit: self.time_limit,
+            alpha_cutoff: self.scene.alpha_cutoff,
+            epsilon: self.scene.epsilon,
+            shadow_bias: self.scene.shadow_bias / self.normalization.map_or(1., |n| n.scale),
+            min_throughput: self.scene.min_throughput,
+            exposure: self.ev100,
+            ambient: self.scene.ambient,
+            strategy: self.strategy,
+            accel: self.accel_kind,
+            integrator: self.scene.integrator,
+            sampler: self.scene.sampler,
+            light_sampler: self.scene.light_sampler,
+            light_sampling: self.scene.light_sampling,
+            seed: self.scene.seed,
+            tonemap: self.tonemap,
+            palette: self.palette.clone(),
+            origin: self.origin,
+            sample_clamp: self.scene.sample_clamp,
+            supersample: self.supersample,
+            downsample: self.downsample,
+            filter: self.scene.filter,
+        }
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.scene.camera
+    }
+
+    // the samples already taken are kept, the render goes on from the last pass
+    // when the target is raised after the end
+    pub fn set_target_samples(&mut self, target_samples: Option<u32>) {
+        let was_complete = self.is_complete();
+        self.target_samples = target_samples;
+
+        if was_complete && !self.is_complete() && !self.timed_out {
+            self.next_pass();
+        }
+    }
+
+    pub fn set_reflects(&mut self, reflects: u32) {
+        if reflects == self.scene.n_reflects {
+            return;
+        }
+
+        let mut scene = Scene::clone(&self.scene);
+        scene.n_reflects = reflects;
+        self.scene = Arc::new(scene);
+        self.update_views();
+
+        self.invalidate();
+    }
+
+    // up to the threads of the builder, the pool is not resized
+    pub fn set_threads(&mut self, threads: u32) {
+        self.n_threads = threads.clamp(1, self.pool.current_num_threads() as u32);
+    }
+
+    // the render restarts with the new chunks
+    pub fn set_strategy(&mut self, strategy: ChunkStrategy) {
+        if strategy == self.strategy {
+            return;
+        }
+
+        self.strategy = strategy;
+        self.image_buffer.lock().unwrap().set_strategy(strategy);
+        for view in &self.views {
+            view.image_buffer.lock().unwrap().set_strategy(strategy);
+        }
+
+        self.invalidate();
+    }
+
+    pub fn tonemap(&self) -> Tonemap {
+        self.tonemap
+    }
+
+    // applied to the accumulated image, the samples are kept
+    pub fn set_tonemap(&mut self, tonemap: Tonemap) {
+        self.tonemap = tonemap;
+        self.preview_changed = true;
+    }
+
+    // exposure value at ISO 100 the image is shown with, the one of the builder
+    // minus the compensation of the tonemap
+    pub fn exposure(&self) -> f32 {
+        self.ev100 - self.tonemap.compensation
+    }
+
+    // the samples keep the ev100 of the builder, the difference goes to
+    // Tonemap::compensation and the image is only tonemapped again
+    pub fn set_exposure(&mut self, ev100: f32) {
+        self.set_tonemap(self.tonemap.compensation(self.ev100 - ev100));
+    }
+
+    // temperature in kelvin of the light shown as white, see Tonemap::white_balance
+    pub fn set_white_balance(&mut self, kelvin: f32) {
+        self.set_tonemap(self.tonemap.white_balance(kelvin));
+    }
+
+    // replaces the models, lights, portals, camera and background with the ones of the file,
+    // render settings are kept
+    pub fn reload(&mut self, file: &SceneFile) -> io::Result<()> {
+        let mut models = file.models()?;
+        let extent = self.extent();
+
+        let mut camera = file
+            .camera
+            .camera(extent.width as f32 / extent.height as f32);
+        let mut lights = file.lights();
+        let mut portals = file.portals();
+
+        // the file is in the units of the first load
+        if let Some(normalization) = &self.normalization {
+            for model in models.iter_mut() {
+                if !model.rescale(normalization.center, normalization.scale) {
+                    log::warn!("Cannot normalize {}", model.name());
+                }
+            }
+            lights = lights.iter().map(|l| normalization.light(l)).collect();
+            portals = portals.iter().map(|p| normalization.portal(p)).collect();
+            camera = normalization.camera(&camera);
+        }
+
+        let models = models.into_iter().map(Arc::new).collect::<Vec<_>>();
+
+        let mut scene = Scene::clone(&self.scene);
+        scene.accel = Arc::new(AccelKind::build(self.accel_kind, &models));
+        scene.emitters = Arc::new(scene::emitters(&models));
+        scene.media = Arc::new(scene::media(&models));
+        scene.responses = Arc::new(self.responses(&models));
+        scene.soft_casters = models.iter().any(|m| m.shadow_softness() != 1.);
+        scene.models = Arc::new(models);
+        scene.lights = Arc::new(lights);
+        scene.portals = Arc::new(portals);
+        scene.camera = Arc::new(camera);
+        if let Some(background) = file.background {
+            scene.background = background.shader();
+        }
+        self.scene = Arc::new(scene);
+        self.update_views();
+        self.file = Some(file.clone());
+
+        self.invalidate();
+
+        Ok(())
     }
 
-    fn compute_pixel(&self, idx: usize, rng: &mut RngPool) -> Color {
-        let i = idx / self.image_buffer.extent.width as usize;
-        let j = idx % self.image_buffer.extent.width as usize;
+    fn check_reload(&mut self) {
+        let Some(watcher) = &self.watcher else {
+            return;
+        };
+
+        if !watcher.changed() {
+            return;
+        }
+
+        let path = watcher.path().to_string_lossy().to_string();
 
-        let mut c = Color::BLACK;
-        for _ in 0..self.n_rays {
-            // -2..2
-            let x = -2. + 4. * ((j as f32 + rng.next()) / self.image_buffer.extent.width as f32);
-            // -1..1
-            let y = 1. - 2. * ((i as f32 + rng.next()) / self.image_buffer.extent.height as f32);
+        // the previous scene is kept if the file is invalid, e.g. while being edited
+        match SceneFile::load(&path).and_then(|file| self.reload(&file)) {
+            Ok(()) => log::info!("Scene reloaded: {}", path),
+            Err(e) => log::warn!("Cannot reload {}: {}", path, e),
+        }
+    }
+
+    // models, lights and materials added, removed or changed between two scene files
+    pub fn diff_scenes(a: &str, b: &str) -> io::Result<SceneDiff> {
+        Ok(SceneDiff::new(&SceneFile::load(a)?, &SceneFile::load(b)?))
+    }
+
+    // writes back the description the scene was loaded from, with the current settings,
+    // camera, lights and models in the units of the builder
+    pub fn save_scene(&self, path: &str) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Err(io::Error::other("scene not loaded from a file"));
+        };
+
+        let mut file = file.clone();
+        file.render = self.config();
+
+        let camera = match &self.normalization {
+            Some(normalization) => normalization.inverse().camera(self.camera()),
+            None => *self.camera(),
+        };
+        file.camera.position = camera.position.to_array();
+        file.camera.yaw = camera.yaw.to_degrees();
+        file.camera.pitch = camera.pitch.to_degrees();
+        if let ProjectionMode::PERSPECTIVE {
+            fovy, near, far, ..
+        } = camera.mode
+        {
+            file.camera.fov = fovy.to_degrees();
+            file.camera.near = near;
+            file.camera.far = far;
+        }
+
+        for (desc, light) in file.lights.iter_mut().zip(self.scene.lights.iter()) {
+            desc.position = self.to_builder(light.position).to_array();
+        }
+
+        // the models moved at runtime are in a group of their own
+        for desc in file.primitives.iter_mut() {
+            let Some((_, Primitive::GROUP(group))) = self.find_model(desc.name()) else {
+                continue;
+            };
+            let transform = group.get_transform();
+
+            match desc {
+                PrimitiveDesc::SPHERE { center, radius, .. } => {
+                    let moved = transform.point(self.to_scene(Vec3::from_array(*center)));
+                    *center = self.to_builder(moved).to_array();
+                    *radius *= transform.scale;
+                }
+                PrimitiveDesc::VOLUME { min, max, .. } => {
+                    let bounds = group.bounds();
+                    *min = self.to_builder(bounds.min).to_array();
+                    *max = self.to_builder(bounds.max).to_array();
+                }
+            }
+        }
+
+        file.save(path)
+    }
+
+    // in the units of the builder
+    pub fn set_camera(&mut self, camera: Camera) {
+        let camera = match &self.normalization {
+            Some(normalization) => normalization.camera(&camera),
+            None => camera,
+        };
+
+        let mut scene = Scene::clone(&self.scene);
+        scene.camera = Arc::new(camera);
+        self.scene = Arc::new(scene);
+        self.update_views();
+
+        self.invalidate();
+    }
+
+    // in the units of the builder, false if there is no light at this index
+    pub fn set_light_position(&mut self, index: usize, position: Vec3) -> bool {
+        let position = match &self.normalization {
+            Some(normalization) => normalization.point(position),
+            None => position,
+        };
+
+        let mut lights = self.scene.lights.to_vec();
+        let Some(light) = lights.get_mut(index) else {
+            return false;
+        };
+        light.position = position;
+
+        let mut scene = Scene::clone(&self.scene);
+        scene.lights = Arc::new(lights);
+        self.scene = Arc::new(scene);
+        self.update_views();
+
+        self.invalidate();
+
+        true
+    }
+
+    // moves a group as a unit, in the units of the builder, false if there is
+    // no group with this name
+    pub fn set_group_transform(&mut self, name: &str, transform: Transform) -> bool {
+        let transform = self.scene_transform(transform);
+
+        let Some((idx, Primitive::GROUP(group))) = self.find_model(name) else {
+            return false;
+        };
+        let group = group.clone().transform(transform);
+
+        self.replace_model(idx, group.into());
+        self.invalidate();
+
+        true
+    }
+
+    // moves any model, relative to its position in the builder for the models out
+    // of a group, only the pixels covered by the model before and after the move
+    // are rendered again, false if there is no model with this name
+    pub fn set_model_transform(&mut self, name: &str, transform: Transform) -> bool {
+        let transform = self.scene_transform(transform);
+
+        self.move_model(name, |_| transform)
+    }
+
+    // moves the model by delta, in the units of the builder
+    pub fn translate_model(&mut self, name: &str, delta: Vec3) -> bool {
+        let delta = match &self.normalization {
+            Some(normalization) => delta * normalization.scale,
+            None => delta,
+        };
+
+        self.move_model(name, |transform| Transform {
+            translation: transform.translation + delta,
+            ..transform
+        })
+    }
+
+    // transform in the units of the builder to the units of the scene
+    fn scene_transform(&self, transform: Transform) -> Transform {
+        match &self.normalization {
+            Some(normalization) => Transform {
+                translation: normalization.point(transform.translation),
+                scale: transform.scale * normalization.scale,
+                ..transform
+            },
+            None => transform,
+        }
+    }
+
+    fn find_model(&self, name: &str) -> Option<(usize, &Primitive)> {
+        self.scene
+            .models
+            .iter()
+            .enumerate()
+            .find(|(_, model)| model.name() == name)
+            .map(|(idx, model)| (idx, model.as_ref()))
+    }
+
+    // the models out of a group are moved in a group of their own
+    fn move_model(&mut self, name: &str, f: impl FnOnce(Transform) -> Transform) -> bool {
+        let Some((idx, model)) = self.find_model(name) else {
+            return false;
+        };
+        let group = match model {
+            Primitive::GROUP(group) => group.clone(),
+            _ => Group::shared(name, vec![self.scene.models[idx].clone()]),
+        };
+        let before = group.bounds();
+        let transform = f(group.get_transform());
+        let group = group.transform(transform);
+        let after = group.bounds();
+
+        self.replace_model(idx, group.into());
+
+        // the shadows and reflections of the model outside of its rectangle are
+        // refreshed with the next full invalidation
+        match self.scene.screen_rect(&before.union(&after)) {
+            Some(rect) => self.invalidate_pixels(rect),
+            _ => self.invalidate(),
+        }
+
+        true
+    }
+
+    // moves the groups along their motion tracks
+    pub fn set_time(&mut self, time: f32) {
+        let mut moved = false;
+        let models = self
+            .scene
+            .models
+            .iter()
+            .map(|model| match model.as_ref() {
+                Primitive::GROUP(group) => match group.at_time(time) {
+                    Some(group) => {
+                        moved = true;
+                        Arc::new(group.into())
+                    }
+                    None => model.clone(),
+                },
+                _ => model.clone(),
+            })
+            .collect();
+
+        if moved {
+            self.set_models(models);
+            self.invalidate();
+        }
+    }
+
+    fn replace_model(&mut self, idx: usize, model: Primitive) {
+        let mut models = self.scene.models.to_vec();
+        models[idx] = Arc::new(model);
+
+        self.set_models(models);
+    }
+
+    // the LUTs are only read by the fast preview
+    fn responses(&self, models: &[Arc<Primitive>]) -> Vec<Option<ResponseLut>> {
+        if self.fast_preview {
+            scene::responses(models)
+        } else {
+            Vec::new()
+        }
+    }
+
+    // copy on write, the chunks in flight keep the models they started with
+    fn set_models(&mut self, models: Vec<Arc<Primitive>>) {
+        let mut scene = Scene::clone(&self.scene);
+        scene.accel = Arc::new(AccelKind::build(self.accel_kind, &models));
+        scene.emitters = Arc::new(scene::emitters(&models));
+        scene.media = Arc::new(scene::media(&models));
+        scene.responses = Arc::new(self.responses(&models));
+        scene.models = Arc::new(models);
+        self.scene = Arc::new(scene);
+        self.update_views();
+    }
+
+    // the views follow the changes of the main scene, with their own camera
+    fn update_views(&mut self) {
+        for view in self.views.iter_mut() {
+            let mut scene = Scene::clone(&self.scene);
+            scene.camera = view.scene.camera.clone();
+            view.scene = Arc::new(scene);
+        }
+    }
+
+    // name of the model seen through the pixel, a group for the models of a group
+    pub fn pick(&self, x: u32, y: u32) -> Option<&str> {
+        let extent = self.extent();
+        if x >= extent.width || y >= extent.height {
+            return None;
+        }
+
+        // center of the output pixel
+        let s = self.supersample;
+        let hit = self.scene.pick(x * s + s / 2, y * s + s / 2)?;
+
+        Some(self.scene.models[hit.id as usize].name())
+    }
+
+    // renders the chunks closest to the pixel first, until clear_focus
+    pub fn set_focus(&mut self, x: u32, y: u32) {
+        let s = self.supersample;
+        self.image_buffer
+            .lock()
+            .unwrap()
+            .set_focus(Some((x * s, y * s)));
+    }
+
+    pub fn clear_focus(&mut self) {
+        self.image_buffer.lock().unwrap().set_focus(None);
+    }
+
+    // chunks dispatched before this call are discarded when they come back
+    pub fn invalidate(&mut self) {
+        self.next_version();
+        self.changed = true;
+    }
+
+    // renders the pixels of the rectangle again, e.g. after editing a model that only
+    // covers them, the rest of the image is kept: the chunks in flight are dropped for
+    // these pixels only and the pixels catch up with the samples of the other ones
+    pub fn invalidate_region(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        let s = self.supersample;
+        self.invalidate_pixels((x * s, y * s, width * s, height * s));
+    }
+
+    // rectangle of the render, at the supersampled size
+    fn invalidate_pixels(&mut self, rect: (u32, u32, u32, u32)) {
+        // the rectangle is in the screen space of the main camera, the views are
+        // rendered again in full
+        if !self.views.is_empty() {
+            self.invalidate();
+        }
+
+        // the whole image is rendered again with the next update
+        if self.changed {
+            return;
+        }
+
+        // the pixels take the samples of the passes already done and of the current one
+        let passes = if self.is_complete() {
+            self.pass
+        } else {
+            self.pass + 1
+        };
+
+        // the queue keeps accepting the older chunks for the other pixels
+        self.version += 1;
+        self.image_buffer
+            .lock()
+            .unwrap()
+            .reset_region(rect, self.version, passes);
+
+        self.denoised = None;
+        self.preview_changed = true;
+    }
+
+    fn next_version(&mut self) {
+        self.version += 1;
+        self.queue.set_version(self.version);
+    }
+
+    pub fn reset(&mut self) {
+        let seed = self.rng_stream.pass_seed();
+        self.image_buffer.lock().unwrap().reset(seed);
+        for view in &self.views {
+            let seed = self.rng_stream.pass_seed();
+            view.image_buffer.lock().unwrap().reset(seed);
+        }
+        self.denoised = None;
+    }
+
+    // the denoised image is shown until the next reset
+    #[cfg(feature = "oidn")]
+    pub fn denoise(&mut self) {
+        let image_buffer = self.image_buffer.lock().unwrap();
+
+        let denoised = crate::raytracer::denoise::oidn(
+            self.scene.extent,
+            &image_buffer.framebuffer,
+            &image_buffer.aovs,
+        );
+        drop(image_buffer);
+
+        self.denoised = Some(denoised);
+        self.preview_changed = true;
+    }
+
+    // seeds recorded or replayed since the start of the render
+    pub fn rng_recording(&self) -> Option<&RngRecording> {
+        self.rng_stream.recording()
+    }
+
+    pub fn save_rng_recording(&self, path: &str) -> io::Result<()> {
+        match self.rng_stream.recording() {
+            Some(recording) => recording.save(path),
+            None => Err(io::Error::other("RNG recording is not enabled")),
+        }
+    }
+
+    // the chunks are also rendered by the processes connected with
+    // distributed::Worker, returns the address to connect to, e.g. with port 0
+    pub fn serve(&mut self, address: impl ToSocketAddrs) -> io::Result<SocketAddr> {
+        let coordinator = Coordinator::bind(address, Arc::downgrade(&self.queue))?;
+        let address = coordinator.address();
+        self.coordinator = Some(coordinator);
+
+        Ok(address)
+    }
+
+    // CPU when the gpu backend is not available or doesn't support the scene
+    pub fn backend(&self) -> Backend {
+        #[cfg(feature = "gpu")]
+        if self.gpu.as_ref().is_some_and(|gpu| gpu.is_ready()) {
+            return Backend::GPU;
+        }
+
+        Backend::CPU
+    }
+
+    pub fn coordinator(&self) -> Option<&Coordinator> {
+        self.coordinator.as_ref()
+    }
+
+    // accumulated samples of the main camera and pixels left in the current pass,
+    // the render goes on from there with TracerBuilder::resume_from, the views
+    // start over
+    pub fn save_checkpoint(&self, path: &str) -> io::Result<()> {
+        let checkpoint = Checkpoint {
+            pass: self.pass,
+            n_rays: self.scene.n_rays,
+            render_time: self.progress().elapsed,
+            ..self.image_buffer.lock().unwrap().checkpoint()
+        };
+
+        log::info!(
+            "Checkpoint {}: pass {}, {:.2}s",
+            path,
+            checkpoint.pass,
+            checkpoint.render_time
+        );
+
+        checkpoint.save(path)
+    }
+
+    fn restore(&mut self, checkpoint: Checkpoint) {
+        if checkpoint.extent != self.scene.extent || checkpoint.n_rays != self.scene.n_rays {
+            log::warn!("Checkpoint ignored: rendered with other settings");
+            return;
+        }
+
+        self.image_buffer.lock().unwrap().restore(&checkpoint);
+        self.pass = checkpoint.pass;
+        self.render_time = checkpoint.render_time;
+        self.preview_pass = false;
+
+        log::info!(
+            "Resumed at pass {}, {:.2}s",
+            checkpoint.pass,
+            checkpoint.render_time
+        );
+    }
+
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.pass * self.scene.n_rays
+    }
+
+    pub fn progress(&self) -> RenderProgress {
+        // all the cameras are rendered in each pass
+        let size = self.scene.extent.size() as u64 * (1 + self.views.len() as u64);
+        let n_rays = self.scene.n_rays.max(1);
+
+        let pixels_done = self.pass as u64 * size + self.pass_pixels;
+        let total = self
+            .target_samples
+            .map(|target| target.div_ceil(n_rays) as u64 * size);
+
+        let elapsed = if self.stopped || self.paused || self.is_complete() {
+            self.render_time + self.pass_time
+        } else {
+            self.render_time + self.pass_time + self.timer.elapsed()
+        };
+
+        let eta = match total {
+            Some(total) if pixels_done > 0 => {
+                let remaining = total.saturating_sub(pixels_done);
+                Some(elapsed * remaining as f32 / pixels_done as f32)
+            }
+            _ => None,
+        };
+
+        RenderProgress {
+            pixels_done,
+            total,
+            samples_per_pixel: self.samples_per_pixel(),
+            elapsed,
+            eta,
+        }
+    }
+
+    // rays cast since the start of the render, with the rays of the views
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> RenderStats {
+        let names = self
+            .scene
+            .models
+            .iter()
+            .map(|model| model.name())
+            .collect::<Vec<_>>();
+
+        self.scene.stats.snapshot(&names, self.progress().elapsed)
+    }
+
+    // compute time of the chunks of the main camera by tile, in the pixels of the
+    // render before the downsample, e.g. to compare chunk strategies and sizes
+    pub fn tile_timings(&self) -> TileTimings {
+        self.image_buffer.lock().unwrap().tile_timings()
+    }
+
+    // also true once the time limit is reached
+    pub fn is_complete(&self) -> bool {
+        if self.timed_out {
+            return true;
+        }
+
+        match self.target_samples {
+            Some(target) => {
+                let target = self
+                    .regions
+                    .iter()
+                    .map(|region| region.target_samples)
+                    .fold(target, u32::max);
+
+                self.samples_per_pixel() >= target
+            }
+            None => false,
+        }
+    }
+
+    // complete and with the invalidated regions caught up, nothing is left to
+    // render until the scene changes
+    pub fn is_idle(&self) -> bool {
+        !self.changed && self.is_complete() && self.in_flight == 0 && self.is_pass_complete()
+    }
+
+    // rendered after the whole image reached the target samples, until each
+    // region reaches its own target
+    pub fn add_region(&mut self, region: Region) {
+        log::info!("Region {}: {} spp", region.name, region.target_samples);
+
+        // a completed render resumes with a new pass
+        let resume = self.is_complete() && self.in_flight == 0;
+
+        self.regions.retain(|r| r.name != region.name);
+        self.regions.push(region);
+
+        if resume && !self.is_complete() {
+            self.next_pass();
+        }
+    }
+
+    pub fn remove_region(&mut self, name: &str) -> Option<Region> {
+        let idx = self.regions.iter().position(|r| r.name == name)?;
+
+        Some(self.regions.remove(idx))
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    // pixels outside of the regions stop at the target of the tracer
+    fn needs_samples(&self, idx: usize, first_sample: u32) -> bool {
+        let Some(target) = self.target_samples else {
+            return true;
+        };
+
+        // the regions are given at the size of the output
+        let (width, s) = (self.scene.extent.width as usize, self.supersample as usize);
+        let idx = idx / width / s * (width / s) + idx % width / s;
+
+        let target = self
+            .regions
+            .iter()
+            .filter(|region| region.contains(self.extent(), idx))
+            .map(|region| region.target_samples)
+            .fold(target, u32::max);
+
+        first_sample < target
+    }
+
+    // non blocking: adds the chunks completed by the workers and dispatches new ones,
+    // returns true when the image changed
+    pub fn update(&mut self) -> bool {
+        let start = Instant::now();
+
+        if self.adaptive_threads {
+            if let (Some(last), Some(pacing)) = (self.last_update, &mut self.pacing) {
+                pacing.frame_time((start - last).as_secs_f32());
+            }
+            self.last_update = Some(start);
+        }
+
+        let result = self.step();
+
+        let update_time = start.elapsed().as_secs_f32();
+        self.frame_stats.update_time = update_time;
+        self.frame_stats.budget_used = match self.frame_budget {
+            Some(budget) => update_time / budget,
+            None => 0.,
+        };
+
+        result
+    }
+
+    pub fn frame_stats(&self) -> FrameStats {
+        self.frame_stats
+    }
+
+    // frame time of the host application, fewer chunks are dispatched per update
+    // while it stays over the frame budget, it replaces the interval between the
+    // updates measured with adaptive_threads
+    pub fn frame_time(&mut self, delta: f32) {
+        self.adaptive_threads = false;
+
+        if let Some(pacing) = &mut self.pacing {
+            pacing.frame_time(delta);
+        }
+    }
+
+    fn step(&mut self) -> bool {
+        self.check_reload();
+
+        let control = self.scene.control.clone();
+
+        if control.is_cancelled() {
+            control.clear();
+            if !self.stopped {
+                log::info!("Rendering cancelled after pass {}", self.pass);
+                self.stopped = true;
+                // the chunks in flight are dropped
+                self.next_version();
+                self.clear_in_flight();
+            }
+        }
+
+        // the time spent paused is not counted in the render time
+        if control.is_paused() {
+            if !self.paused && !self.stopped {
+                self.paused = true;
+                self.pass_time += self.timer.delta();
+            }
+            return false;
+        } else if self.paused {
+            self.paused = false;
+            self.timer.reset();
+        }
+
+        let reset = self.changed;
+
+        if self.changed {
+            // chunks of the previous version not started yet
+            self.clear_in_flight();
+            self.reset();
+            self.timer.reset();
+            self.render_time = 0.;
+            self.scene.stats.reset();
+            self.pass = 0;
+            self.pass_time = 0.;
+            self.pass_pixels = 0;
+            self.pass_started = false;
+            self.stopped = false;
+            self.timed_out = false;
+            self.preview_pass = self.fast_preview;
+
+            if let Some(checkpoint) = self.resume.take() {
+                self.restore(checkpoint);
+            }
+
+            #[cfg(feature = "gpu")]
+            if let Some(gpu) = &mut self.gpu {
+                gpu.upload(&self.scene);
+            }
+        }
+
+        // chunks of a previous version are still drained to release their slot
+        let added = self.drain_chunks();
+
+        let mut result = reset || added > 0 || self.preview_changed;
+
+        if !self.stopped && !self.is_complete() && self.is_out_of_time() {
+            self.pass_time += self.timer.delta();
+            self.timed_out = true;
+
+            log::info!(
+                "Time limit reached: {:.2}s ({} spp)",
+                self.render_time + self.pass_time,
+                self.samples_per_pixel()
+            );
+
+            // the image keeps the samples added so far, the chunks in flight are dropped
+            self.next_version();
+            self.clear_in_flight();
+            self.finish();
+
+            result = true;
+        }
+
+        // a region invalidated once the render is complete is rendered on its own
+        let catching_up = self.is_complete() && !self.is_pass_complete();
+        let rendering = !self.stopped && (!self.is_complete() || catching_up);
+
+        if rendering {
+            if !self.pass_started && !self.preview_pass && !catching_up {
+                self.pass_started = true;
+                let stats = PassStats {
+                    pass: self.pass,
+                    pixels: 0,
+                    samples_per_pixel: self.samples_per_pixel(),
+                    elapsed: 0.,
+                };
+                self.run_hooks(true, &stats);
+            }
+
+            self.dispatch_chunks();
+
+            let pass_complete = self.in_flight == 0 && self.is_pass_complete();
+
+            if pass_complete && self.preview_pass {
+                log::debug!("Preview done");
+
+                self.preview_pass = false;
+                self.pass_pixels = 0;
+                self.next_pass();
+            } else if pass_complete && catching_up {
+                log::debug!("Region done");
+
+                result = true;
+
+                self.finish();
+            } else if pass_complete {
+                let elapsed = self.pass_time + self.timer.delta();
+                self.pass_time = 0.;
+                self.render_time += elapsed;
+                self.pass += 1;
+                self.pass_pixels = 0;
+
+                log::debug!("Pass {} time: {:.2}s", self.pass, elapsed);
+
+                let stats = PassStats {
+                    pass: self.pass - 1,
+                    pixels: self.scene.extent.size() as usize * (1 + self.views.len()),
+                    samples_per_pixel: self.samples_per_pixel(),
+                    elapsed,
+                };
+                self.run_hooks(false, &stats);
+
+                self.pass_started = false;
+
+                if self.is_complete() {
+                    log::info!(
+                        "Rendering time: {:.2}s ({} spp)",
+                        self.render_time,
+                        self.samples_per_pixel()
+                    );
+                    if let Some(summary) = self.tile_timings().summary() {
+                        log::info!("Tile times: {}", summary);
+                    }
+
+                    result = true;
+
+                    self.finish();
+                } else {
+                    self.next_pass();
+                }
+
+                #[cfg(feature = "video")]
+                self.record_frame();
+            }
+        }
+
+        self.status.update(
+            self.pass,
+            self.samples_per_pixel(),
+            self.version,
+            self.is_complete(),
+        );
+
+        // the settings of the preview change all the pixels
+        self.full_refresh |= self.preview_changed;
+
+        self.changed = false;
+        self.preview_changed = false;
+
+        result
+    }
+
+    // adds the image after each pass to the video until stop_video, a timelapse of
+    // the progressive refinement
+    #[cfg(feature = "video")]
+    pub fn record_video(&mut self, path: &str, fps: f32) -> io::Result<()> {
+        self.stop_video()?;
+        self.video = Some(VideoEncoder::new(path, self.extent(), fps)?);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "video")]
+    pub fn stop_video(&mut self) -> io::Result<()> {
+        match self.video.take() {
+            Some(video) => video.finish(),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "video")]
+    fn record_frame(&mut self) {
+        if self.video.is_none() {
+            return;
+        }
+
+        let frame = self.bytes();
+        if let Some(Err(e)) = self.video.as_mut().map(|video| video.add_frame(&frame)) {
+            log::warn!("Recording stopped: {}", e);
+            self.video = None;
+        }
+    }
+
+    fn is_out_of_time(&self) -> bool {
+        match self.time_limit {
+            Some(limit) => self.render_time + self.pass_time + self.timer.elapsed() >= limit,
+            None => false,
+        }
+    }
+
+    // final image, denoised if the tracer has a denoiser
+    fn finish(&mut self) {
+        if let Some((atrous, _)) = &self.denoiser {
+            let image_buffer = self.image_buffer.lock().unwrap();
+            self.denoised = Some(atrous.apply(
+                self.scene.extent,
+                &image_buffer.framebuffer,
+                &image_buffer.aovs,
+            ));
+        }
+
+        #[cfg(feature = "oidn")]
+        if self.auto_denoise {
+            self.denoise();
+        }
+    }
+
+    fn next_pass(&mut self) {
+        let seed = self.rng_stream.pass_seed();
+        self.image_buffer.lock().unwrap().next_pass(seed);
+        for view in &self.views {
+            let seed = self.rng_stream.pass_seed();
+            view.image_buffer.lock().unwrap().next_pass(seed);
+        }
+    }
+
+    fn run_hooks(&mut self, pre_pass: bool, stats: &PassStats) {
+        let hooks = if pre_pass {
+            &mut self.pre_pass_hooks
+        } else {
+            &mut self.post_pass_hooks
+        };
+
+        for hook in hooks.iter_mut() {
+            if hook(stats) == PassControl::STOP {
+                log::info!("Rendering stopped by hook after pass {}", stats.pass);
+                self.stopped = true;
+            }
+        }
+    }
+
+    // chunks are queued while update() returns, the workers on the pool pull them
+    // until the queue is empty
+    fn dispatch_chunks(&mut self) {
+        let max_workers = match &self.pacing {
+            Some(pacing) => pacing.chunks().min(self.n_threads),
+            None => self.n_threads,
+        } as usize;
+        self.queue.set_max_workers(max_workers);
+
+        #[cfg(feature = "gpu")]
+        if self.gpu_pass() {
+            return;
+        }
+
+        let first_sample = self.samples_per_pixel();
+        let workers =
+            self.n_threads as usize + self.coordinator.as_ref().map_or(0, |c| c.workers());
+
+        // enough chunks to keep the workers busy until the next update
+        while self.in_flight < workers * Self::QUEUE_DEPTH {
+            let Some((camera, image_buffer, scene, chunk, first_sample)) =
+                self.next_chunk(first_sample)
+            else {
+                break;
+            };
+            // the preview doesn't take random samples
+            let rng_seed = if self.preview_pass {
+                0
+            } else {
+                self.rng_stream
+                    .chunk_seed(chunk.first().copied().unwrap_or(0))
+            };
+
+            self.queue.push(Job {
+                version: self.version,
+                camera,
+                image_buffer,
+                scene,
+                chunk,
+                first_sample,
+                rng_seed,
+                preview: self.preview_pass,
+            });
+
+            self.in_flight += 1;
+        }
+
+        while self.queue.add_worker() {
+            let queue = self.queue.clone();
+
+            self.pool.spawn(move || Self::work(&queue));
+        }
+    }
+
+    // the chunks not started yet and the pass of the gpu are dropped
+    fn clear_in_flight(&mut self) {
+        self.in_flight -= self.queue.clear();
+
+        #[cfg(feature = "gpu")]
+        if self.gpu_pass.take().is_some() {
+            self.in_flight -= 1;
+        }
+    }
+
+    // the whole pass of the main camera in one dispatch, polled by the next updates,
+    // false to render it on the cpu
+    #[cfg(feature = "gpu")]
+    fn gpu_pass(&mut self) -> bool {
+        if let Some((pass, _)) = &self.gpu_pass {
+            let Some(gpu) = &self.gpu else {
+                return false;
+            };
+
+            let status = gpu.poll(pass);
+            if matches!(status, GpuStatus::PENDING) {
+                return true;
+            }
+
+            let (_, pixels) = self.gpu_pass.take().expect("GPU pass");
+            self.in_flight -= 1;
+
+            let GpuStatus::DONE(samples) = status else {
+                // the pass is lost, the next one goes to the cpu
+                self.gpu = None;
+                return false;
+            };
+
+            let mut image_buffer = self.image_buffer.lock().unwrap();
+            for &idx in &pixels {
+                image_buffer.add_samples(&samples[idx]);
+            }
+
+            self.pass_pixels += pixels.len() as u64;
+            self.preview_changed = true;
+
+            return true;
+        }
+
+        let Some(gpu) = self.gpu.as_ref().filter(|gpu| gpu.is_ready()) else {
+            return false;
+        };
+        // the shader doesn't splat the samples
+        if self.preview_pass
+            || !self.regions.is_empty()
+            || self.in_flight > 0
+            || self.scene.filter != PixelFilter::BOX
+        {
+            return false;
+        }
+
+        let mut image_buffer = self.image_buffer.lock().unwrap();
+        let Some(pixels) = image_buffer.take_pass() else {
+            return false;
+        };
+        if pixels.is_empty() {
+            return false;
+        }
+
+        let seed = self.rng_stream.chunk_seed(0) as u32;
+        let Some(pass) = gpu.dispatch(&self.scene, self.samples_per_pixel(), seed) else {
+            // the pass is lost, the next one goes to the cpu
+            drop(image_buffer);
+            self.gpu = None;
+            return false;
+        };
+
+        self.gpu_pass = Some((pass, pixels));
+        self.in_flight += 1;
+
+        true
+    }
+
+    fn work(queue: &ChunkQueue) {
+        let mut scratch = ChunkScratch::default();
+        let mut result = Vec::new();
+
+        while let Some(job) = queue.pop() {
+            let mut timer = Timer::new();
+            job.run(&mut scratch, &mut result);
+
+            queue.complete(&job, &result, timer.delta());
+        }
+    }
+
+    // pixels already at their target are skipped, the chunks of a reset region come
+    // with their own first sample
+    fn next_chunk(&self, first_sample: u32) -> Option<SampledChunk> {
+        loop {
+            let (camera, image_buffer, scene, (mut chunk, pass)) = self.take_chunk()?;
+            let first_sample = match pass {
+                Some(pass) => pass * self.scene.n_rays,
+                None => first_sample,
+            };
+
+            if !self.regions.is_empty() {
+                chunk.retain(|&idx| self.needs_samples(idx, first_sample));
+            }
+
+            if !chunk.is_empty() {
+                return Some((camera, image_buffer, scene, chunk, first_sample));
+            }
+        }
+    }
+
+    // 0 for the main camera, then the views
+    pub(crate) fn camera_scene(&self, camera: usize) -> Option<Arc<Scene>> {
+        match camera {
+            0 => Some(self.scene.clone()),
+            _ => self.views.get(camera - 1).map(|view| view.scene.clone()),
+        }
+    }
+
+    // the main camera first, then the views in the order they were added
+    fn take_chunk(&self) -> Option<CameraChunk> {
+        let mut image_buffer = self.image_buffer.lock().unwrap();
+        if !image_buffer.is_pass_complete() {
+            return Some((
+                0,
+                self.image_buffer.clone(),
+                self.scene.clone(),
+                image_buffer.get_chunk(),
+            ));
+        }
+
+        self.views.iter().enumerate().find_map(|(i, view)| {
+            let mut image_buffer = view.image_buffer.lock().unwrap();
+
+            (!image_buffer.is_pass_complete()).then(|| {
+                (
+                    i + 1,
+                    view.image_buffer.clone(),
+                    view.scene.clone(),
+                    image_buffer.get_chunk(),
+                )
+            })
+        })
+    }
+
+    fn is_pass_complete(&self) -> bool {
+        self.image_buffer.lock().unwrap().is_pass_complete()
+            && self
+                .views
+                .iter()
+                .all(|view| view.image_buffer.lock().unwrap().is_pass_complete())
+    }
 
-            let ray = Ray::new(self.camera.position, Vec3::new(x, y, 1.));
+    // counts the chunks completed since the last call, the workers already added
+    // them to the image, returns the number of chunks added
+    fn drain_chunks(&mut self) -> u32 {
+        let counts = self.queue.progress();
+        self.in_flight -= counts.done;
 
-            c = c + self.cast(&ray, self.n_reflects);
+        if counts.done > 0 {
+            if let Some(pacing) = &mut self.pacing {
+                pacing.chunk_time(counts.time / counts.done as f32);
+            }
         }
 
-        c = c / self.n_rays as f32;
+        if counts.clamped > 0 || counts.invalid > 0 {
+            log::debug!(
+                "Samples clamped: {}, invalid: {}",
+                counts.clamped,
+                counts.invalid
+            );
+        }
 
-        c
-    }
+        self.pass_pixels += counts.pixels;
 
-    fn cast(&self, ray: &Ray, limit: u32) -> Color {
-        if limit <= 0 {
-            return Color::BLACK;
+        if counts.added > 0 && self.progress_hook.is_some() {
+            let progress = self.progress();
+            if let Some(hook) = &mut self.progress_hook {
+                hook(&progress, counts.added);
+            }
         }
 
-        let bg: fn(&Ray) -> Color = self.background;
+        self.frame_stats.chunks = counts.added;
 
-        let hit = self
-            .models
-            .iter()
-            .filter_map(|m| m.hit(&ray, self.camera.mode.near(), self.camera.mode.far()))
-            .min_by(|h1, h2| h1.distance.partial_cmp(&h2.distance).unwrap());
-
-        match hit {
-            Some(hit) => {
-                let reflect_color = self.cast(&ray.reflect(hit.position, hit.normal), limit - 1);
-
-                for light in &self.lights {
-                    let light_direction = light.position - hit.position;
-                    let light_ray = Ray::new(hit.position, light_direction);
-                    let blocker = self.models.iter().find(|m| {
-                        m.hit_distance(&light_ray, self.camera.mode.near(), self.camera.mode.far())
-                            .is_some()
-                    });
-
-                    if blocker.is_none() {
-                        return hit.color * (1. - hit.reflect) + reflect_color * hit.reflect;
-                    }
-                }
+        counts.added
+    }
+}
 
-                (hit.color * (1. - hit.reflect) + reflect_color * hit.reflect) * 0.5
-            }
-            None => bg(&ray),
-        }
+// the workers stop after their current chunk
+impl Drop for Tracer {
+    fn drop(&mut self) {
+        self.queue.clear();
     }
 }
 
 pub struct TracerBuilder {
-    extent: ImageExtent2D,
-    models: Vec<Box<dyn Hitable + Sync + Send>>,
-    lights: Vec<Light>,
+    extent: Extent,
+    models: Vec<Primitive>,
+    lights: Vec<PointLight>,
+    portals: Vec<Portal>,
+    decals: Vec<Decal>,
     camera: Camera,
+    cameras: Vec<(String, Camera)>,
     background: fn(&Ray) -> Color,
     n_rays: u32,
     n_reflects: u32,
     n_threads: u32,
+    low_priority: bool,
+    target_samples: Option<u32>,
+    time_limit: Option<f32>,
+    regions: Vec<Region>,
+    normalize_scale: bool,
+    alpha_cutoff: f32,
+    epsilon: f32,
+    shadow_bias: f32,
+    min_throughput: f32,
+    ev100: f32,
+    ambient: f32,
     strategy: ChunkStrategy,
+    accel: AccelKind,
+    integrator: Integrator,
+    sampler: SamplerKind,
+    light_sampler: SamplerKind,
+    light_sampling: LightSampling,
+    seed: u32,
+    tonemap: Tonemap,
+    outline: Option<Outline>,
+    palette: Option<Palette>,
+    post_chain: Vec<PostStage>,
+    origin: ImageOrigin,
+    supersample: u32,
+    downsample: DownsampleFilter,
+    filter: PixelFilter,
+    file: Option<SceneFile>,
+    watch: Option<String>,
+    resume: Option<String>,
+    backend: Backend,
+    sample_clamp: Option<f32>,
+    payload: Option<PayloadFactory>,
+    denoiser: Option<(Atrous, DenoiseMode)>,
+    #[cfg(feature = "oidn")]
+    auto_denoise: bool,
+    progress_hook: Option<ProgressHook>,
+    frame_budget: Option<f32>,
+    thread_bounds: Option<(u32, u32)>,
+    fast_preview: bool,
+    rng_stream: RngStream,
+    pre_pass_hooks: Vec<PassHook>,
+    post_pass_hooks: Vec<PassHook>,
 }
 
 impl TracerBuilder {
@@ -183,15 +2107,15 @@ impl TracerBuilder {
         Color::BLACK
     }
 
-    pub async fn new(extent: ImageExtent2D) -> Self {
+    pub async fn new(extent: Extent) -> Self {
         let camera = Camera::perspective(
             Vec3::new(0., 0.2, 0.),
             extent.width as f32 / extent.height as f32,
-            (45. as f32).to_radians(),
+            45_f32.to_radians(),
             0.1,
             100.,
-            (-90. as f32).to_radians(),
-            (0. as f32).to_radians(),
+            -90_f32.to_radians(),
+            0_f32.to_radians(),
             Vec3::Y,
         );
 
@@ -199,12 +2123,56 @@ impl TracerBuilder {
             extent,
             models: Vec::new(),
             lights: Vec::new(),
+            portals: Vec::new(),
+            decals: Vec::new(),
             camera,
+            cameras: Vec::new(),
             background: Self::default_background,
             n_rays: 10,
             n_reflects: 10,
             n_threads: 1,
-            strategy: ChunkStrategy::BOX,
+            low_priority: false,
+            target_samples: None,
+            time_limit: None,
+            regions: Vec::new(),
+            normalize_scale: false,
+            alpha_cutoff: 0.,
+            epsilon: TracerConfig::default_epsilon(),
+            shadow_bias: TracerConfig::default_shadow_bias(),
+            min_throughput: TracerConfig::default_min_throughput(),
+            ev100: 0.,
+            ambient: 0.5,
+            strategy: ChunkStrategy::default(),
+            accel: AccelKind::BVH,
+            integrator: Integrator::default(),
+            sampler: TracerConfig::default_sampler(),
+            light_sampler: TracerConfig::default_light_sampler(),
+            light_sampling: LightSampling::default(),
+            seed: 0,
+            tonemap: Tonemap::default(),
+            outline: None,
+            palette: None,
+            post_chain: PostStage::default_chain(),
+            origin: ImageOrigin::default(),
+            supersample: TracerConfig::default_supersample(),
+            downsample: DownsampleFilter::default(),
+            filter: PixelFilter::default(),
+            file: None,
+            watch: None,
+            resume: None,
+            backend: Backend::CPU,
+            sample_clamp: None,
+            payload: None,
+            denoiser: None,
+            #[cfg(feature = "oidn")]
+            auto_denoise: false,
+            progress_hook: None,
+            frame_budget: None,
+            thread_bounds: None,
+            fast_preview: false,
+            rng_stream: RngStream::LIVE,
+            pre_pass_hooks: Vec::new(),
+            post_pass_hooks: Vec::new(),
         }
     }
 
@@ -214,20 +2182,55 @@ impl TracerBuilder {
         self
     }
 
+    pub fn background_preset(self, background: Background) -> Self {
+        self.background(background.shader())
+    }
+
     pub fn camera(mut self, camera: Camera) -> Self {
         self.camera = camera;
 
         self
     }
 
-    pub fn light(mut self, light: Light) -> Self {
+    // rendered with the main camera in each pass, see Tracer::framebuffer_for
+    pub fn add_camera(mut self, name: &str, camera: Camera) -> Self {
+        self.cameras.push((name.to_string(), camera));
+
+        self
+    }
+
+    pub fn light(mut self, light: PointLight) -> Self {
         self.lights.push(light);
 
         self
     }
 
-    pub fn model(mut self, model: Box<dyn Hitable + Sync + Send>) -> Self {
-        self.models.push(model);
+    // openings through which the background lights an interior
+    pub fn portal(mut self, portal: Portal) -> Self {
+        self.portals.push(portal);
+
+        self
+    }
+
+    pub fn decal(mut self, decal: Decal) -> Self {
+        self.decals.push(decal);
+
+        self
+    }
+
+    // built-in primitives or boxed user types implementing Hitable
+    pub fn model(mut self, model: impl Into<Primitive>) -> Self {
+        self.models.push(model.into());
+
+        self
+    }
+
+    // the model is wrapped in a group of the same name up front, as other models
+    // are on their first Tracer::translate_model or Tracer::set_model_transform
+    pub fn movable_model(mut self, model: impl Into<Primitive>) -> Self {
+        let model = model.into();
+        let name = model.name().to_string();
+        self.models.push(Group::build(&name, vec![model]).into());
 
         self
     }
@@ -250,26 +2253,604 @@ impl TracerBuilder {
         self
     }
 
+    // the threads of the tracer yield to the host application, e.g. the render loop
+    pub fn low_priority(mut self) -> Self {
+        self.low_priority = true;
+
+        self
+    }
+
+    pub fn target_samples(mut self, samples: u32) -> Self {
+        self.target_samples = Some(samples);
+
+        self
+    }
+
+    // the render stops at the end of the budget with the samples computed so far,
+    // the render time restarts when the scene changes
+    pub fn time_limit(mut self, limit: Duration) -> Self {
+        self.time_limit = Some(limit.as_secs_f32());
+
+        self
+    }
+
+    // see Tracer::add_region
+    pub fn region(mut self, region: Region) -> Self {
+        self.regions.retain(|r| r.name != region.name);
+        self.regions.push(region);
+
+        self
+    }
+
+    pub fn alpha_cutoff(mut self, alpha_cutoff: f32) -> Self {
+        self.alpha_cutoff = alpha_cutoff;
+
+        self
+    }
+
+    // relative offset of the rays leaving a surface, see Scene::t_min()
+    // for scenes mixing tiny and huge objects or far from the origin, the
+    // positions given to the tracer afterwards stay in the same units
+    pub fn normalize_scale(mut self) -> Self {
+        self.normalize_scale = true;
+
+        self
+    }
+
+    pub fn epsilon(mut self, epsilon: f32) -> Self {
+        self.epsilon = epsilon;
+
+        self
+    }
+
+    // distance along the normal between a surface and the start of its shadow rays,
+    // larger values fix shadow acne but detach the shadows from the casters
+    pub fn shadow_bias(mut self, shadow_bias: f32) -> Self {
+        self.shadow_bias = shadow_bias;
+
+        self
+    }
+
+    pub fn min_throughput(mut self, min_throughput: f32) -> Self {
+        self.min_throughput = min_throughput;
+
+        self
+    }
+
+    // exposure value at ISO 100 of the camera, it scales the radiance of the samples,
+    // see Tonemap::compensation to adjust the image once rendered
+    pub fn exposure(mut self, ev100: f32) -> Self {
+        self.ev100 = ev100;
+
+        self
+    }
+
+    pub fn ambient(mut self, ambient: f32) -> Self {
+        self.ambient = ambient;
+
+        self
+    }
+
     pub fn strategy(mut self, strategy: ChunkStrategy) -> Self {
         self.strategy = strategy;
 
         self
     }
 
-    pub async fn build(self) -> Tracer {
-        let image_buffer = ImageBuffer::new(self.extent, self.strategy);
+    pub fn accel(mut self, accel: AccelKind) -> Self {
+        self.accel = accel;
 
-        Tracer {
-            image_buffer,
-            models: self.models,
-            lights: self.lights,
-            camera: self.camera,
+        self
+    }
+
+    pub fn integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator = integrator;
+
+        self
+    }
+
+    pub fn sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = sampler;
+
+        self
+    }
+
+    pub fn light_sampler(mut self, sampler: SamplerKind) -> Self {
+        self.light_sampler = sampler;
+
+        self
+    }
+
+    pub fn light_sampling(mut self, light_sampling: LightSampling) -> Self {
+        self.light_sampling = light_sampling;
+
+        self
+    }
+
+    // scrambles the sample sequences of every pixel
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+
+        self
+    }
+
+    pub fn tonemap(mut self, tonemap: Tonemap) -> Self {
+        self.tonemap = tonemap;
+
+        self
+    }
+
+    pub fn outline(mut self, outline: Outline) -> Self {
+        self.outline = Some(outline);
+
+        self
+    }
+
+    // stylized output, quantized to the colors of the palette
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = Some(palette);
+
+        self
+    }
+
+    // order of the post effects, replaces the default tonemap, outline, dither, palette chain
+    pub fn post_chain(mut self, post_chain: Vec<PostStage>) -> Self {
+        self.post_chain = post_chain;
+
+        self
+    }
+
+    // on the linear values: inserted before the tonemap, after the effects already
+    // added, or at the end of a chain without tonemap, see post_chain for another order
+    pub fn post_effect(mut self, effect: impl PostEffect + 'static) -> Self {
+        let idx = self
+            .post_chain
+            .iter()
+            .position(|stage| matches!(stage, PostStage::TONEMAP))
+            .unwrap_or(self.post_chain.len());
+        self.post_chain.insert(idx, PostStage::effect(effect));
+
+        self
+    }
+
+    pub fn origin(mut self, origin: ImageOrigin) -> Self {
+        self.origin = origin;
+
+        self
+    }
+
+    // renders factor x factor pixels for each pixel of the output, independent of
+    // the rays per pixel, the regions and the coordinates of the tracer stay in
+    // output pixels
+    pub fn supersample(mut self, factor: u32) -> Self {
+        self.supersample = factor.max(1);
+
+        self
+    }
+
+    pub fn downsample_filter(mut self, filter: DownsampleFilter) -> Self {
+        self.downsample = filter;
+
+        self
+    }
+
+    // spreads each sample over the pixels around it instead of averaging the rays
+    // of a pixel, less aliasing on thin models and highlights
+    pub fn pixel_filter(mut self, filter: PixelFilter) -> Self {
+        self.filter = filter;
+
+        self
+    }
+
+    // clamps the luminance of each sample to remove fireflies
+    pub fn sample_clamp(mut self, max: f32) -> Self {
+        self.sample_clamp = Some(max);
+
+        self
+    }
+
+    // state threaded through the path of each sample, created per pixel and sample
+    pub fn payload<F>(mut self, factory: F) -> Self
+    where
+        F: Fn(usize, u32) -> Box<dyn Payload> + Send + Sync + 'static,
+    {
+        self.payload = Some(Arc::new(factory));
+
+        self
+    }
+
+    pub fn denoiser(mut self, atrous: Atrous, mode: DenoiseMode) -> Self {
+        self.denoiser = Some((atrous, mode));
+
+        self
+    }
+
+    // denoise once the target samples are reached
+    #[cfg(feature = "oidn")]
+    pub fn auto_denoise(mut self, auto_denoise: bool) -> Self {
+        self.auto_denoise = auto_denoise;
+
+        self
+    }
+
+    // called by update() when chunks were added to the image since the previous
+    // update, with the number of chunks, the workers add them as they complete
+    pub fn on_progress<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&RenderProgress, u32) + Send + 'static,
+    {
+        self.progress_hook = Some(Box::new(hook));
+
+        self
+    }
+
+    // target frame time of the host, enables the adaptive chunk dispatch
+    pub fn frame_budget(mut self, budget: Duration) -> Self {
+        self.frame_budget = Some(budget.as_secs_f32());
+
+        self
+    }
+
+    // keeps between min and max chunks computed at the same time, fewer while the
+    // calls to Tracer::update are further apart than the frame budget, i.e. while
+    // the host is starving, and up to max when it polls in a loop, see
+    // Tracer::frame_time for the hosts that measure their frames
+    pub fn adaptive_threads(mut self, min: u32, max: u32) -> Self {
+        self.thread_bounds = Some((min, max));
+
+        self
+    }
+
+    // approximate image before the first pass each time the render restarts, lit
+    // without shadows and reflecting only the background, the response of each
+    // material is baked in a LUT when the tracer is built
+    pub fn fast_preview(mut self) -> Self {
+        self.fast_preview = true;
+
+        self
+    }
+
+    // the chunk order and the samples are the same in each run with the same scene
+    // and settings, e.g. to compare the images of two versions
+    pub fn deterministic(mut self, seed: u64) -> Self {
+        self.rng_stream = RngStream::SEEDED(Box::new(StdRng::seed_from_u64(seed)));
+
+        self
+    }
+
+    // keeps the seeds of the chunk order and of the samplers, see Tracer::save_rng_recording
+    pub fn record_rng(mut self) -> Self {
+        self.rng_stream = RngStream::RECORD(RngRecording::default());
+
+        self
+    }
+
+    // renders with the seeds of a recorded session, the scene and settings must be the same
+    pub fn replay_rng(mut self, recording: RngRecording) -> Self {
+        self.rng_stream = RngStream::REPLAY {
+            recording,
+            pass: 0,
+            chunk: 0,
+        };
+
+        self
+    }
+
+    pub fn pre_pass<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&PassStats) -> PassControl + Send + 'static,
+    {
+        self.pre_pass_hooks.push(Box::new(hook));
+
+        self
+    }
+
+    pub fn post_pass<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&PassStats) -> PassControl + Send + 'static,
+    {
+        self.post_pass_hooks.push(Box::new(hook));
+
+        self
+    }
+
+    // reloads the scene from path when the file changes
+    pub fn watch(mut self, path: &str) -> Self {
+        self.watch = Some(path.to_string());
+
+        self
+    }
+
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+
+        self
+    }
+
+    // goes on with the samples of Tracer::save_checkpoint, the scene and settings
+    // are expected to be the same
+    pub fn resume_from(mut self, path: &str) -> Self {
+        self.resume = Some(path.to_string());
+
+        self
+    }
+
+    // description of the scene, kept for save_scene()
+    pub(crate) fn file(mut self, file: SceneFile) -> Self {
+        self.file = Some(file);
+
+        self
+    }
+
+    pub async fn from_config(config: &TracerConfig) -> Self {
+        Self::new(Extent::new(config.width, config.height))
+            .await
+            .config(config)
+    }
+
+    pub fn config(mut self, config: &TracerConfig) -> Self {
+        self.target_samples = config.target_samples;
+        self.time_limit = config.time_limit;
+        self.sample_clamp = config.sample_clamp;
+        self.palette = config.palette.clone();
+
+        self.rays(config.rays)
+            .reflects(config.reflects)
+            .threads(config.threads)
+            .alpha_cutoff(config.alpha_cutoff)
+            .epsilon(config.epsilon)
+            .shadow_bias(config.shadow_bias)
+            .min_throughput(config.min_throughput)
+            .exposure(config.exposure)
+            .ambient(config.ambient)
+            .strategy(config.strategy)
+            .accel(config.accel)
+            .integrator(config.integrator)
+            .sampler(config.sampler)
+            .light_sampler(config.light_sampler)
+            .light_sampling(config.light_sampling)
+            .seed(config.seed)
+            .tonemap(config.tonemap)
+            .origin(config.origin)
+            .supersample(config.supersample)
+            .downsample_filter(config.downsample)
+            .pixel_filter(config.filter)
+    }
+
+    // moves and scales the models, lights, decals and cameras to fit in a cube of
+    // side 2 around the origin
+    fn normalize_units(&mut self) -> Option<Normalization> {
+        let bounds = self
+            .models
+            .iter()
+            .fold(Aabb::empty(), |bounds, model| bounds.union(&model.bounds()));
+        let normalization = Normalization::from_bounds(&bounds)?;
+        let (center, scale) = (normalization.center, normalization.scale);
+
+        for i in 0..self.models.len() {
+            if !self.models[i].rescale(center, scale) {
+                log::warn!(
+                    "Scene not normalized: {} cannot be rescaled",
+                    self.models[i].name()
+                );
+
+                // back to the original units
+                for model in self.models[..i].iter_mut() {
+                    model.rescale(-center * scale, 1. / scale);
+                }
+
+                return None;
+            }
+        }
+
+        self.lights = self.lights.iter().map(|l| normalization.light(l)).collect();
+        self.portals = self
+            .portals
+            .iter()
+            .map(|p| normalization.portal(p))
+            .collect();
+        for decal in self.decals.iter_mut() {
+            decal.rescale(center, scale);
+        }
+        self.camera = normalization.camera(&self.camera);
+        for (_, camera) in self.cameras.iter_mut() {
+            *camera = normalization.camera(camera);
+        }
+
+        log::info!("Scene normalized: center {:?}, scale {}", center, scale);
+
+        Some(normalization)
+    }
+
+    pub async fn build(mut self) -> Tracer {
+        let normalization = if self.normalize_scale {
+            self.normalize_units()
+        } else {
+            None
+        };
+
+        let extent = Extent::new(
+            self.extent.width * self.supersample,
+            self.extent.height * self.supersample,
+        );
+        let image_buffer = ImageBuffer::new(extent, self.strategy);
+
+        let models = self.models.into_iter().map(Arc::new).collect::<Vec<_>>();
+        let accel = AccelKind::build(self.accel, &models);
+
+        let soft_casters = models.iter().any(|m| m.shadow_softness() != 1.);
+        let emitters = scene::emitters(&models);
+        let media = scene::media(&models);
+        let responses = if self.fast_preview {
+            scene::responses(&models)
+        } else {
+            Vec::new()
+        };
+
+        let low_priority = self.low_priority;
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(self.n_threads as usize)
+            .thread_name(|i| format!("tracer-{}", i))
+            .start_handler(move |i| {
+                if low_priority {
+                    if let Err(e) = set_current_thread_priority(ThreadPriority::Min) {
+                        log::warn!("Cannot lower the priority of tracer-{}: {}", i, e);
+                    }
+                }
+            })
+            .build()
+            .expect("Thread pool");
+
+        let pacing = match (self.frame_budget, self.thread_bounds) {
+            (budget, Some((min, max))) => Some(
+                FramePacing::new(
+                    budget.unwrap_or(FramePacing::DEFAULT_BUDGET),
+                    max.min(self.n_threads),
+                )
+                .min_chunks(min),
+            ),
+            (Some(budget), None) => Some(FramePacing::new(budget, self.n_threads)),
+            (None, None) => None,
+        };
+
+        let watcher = self.watch.and_then(|path| {
+            FileWatcher::new(&path)
+                .map_err(|e| log::warn!("Cannot watch {}: {}", path, e))
+                .ok()
+        });
+
+        #[cfg(feature = "gpu")]
+        let gpu = match self.backend {
+            Backend::GPU => Gpu::new().await,
+            Backend::CPU => None,
+        };
+        #[cfg(not(feature = "gpu"))]
+        if self.backend == Backend::GPU {
+            log::warn!("GPU backend not available: built without the gpu feature");
+        }
+
+        let resume = self.resume.and_then(|path| {
+            Checkpoint::load(&path, extent)
+                .map_err(|e| log::warn!("Cannot resume from {}: {}", path, e))
+                .ok()
+        });
+
+        let scene = Scene {
+            extent,
+            models: Arc::new(models),
+            accel: Arc::new(accel),
+            lights: Arc::new(self.lights),
+            emitters: Arc::new(emitters),
+            media: Arc::new(media),
+            responses: Arc::new(responses),
+            portals: Arc::new(self.portals),
+            decals: Arc::new(self.decals),
+            camera: Arc::new(self.camera),
             background: self.background,
             n_rays: self.n_rays,
             n_reflects: self.n_reflects,
+            alpha_cutoff: self.alpha_cutoff,
+            epsilon: self.epsilon,
+            // along the normals, in the units of the scene
+            shadow_bias: self.shadow_bias * normalization.map_or(1., |n| n.scale),
+            min_throughput: self.min_throughput,
+            exposure: ev100_to_exposure(self.ev100),
+            ambient: self.ambient,
+            integrator: self.integrator,
+            sampler: self.sampler,
+            light_sampler: self.light_sampler,
+            light_sampling: self.light_sampling,
+            seed: self.seed,
+            soft_casters,
+            control: Arc::new(RenderControl::default()),
+            sample_clamp: self.sample_clamp,
+            filter: self.filter,
+            payload: self.payload,
+            debug_counts: false,
+            stats: Arc::new(StatCounters::default()),
+        };
+
+        // the views share the models and the acceleration structure
+        let views = self
+            .cameras
+            .into_iter()
+            .map(|(name, camera)| {
+                let mut view = scene.clone();
+                view.camera = Arc::new(camera);
+
+                View {
+                    name,
+                    scene: Arc::new(view),
+                    image_buffer: Arc::new(Mutex::new(ImageBuffer::new(extent, self.strategy))),
+                }
+            })
+            .collect();
+
+        Tracer {
+            scene: Arc::new(scene),
+            image_buffer: Arc::new(Mutex::new(image_buffer)),
+            views,
+            normalization,
+            status: Arc::new(RenderStatus::default()),
             n_threads: self.n_threads,
+            pool,
+            queue: Arc::new(ChunkQueue::default()),
+            in_flight: 0,
+            target_samples: self.target_samples,
+            time_limit: self.time_limit,
+            timed_out: false,
+            regions: self.regions,
+            ev100: self.ev100,
+            strategy: self.strategy,
+            accel_kind: self.accel,
+            tonemap: self.tonemap,
+            outline: self.outline,
+            palette: self.palette,
+            post_chain: self.post_chain,
+            origin: self.origin,
+            supersample: self.supersample,
+            downsample: self.downsample,
+            file: self.file,
+            watcher,
+            denoised: None,
+            progressive: Mutex::new(HashMap::new()),
+            denoiser: self.denoiser,
+            #[cfg(feature = "oidn")]
+            auto_denoise: self.auto_denoise,
+            preview_mode: PreviewMode::COLOR,
+            debug_view: DebugView::NONE,
+            preview_changed: false,
+            full_refresh: true,
+            fast_preview: self.fast_preview,
+            preview_pass: false,
+            version: 0,
+            pre_pass_hooks: self.pre_pass_hooks,
+            post_pass_hooks: self.post_pass_hooks,
+            pass: 0,
+            pass_started: false,
+            stopped: false,
+            paused: false,
+            pass_time: 0.,
             changed: true,
+            pass_pixels: 0,
+            progress_hook: self.progress_hook,
+            snapshots: Vec::new(),
+            frame_budget: self.frame_budget,
+            pacing,
+            adaptive_threads: self.thread_bounds.is_some(),
+            last_update: None,
+            frame_stats: FrameStats::default(),
+            rng_stream: self.rng_stream,
             timer: Timer::new(),
+            render_time: 0.,
+            resume,
+            coordinator: None,
+            #[cfg(feature = "gpu")]
+            gpu,
+            #[cfg(feature = "gpu")]
+            gpu_pass: None,
+            #[cfg(feature = "video")]
+            video: None,
         }
     }
 }
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use glam::Vec3;
+
+use crate::raytracer::{Color, Extent, Integrator, Material, Ray, Sphere, TracerBuilder};
+
+#[derive(Clone, Copy, Debug)]
+pub struct FurnaceReport {
+    pub expected: f32,
+    pub mean: f32,
+    pub max_error: f32,
+}
+
+impl FurnaceReport {
+    pub fn passed(&self, tolerance: f32) -> bool {
+        self.max_error <= tolerance
+    }
+}
+
+fn furnace_background(_: &Ray) -> Color {
+    Color::WHITE
+}
+
+// white furnace test: a white object lit only by a uniform white environment must
+// disappear into the background if the material conserves energy, WHITTED only
+// carries the environment along the mirror reflections
+pub async fn white_furnace(material: Material, integrator: Integrator) -> FurnaceReport {
+    let extent = Extent::new(64, 32);
+    let expected = 1.;
+
+    let material = Material {
+        albedo: Arc::new(Color::WHITE),
+        ..material
+    };
+
+    let mut tracer = TracerBuilder::new(extent)
+        .await
+        .model(Sphere::with_material(
+            "furnace",
+            Vec3::new(0., 0.2, 2.),
+            0.5,
+            material,
+        ))
+        .background(furnace_background)
+        .ambient(0.)
+        .integrator(integrator)
+        .rays(4)
+        .target_samples(4)
+        .build()
+        .await;
+
+    while !tracer.is_complete() {
+        tracer.update();
+    }
+
+    let framebuffer = tracer.hdr_framebuffer();
+
+    let errors = framebuffer
+        .iter()
+        .flat_map(|c| [c.r, c.g, c.b])
+        .map(|v| (v - expected).abs())
+        .collect::<Vec<f32>>();
+
+    let report = FurnaceReport {
+        expected,
+        mean: framebuffer
+            .iter()
+            .map(|c| (c.r + c.g + c.b) / 3.)
+            .sum::<f32>()
+            / framebuffer.len() as f32,
+        max_error: errors.iter().copied().fold(0., f32::max),
+    };
+
+    log::info!("White furnace: {:?}", report);
+
+    report
+}
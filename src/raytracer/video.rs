@@ -0,0 +1,85 @@
+use std::{
+    io::{self, BufWriter, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+use crate::raytracer::Extent;
+
+// frames piped to an ffmpeg process, the codec is given by the extension: vp9 for
+// .webm, h264 otherwise, see Tracer::record_video and Animation::render_video
+pub struct VideoEncoder {
+    child: Child,
+    stdin: Option<BufWriter<ChildStdin>>,
+    extent: Extent,
+    frames: u32,
+}
+
+impl VideoEncoder {
+    pub fn new(path: &str, extent: Extent, fps: f32) -> io::Result<Self> {
+        let codec = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("webm") => "libvpx-vp9",
+            _ => "libx264",
+        };
+
+        let mut child = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{}x{}", extent.width, extent.height)])
+            .args(["-r", &fps.to_string(), "-i", "-"])
+            // yuv420p needs even sizes
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+            .args(["-c:v", codec, "-pix_fmt", "yuv420p", path])
+            .stdin(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().map(BufWriter::new);
+
+        log::info!("Recording {} with {}", path, codec);
+
+        Ok(Self {
+            child,
+            stdin,
+            extent,
+            frames: 0,
+        })
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    // 8 bits rgba, top row first, see Tracer::bytes
+    pub fn add_frame(&mut self, rgba: &[u8]) -> io::Result<()> {
+        if rgba.len() != self.extent.size() as usize * 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "frame size does not match the video",
+            ));
+        }
+
+        let Some(stdin) = &mut self.stdin else {
+            return Err(io::Error::other("video already finished"));
+        };
+        stdin.write_all(rgba)?;
+        self.frames += 1;
+
+        Ok(())
+    }
+
+    // waits for ffmpeg to write the file
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(mut stdin) = self.stdin.take() {
+            stdin.flush()?;
+        }
+
+        let status = self.child.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("ffmpeg failed: {}", status)));
+        }
+
+        log::info!("Video done: {} frames", self.frames);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use std::{fs, io};
+
+use glam::{UVec3, Vec2, Vec3};
+
+use crate::raytracer::{aabb::Aabb, hit::Differentials, Color, Hit, Hitable, Primitive, Ray};
+
+// density grid filling a box, e.g. a cloud or smoke, rendered as a medium: the rays
+// collide with it at distances sampled by delta tracking and scatter there with an
+// isotropic phase function, the shadow rays are attenuated by the transmittance
+pub struct VoxelVolume {
+    name: String,
+    size: UVec3,
+    density: Vec<f32>,
+    // majorant of the density
+    max_density: f32,
+    bounds: Aabb,
+    // extinction for a density of 1, per unit of length
+    sigma: f32,
+    albedo: Color,
+}
+
+impl VoxelVolume {
+    pub fn new(name: &str, size: UVec3, density: Vec<f32>, bounds: Aabb) -> io::Result<Self> {
+        if Self::voxels(size) != Some(density.len()) || density.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}: {} densities for a {:?} grid",
+                    name,
+                    density.len(),
+                    size
+                ),
+            ));
+        }
+
+        let max_density = density.iter().copied().fold(0., f32::max);
+
+        Ok(Self {
+            name: name.to_string(),
+            size,
+            density,
+            max_density,
+            bounds,
+            sigma: 1.,
+            albedo: Color::WHITE,
+        })
+    }
+
+    // number of voxels of the grid, None if it doesn't fit in memory
+    fn voxels(size: UVec3) -> Option<usize> {
+        (size.x as usize)
+            .checked_mul(size.y as usize)?
+            .checked_mul(size.z as usize)
+    }
+
+    // little endian f32 densities, x varies first, then y and z
+    pub fn load_raw(name: &str, path: &str, size: UVec3, bounds: Aabb) -> io::Result<Self> {
+        let data = fs::read(path)?;
+
+        if Self::voxels(size).and_then(|n| n.checked_mul(4)) != Some(data.len()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {} bytes for a {:?} grid", path, data.len(), size),
+            ));
+        }
+
+        let density = data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]).max(0.))
+            .collect();
+
+        log::info!("Volume loaded: {} ({:?})", path, size);
+
+        Self::new(name, size, density, bounds)
+    }
+
+    pub fn sigma(mut self, sigma: f32) -> Self {
+        self.sigma = sigma;
+
+        self
+    }
+
+    pub fn albedo(mut self, albedo: Color) -> Self {
+        self.albedo = albedo;
+
+        self
+    }
+
+    // nearest voxel, 0 outside of the grid
+    fn density(&self, p: Vec3) -> f32 {
+        let uvw = (p - self.bounds.min) / self.bounds.extent();
+        if uvw.min_element() < 0. || uvw.max_element() >= 1. {
+            return 0.;
+        }
+
+        let voxel = (uvw * self.size.as_vec3()).as_uvec3().min(self.size - 1);
+        let (x, y, z) = (voxel.x as usize, voxel.y as usize, voxel.z as usize);
+        let (width, height) = (self.size.x as usize, self.size.y as usize);
+
+        self.density[x + (y + z * height) * width]
+    }
+
+    // fraction of the light going through the volume between min and max, estimated
+    // by ratio tracking
+    fn ratio_tracking(&self, ray: &Ray, min: f32, max: f32) -> f32 {
+        let majorant = self.max_density * self.sigma;
+        let Some((t_enter, t_exit)) = self.bounds.hit(ray, min, max) else {
+            return 1.;
+        };
+        if majorant <= 0. {
+            return 1.;
+        }
+
+        let mut rng = TrackingRng::new(ray);
+        let mut transmittance = 1.;
+        let mut t = t_enter;
+
+        loop {
+            t -= (1. - rng.next()).ln() / majorant;
+            if t >= t_exit {
+                return transmittance;
+            }
+
+            let density = self.density(ray.origin + t * ray.direction);
+            transmittance *= 1. - density * self.sigma / majorant;
+        }
+    }
+
+    // distance of a collision with the medium, sampled by delta tracking
+    fn collision(&self, ray: &Ray, min: f32, max: f32) -> Option<f32> {
+        let majorant = self.max_density * self.sigma;
+        let (t_enter, t_exit) = self.bounds.hit(ray, min, max)?;
+        if majorant <= 0. {
+            return None;
+        }
+
+        let mut rng = TrackingRng::new(ray);
+        let mut t = t_enter;
+
+        loop {
+            t -= (1. - rng.next()).ln() / majorant;
+            if t >= t_exit {
+                return None;
+            }
+
+            let density = self.density(ray.origin + t * ray.direction);
+            if rng.next() < density * self.sigma / majorant {
+                return Some(t);
+            }
+        }
+    }
+}
+
+impl From<VoxelVolume> for Primitive {
+    fn from(volume: VoxelVolume) -> Self {
+        Primitive::VOLUME(volume)
+    }
+}
+
+impl Hitable for VoxelVolume {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    // the shadow rays go through the medium, see transmittance
+    fn hit_distance(&self, _ray: &Ray, _min: f32, _max: f32) -> Option<f32> {
+        None
+    }
+
+    fn transmittance(&self, ray: &Ray, min: f32, max: f32) -> f32 {
+        self.ratio_tracking(ray, min, max)
+    }
+
+    fn is_medium(&self) -> bool {
+        true
+    }
+
+    fn hit(&self, ray: &Ray, min: f32, max: f32) -> Option<Hit> {
+        let t = self.collision(ray, min, max)?;
+
+        Some(Hit {
+            distance: t,
+            position: ray.origin + t * ray.direction,
+            normal: -ray.direction,
+            uv: Vec2::ZERO,
+            color: self.albedo,
+            reflect: 0.,
+            clearcoat: None,
+            emission: Color::new(0., 0., 0., 0.),
+            differentials: Differentials::default(),
+            front_face: true,
+            medium: true,
+            id: 0,
+        })
+    }
+
+    // the optical depth of the volume is kept
+    fn rescale(&mut self, center: Vec3, scale: f32) -> bool {
+        self.bounds = Aabb::new(
+            (self.bounds.min - center) * scale,
+            (self.bounds.max - center) * scale,
+        );
+        self.sigma /= scale;
+
+        true
+    }
+}
+
+// random numbers of the tracking, given by the ray since Hitable has no sampler:
+// the primary rays are jittered and the secondary rays start at random points
+struct TrackingRng(u32);
+
+impl TrackingRng {
+    fn new(ray: &Ray) -> Self {
+        let mut h = 0x9e3779b9_u32;
+        for v in ray
+            .origin
+            .to_array()
+            .iter()
+            .chain(&ray.direction.to_array())
+        {
+            h = (h ^ v.to_bits()).wrapping_mul(0x85ebca6b);
+            h ^= h >> 13;
+        }
+
+        Self(h | 1)
+    }
+
+    // xorshift
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+// flags changes to a file, the parent directory is watched so that editors
+// saving through a rename are still detected
+pub struct FileWatcher {
+    path: PathBuf,
+    changed: Arc<AtomicBool>,
+    _watcher: RecommendedWatcher,
+}
+
+impl FileWatcher {
+    pub fn new(path: &str) -> notify::Result<Self> {
+        let path = Path::new(path).to_path_buf();
+        let name = path.file_name().map(|name| name.to_os_string());
+        let changed = Arc::new(AtomicBool::new(false));
+
+        let flag = changed.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+
+            if !(event.kind.is_modify() || event.kind.is_create()) {
+                return;
+            }
+
+            if event.paths.iter().any(|p| p.file_name() == name.as_deref()) {
+                flag.store(true, Ordering::Relaxed);
+            }
+        })?;
+
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+        log::info!("Watching {}", path.display());
+
+        Ok(Self {
+            path,
+            changed,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // true once after each change
+    pub fn changed(&self) -> bool {
+        self.changed.swap(false, Ordering::Relaxed)
+    }
+}
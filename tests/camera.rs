@@ -0,0 +1,80 @@
+// the image plane follows the shape of the image: a sphere in front of the camera
+// covers as many pixels horizontally as vertically
+
+use glam::Vec3;
+
+use raytracer::prelude::{Camera, Color, Extent, Sphere, Tracer, TracerBuilder};
+
+// the edge of the sphere is at atan(0.2) from the center of the image
+const DISTANCE: f32 = 5.;
+const RADIUS: f32 = 1.;
+
+fn tracer(width: u32, height: u32) -> Tracer {
+    let camera = Camera::perspective(
+        Vec3::ZERO,
+        width as f32 / height as f32,
+        90_f32.to_radians(),
+        0.1,
+        100.,
+        0.,
+        0.,
+        Vec3::Y,
+    );
+
+    pollster::block_on(
+        pollster::block_on(TracerBuilder::new(Extent::new(width, height)))
+            .camera(camera)
+            .model(Sphere::new(
+                "sphere",
+                Vec3::new(0., 0., DISTANCE),
+                RADIUS,
+                Color::WHITE,
+                0.,
+            ))
+            .build(),
+    )
+}
+
+// pixels from the center of the image to the edge of the sphere, the vertical
+// field of view is 90 degrees
+fn edge(height: u32) -> f32 {
+    let tan = RADIUS / (DISTANCE * DISTANCE - RADIUS * RADIUS).sqrt();
+
+    tan * height as f32 / 2.
+}
+
+fn check_symmetric(width: u32, height: u32) {
+    let tracer = tracer(width, height);
+    let (cx, cy) = (width / 2, height / 2);
+    let edge = edge(height);
+
+    let inside = (edge * 0.8) as u32;
+    let outside = (edge * 1.2).ceil() as u32;
+
+    assert_eq!(tracer.pick(cx, cy), Some("sphere"));
+
+    assert_eq!(tracer.pick(cx + inside, cy), Some("sphere"), "right");
+    assert_eq!(tracer.pick(cx - inside, cy), Some("sphere"), "left");
+    assert_eq!(tracer.pick(cx, cy + inside), Some("sphere"), "below");
+    assert_eq!(tracer.pick(cx, cy - inside), Some("sphere"), "above");
+
+    assert_eq!(tracer.pick(cx + outside, cy), None, "right");
+    assert_eq!(tracer.pick(cx - outside, cy), None, "left");
+    assert_eq!(tracer.pick(cx, cy + outside), None, "below");
+    assert_eq!(tracer.pick(cx, cy - outside), None, "above");
+}
+
+#[test]
+fn square() {
+    check_symmetric(100, 100);
+}
+
+#[test]
+fn portrait() {
+    check_symmetric(60, 120);
+}
+
+#[test]
+fn landscape() {
+    check_symmetric(160, 90);
+}
@@ -0,0 +1,110 @@
+// a render resumed from a checkpoint goes on with the same samples
+
+use std::fs;
+
+use glam::Vec3;
+
+use raytracer::prelude::{
+    Camera, Color, Extent, LightPower, PointLight, Sphere, Tracer, TracerBuilder,
+};
+
+fn builder(extent: Extent) -> TracerBuilder {
+    let camera = Camera::perspective(
+        Vec3::new(0., 1., -4.),
+        extent.width as f32 / extent.height as f32,
+        60_f32.to_radians(),
+        0.1,
+        100.,
+        0.,
+        -0.3,
+        Vec3::Y,
+    );
+
+    pollster::block_on(TracerBuilder::new(extent))
+        .camera(camera)
+        .model(Sphere::new(
+            "ground",
+            Vec3::new(0., -100., 0.),
+            100.,
+            Color::WHITE,
+            0.,
+        ))
+        .model(Sphere::new(
+            "ball",
+            Vec3::new(0., 0.5, 0.),
+            0.5,
+            Color::new(0.8, 0.2, 0.2, 1.),
+            0.,
+        ))
+        .light(PointLight::new(
+            Vec3::new(2., 4., -2.),
+            Color::WHITE,
+            LightPower::LUMENS(2000.),
+        ))
+        .threads(1)
+        .rays(1)
+        .target_samples(2)
+        .deterministic(1)
+}
+
+fn render(tracer: &mut Tracer) {
+    while !tracer.is_complete() {
+        tracer.update();
+    }
+}
+
+fn path(name: &str) -> String {
+    let dir = std::env::temp_dir().join("raytracer-checkpoint");
+    fs::create_dir_all(&dir).unwrap();
+
+    dir.join(name).to_string_lossy().into_owned()
+}
+
+#[test]
+fn round_trip() {
+    let extent = Extent::new(32, 24);
+    let path = path("round_trip.ckp");
+
+    let mut tracer = pollster::block_on(builder(extent).build());
+    render(&mut tracer);
+    tracer.save_checkpoint(&path).unwrap();
+
+    let mut resumed = pollster::block_on(builder(extent).resume_from(&path).build());
+    resumed.update();
+
+    assert!(resumed.is_complete());
+    assert_eq!(resumed.samples_per_pixel(), tracer.samples_per_pixel());
+    assert_eq!(resumed.hdr_framebuffer(), tracer.hdr_framebuffer());
+}
+
+#[test]
+fn other_extent() {
+    let path = path("other_extent.ckp");
+
+    let mut tracer = pollster::block_on(builder(Extent::new(32, 24)).build());
+    render(&mut tracer);
+    tracer.save_checkpoint(&path).unwrap();
+
+    let mut resumed = pollster::block_on(builder(Extent::new(16, 12)).resume_from(&path).build());
+    resumed.update();
+
+    assert_eq!(resumed.samples_per_pixel(), 0);
+}
+
+#[test]
+fn truncated() {
+    let extent = Extent::new(32, 24);
+    let path = path("truncated.ckp");
+
+    let mut tracer = pollster::block_on(builder(extent).build());
+    render(&mut tracer);
+    tracer.save_checkpoint(&path).unwrap();
+
+    let bytes = fs::read(&path).unwrap();
+    fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+
+    let mut resumed = pollster::block_on(builder(extent).resume_from(&path).build());
+    resumed.update();
+
+    assert_eq!(resumed.samples_per_pixel(), 0);
+}
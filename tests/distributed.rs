@@ -0,0 +1,133 @@
+// the samples of a worker are checked before they are accumulated: a chunk sent
+// back with NaN colors is rendered by the coordinator instead, as without workers
+
+use std::{
+    io::{BufReader, BufWriter, Read, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use glam::Vec3;
+
+use raytracer::prelude::{
+    ChunkStrategy, Color, Extent, LightPower, PointLight, Sphere, Tracer, TracerBuilder,
+};
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+
+fn tracer() -> Tracer {
+    pollster::block_on(
+        pollster::block_on(TracerBuilder::new(Extent::new(WIDTH, HEIGHT)))
+            .model(Sphere::new(
+                "sphere",
+                Vec3::new(0., 0., 3.),
+                1.,
+                Color::WHITE,
+                0.,
+            ))
+            .light(PointLight::new(
+                Vec3::new(0., 2., 0.),
+                Color::WHITE,
+                LightPower::LUMENS(100.),
+            ))
+            .strategy(ChunkStrategy::RANDOM { pixels: 8 })
+            .threads(1)
+            .rays(1)
+            .target_samples(16)
+            .deterministic(1)
+            .build(),
+    )
+}
+
+fn read_u32(reader: &mut impl Read) -> Option<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes).ok()?;
+
+    Some(u32::from_le_bytes(bytes))
+}
+
+// answers each chunk with NaN samples until the coordinator hangs up, returns the
+// number of chunks answered
+fn nan_worker(address: SocketAddr) -> u32 {
+    let stream = TcpStream::connect(address).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = BufWriter::new(stream);
+
+    writer.write_all(b"RTD2").unwrap();
+    for v in [WIDTH, HEIGHT, 1] {
+        writer.write_all(&v.to_le_bytes()).unwrap();
+    }
+    writer.flush().unwrap();
+
+    let mut chunks = 0;
+    // camera, first sample, seed and preview
+    let mut header = [0; 17];
+    while reader.read_exact(&mut header).is_ok() {
+        let Some(count) = read_u32(&mut reader) else {
+            break;
+        };
+        let pixels = (0..count)
+            .map_while(|_| read_u32(&mut reader))
+            .collect::<Vec<_>>();
+
+        let mut reply = (pixels.len() as u32).to_le_bytes().to_vec();
+        for idx in pixels {
+            reply.extend(idx.to_le_bytes());
+            // color, weight, luminance and luminance squared
+            for v in [f32::NAN, 0., 0., 1., 1., 0., 0.] {
+                reply.extend(v.to_le_bytes());
+            }
+            // count, clamped, invalid
+            for v in [1_u32, 0, 0] {
+                reply.extend(v.to_le_bytes());
+            }
+            // preview, splat
+            reply.extend([0, 0]);
+            reply.extend(1_f32.to_le_bytes());
+            reply.extend(0_u32.to_le_bytes());
+            // normal, albedo
+            for v in [0_f32, 0., 1., 1., 1., 1., 1.] {
+                reply.extend(v.to_le_bytes());
+            }
+        }
+        if writer
+            .write_all(&reply)
+            .and_then(|_| writer.flush())
+            .is_err()
+        {
+            break;
+        }
+        chunks += 1;
+    }
+
+    chunks
+}
+
+fn render(tracer: &mut Tracer) -> Vec<Color> {
+    while !tracer.is_idle() {
+        tracer.update();
+    }
+
+    tracer.framebuffer()
+}
+
+#[test]
+fn nan_samples() {
+    let expected = render(&mut tracer());
+
+    let mut tracer = tracer();
+    let address = tracer.serve("127.0.0.1:0").unwrap();
+
+    let worker = thread::spawn(move || nan_worker(address));
+    while tracer.coordinator().unwrap().workers() == 0 {
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    let framebuffer = render(&mut tracer);
+    drop(tracer);
+
+    assert!(worker.join().unwrap() > 0, "no chunk sent to the worker");
+    assert_eq!(framebuffer, expected);
+}
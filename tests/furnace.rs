@@ -0,0 +1,33 @@
+// a white object lit only by the white background disappears into it
+
+use std::sync::Arc;
+
+use raytracer::prelude::{Color, Integrator, Material};
+use raytracer::raytracer::white_furnace;
+
+// the bounces off a convex object always escape, only the rounding is left
+const TOLERANCE: f32 = 0.02;
+
+fn furnace(material: Material, integrator: Integrator) {
+    let report = pollster::block_on(white_furnace(material, integrator));
+
+    assert!(report.passed(TOLERANCE), "{:?}: {:?}", integrator, report);
+}
+
+#[test]
+fn whitted_mirror() {
+    furnace(
+        Material::new(Arc::new(Color::WHITE), 1.),
+        Integrator::WHITTED,
+    );
+}
+
+#[test]
+fn path_diffuse() {
+    furnace(Material::new(Arc::new(Color::WHITE), 0.), Integrator::PATH);
+}
+
+#[test]
+fn path_mirror() {
+    furnace(Material::new(Arc::new(Color::WHITE), 1.), Integrator::PATH);
+}
@@ -0,0 +1,161 @@
+// renders small scenes with fixed seeds on one thread and compares them to the
+// reference images of tests/golden, the references are written again with:
+// RAYTRACER_UPDATE_GOLDEN=1 cargo test --test golden
+
+use std::{env, fs, path::PathBuf};
+
+use image::RgbImage;
+
+use raytracer::{
+    prelude::{Extent, TracerBuilder},
+    raytracer::BenchScene,
+};
+
+const UPDATE: &str = "RAYTRACER_UPDATE_GOLDEN";
+// db
+const MIN_PSNR: f64 = 30.;
+const MIN_SSIM: f64 = 0.95;
+
+fn render(builder: TracerBuilder) -> RgbImage {
+    let mut tracer = pollster::block_on(
+        builder
+            .threads(1)
+            .rays(4)
+            .target_samples(8)
+            .deterministic(1)
+            .build(),
+    );
+
+    while !tracer.is_complete() {
+        tracer.update();
+    }
+
+    let extent = tracer.extent();
+    let rgb = tracer
+        .bytes()
+        .chunks_exact(4)
+        .flat_map(|c| [c[0], c[1], c[2]])
+        .collect();
+
+    RgbImage::from_raw(extent.width, extent.height, rgb).unwrap()
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.png", name))
+}
+
+fn check(name: &str, image: RgbImage) {
+    let path = golden_path(name);
+
+    if env::var_os(UPDATE).is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        image.save(&path).unwrap();
+        return;
+    }
+
+    assert!(
+        path.exists(),
+        "{}: no reference at {}, see {}",
+        name,
+        path.display(),
+        UPDATE
+    );
+
+    let golden = image::open(&path).unwrap().to_rgb8();
+    assert_eq!(
+        golden.dimensions(),
+        image.dimensions(),
+        "{}: size changed",
+        name
+    );
+
+    let (psnr, ssim) = (psnr(&golden, &image), ssim(&golden, &image));
+    if psnr < MIN_PSNR || ssim < MIN_SSIM {
+        // kept next to the reference to compare them
+        let failed = path.with_extension("failed.png");
+        image.save(&failed).unwrap();
+
+        panic!(
+            "{}: psnr {:.2} db, ssim {:.4}, image written to {}",
+            name,
+            psnr,
+            ssim,
+            failed.display()
+        );
+    }
+}
+
+fn psnr(a: &RgbImage, b: &RgbImage) -> f64 {
+    let mse = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&x, &y)| (x as f64 - y as f64).powi(2))
+        .sum::<f64>()
+        / a.as_raw().len() as f64;
+
+    10. * (255. * 255. / mse).log10()
+}
+
+// mean of the ssim of the luminance over 8x8 windows
+fn ssim(a: &RgbImage, b: &RgbImage) -> f64 {
+    const WINDOW: u32 = 8;
+    const C1: f64 = (0.01 * 255.) * (0.01 * 255.);
+    const C2: f64 = (0.03 * 255.) * (0.03 * 255.);
+
+    let luminance = |image: &RgbImage, x, y| {
+        let p = image.get_pixel(x, y);
+        0.2126 * p[0] as f64 + 0.7152 * p[1] as f64 + 0.0722 * p[2] as f64
+    };
+
+    let (width, height) = a.dimensions();
+    let mut total = 0.;
+    let mut windows = 0;
+
+    for wy in (0..height.saturating_sub(WINDOW - 1)).step_by(WINDOW as usize) {
+        for wx in (0..width.saturating_sub(WINDOW - 1)).step_by(WINDOW as usize) {
+            let pixels = (wy..wy + WINDOW)
+                .flat_map(|y| (wx..wx + WINDOW).map(move |x| (x, y)))
+                .map(|(x, y)| (luminance(a, x, y), luminance(b, x, y)))
+                .collect::<Vec<_>>();
+            let n = pixels.len() as f64;
+
+            let mean_a = pixels.iter().map(|p| p.0).sum::<f64>() / n;
+            let mean_b = pixels.iter().map(|p| p.1).sum::<f64>() / n;
+            let (mut var_a, mut var_b, mut cov) = (0., 0., 0.);
+            for (x, y) in &pixels {
+                var_a += (x - mean_a).powi(2) / n;
+                var_b += (y - mean_b).powi(2) / n;
+                cov += (x - mean_a) * (y - mean_b) / n;
+            }
+
+            total += ((2. * mean_a * mean_b + C1) * (2. * cov + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+
+    total / windows as f64
+}
+
+fn scene(scene: BenchScene) -> TracerBuilder {
+    pollster::block_on(scene.builder(Extent::new(96, 54)))
+}
+
+#[test]
+fn sphere_field() {
+    check("sphere_field", render(scene(BenchScene::SPHEREFIELD)));
+}
+
+#[test]
+fn cornell_box() {
+    check("cornell_box", render(scene(BenchScene::CORNELLBOX)));
+}
+
+#[test]
+fn random_spheres() {
+    check("random_spheres", render(scene(BenchScene::RANDOMSPHERES)));
+}
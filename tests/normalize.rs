@@ -0,0 +1,90 @@
+// normalize_scale only changes the units the scene is traced in, the image stays
+// the same, including the textures sampled by position
+
+use std::sync::Arc;
+
+use glam::Vec3;
+
+use raytracer::prelude::{
+    Camera, CheckerTexture, Color, Extent, LightPower, PointLight, Sphere, TracerBuilder,
+};
+
+// part of the pixels allowed to differ, e.g. on the edges of the checker
+const MAX_DIFFERENT: f32 = 0.02;
+
+fn render(normalize: bool) -> Vec<u8> {
+    let (width, height) = (64, 48);
+    let checker = Arc::new(CheckerTexture::new(
+        Arc::new(Color::WHITE),
+        Arc::new(Color::new(0.1, 0.1, 0.1, 1.)),
+        0.25,
+    ));
+
+    let camera = Camera::perspective(
+        Vec3::new(0., 1., -4.),
+        width as f32 / height as f32,
+        60_f32.to_radians(),
+        0.1,
+        100.,
+        0.,
+        -0.3,
+        Vec3::Y,
+    );
+
+    let builder = pollster::block_on(TracerBuilder::new(Extent::new(width, height)))
+        .camera(camera)
+        .model(Sphere::textured(
+            "ground",
+            Vec3::new(0., -100., 0.),
+            100.,
+            checker.clone(),
+            0.,
+        ))
+        .model(Sphere::textured(
+            "ball",
+            Vec3::new(0., 0.5, 0.),
+            0.5,
+            checker,
+            0.,
+        ))
+        .light(PointLight::new(
+            Vec3::new(2., 4., -2.),
+            Color::WHITE,
+            LightPower::LUMENS(2000.),
+        ))
+        .threads(1)
+        .rays(1)
+        .target_samples(1)
+        .deterministic(1);
+
+    let builder = if normalize {
+        builder.normalize_scale()
+    } else {
+        builder
+    };
+
+    let mut tracer = pollster::block_on(builder.build());
+    while !tracer.is_complete() {
+        tracer.update();
+    }
+
+    tracer.bytes()
+}
+
+#[test]
+fn checker() {
+    let (a, b) = (render(false), render(true));
+
+    let different = a
+        .chunks_exact(4)
+        .zip(b.chunks_exact(4))
+        .filter(|(a, b)| a.iter().zip(b.iter()).any(|(x, y)| x.abs_diff(*y) > 8))
+        .count();
+
+    let ratio = different as f32 / (a.len() / 4) as f32;
+    assert!(
+        ratio <= MAX_DIFFERENT,
+        "{:.1}% of the pixels differ",
+        ratio * 100.
+    );
+}
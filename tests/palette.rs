@@ -0,0 +1,19 @@
+// a palette needs colors, whether it is built in code or read from a config
+
+use raytracer::raytracer::{Dither, Palette};
+
+#[test]
+fn empty_palette() {
+    assert!(Palette::new(Vec::new()).is_err());
+    assert!(toml::from_str::<Palette>("colors = []").is_err());
+}
+
+#[test]
+fn config_palette() {
+    let palette =
+        toml::from_str::<Palette>("colors = [[0, 0, 0], [1, 1, 1]]\ndither = \"ORDERED\"").unwrap();
+
+    assert_eq!(palette.colors.len(), 2);
+    assert_eq!(palette.dither, Dither::ORDERED);
+    assert_eq!(palette.spread, 0.125);
+}
@@ -0,0 +1,140 @@
+// the pixels of an invalidated region are rendered again up to the target samples,
+// once even when the regions overlap, and by the added cameras too
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32, Ordering},
+    Arc,
+};
+
+use glam::Vec3;
+
+use raytracer::prelude::{
+    Camera, Color, Extent, LightPower, PointLight, Sphere, Tracer, TracerBuilder,
+};
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+const SAMPLES: u32 = 4;
+
+// samples computed for each pixel while counting
+struct Counts {
+    counting: AtomicBool,
+    samples: Vec<AtomicU32>,
+}
+
+impl Counts {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            counting: AtomicBool::new(false),
+            samples: (0..WIDTH * HEIGHT).map(|_| AtomicU32::new(0)).collect(),
+        })
+    }
+}
+
+fn tracer(counts: Arc<Counts>, views: &[&str]) -> Tracer {
+    let mut builder = pollster::block_on(TracerBuilder::new(Extent::new(WIDTH, HEIGHT)))
+        .model(Sphere::new(
+            "sphere",
+            Vec3::new(0., 0., 3.),
+            1.,
+            Color::WHITE,
+            0.,
+        ))
+        .light(PointLight::new(
+            Vec3::new(0., 2., 0.),
+            Color::WHITE,
+            LightPower::LUMENS(100.),
+        ))
+        .payload(move |idx, _sample| {
+            if counts.counting.load(Ordering::Relaxed) {
+                counts.samples[idx].fetch_add(1, Ordering::Relaxed);
+            }
+            Box::new(())
+        });
+
+    for name in views {
+        let camera = Camera::perspective(
+            Vec3::new(2., 0., 0.),
+            WIDTH as f32 / HEIGHT as f32,
+            60_f32.to_radians(),
+            0.1,
+            100.,
+            -30_f32.to_radians(),
+            0.,
+            Vec3::Y,
+        );
+        builder = builder.add_camera(name, camera);
+    }
+
+    pollster::block_on(
+        builder
+            .threads(1)
+            .rays(1)
+            .target_samples(SAMPLES)
+            .deterministic(1)
+            .build(),
+    )
+}
+
+fn render(tracer: &mut Tracer) {
+    while !tracer.is_idle() {
+        tracer.update();
+    }
+}
+
+fn contains(rect: (u32, u32, u32, u32), idx: usize) -> bool {
+    let (x, y) = (idx as u32 % WIDTH, idx as u32 / WIDTH);
+
+    x >= rect.0 && x < rect.0 + rect.2 && y >= rect.1 && y < rect.1 + rect.3
+}
+
+#[test]
+fn overlapping_regions() {
+    let counts = Counts::new();
+
+    let mut tracer = tracer(counts.clone(), &[]);
+    render(&mut tracer);
+
+    let (a, b) = ((4, 4, 12, 12), (8, 8, 12, 12));
+    tracer.invalidate_region(a.0, a.1, a.2, a.3);
+    tracer.invalidate_region(b.0, b.1, b.2, b.3);
+
+    counts.counting.store(true, Ordering::Relaxed);
+    render(&mut tracer);
+
+    for (idx, samples) in counts.samples.iter().enumerate() {
+        let expected = if contains(a, idx) || contains(b, idx) {
+            SAMPLES
+        } else {
+            0
+        };
+
+        assert_eq!(samples.load(Ordering::Relaxed), expected, "pixel {}", idx);
+    }
+}
+
+#[test]
+fn region_with_view() {
+    let counts = Counts::new();
+
+    let mut tracer = tracer(counts.clone(), &["side"]);
+    render(&mut tracer);
+
+    let rect = (4, 4, 12, 12);
+    tracer.invalidate_region(rect.0, rect.1, rect.2, rect.3);
+
+    counts.counting.store(true, Ordering::Relaxed);
+    render(&mut tracer);
+
+    // the rectangle is rendered again by both cameras
+    for (idx, samples) in counts.samples.iter().enumerate() {
+        if contains(rect, idx) {
+            assert_eq!(
+                samples.load(Ordering::Relaxed),
+                2 * SAMPLES,
+                "pixel {}",
+                idx
+            );
+        }
+    }
+}
@@ -0,0 +1,54 @@
+// the sequences drive the bounces of the path tracer, the image converges to the
+// same light as with the random samplers, which the sequences replace
+
+use raytracer::prelude::{Extent, Integrator, SamplerKind};
+use raytracer::raytracer::BenchScene;
+
+// relative difference of the mean luminance
+const TOLERANCE: f32 = 0.05;
+
+fn mean_luminance(sampler: SamplerKind) -> f32 {
+    let builder = pollster::block_on(BenchScene::CORNELLBOX.builder(Extent::new(32, 24)));
+    let mut tracer = pollster::block_on(
+        builder
+            .integrator(Integrator::PATH)
+            .sampler(sampler)
+            .light_sampler(SamplerKind::RANDOM)
+            .threads(1)
+            .rays(16)
+            .target_samples(64)
+            .deterministic(1)
+            .build(),
+    );
+
+    while !tracer.is_complete() {
+        tracer.update();
+    }
+
+    let framebuffer = tracer.hdr_framebuffer();
+
+    let luminance = framebuffer
+        .iter()
+        .map(|c| 0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b)
+        .sum::<f32>();
+
+    luminance / framebuffer.len() as f32
+}
+
+#[test]
+fn sequences_converge() {
+    let reference = mean_luminance(SamplerKind::RANDOM);
+
+    for sampler in [SamplerKind::HALTON, SamplerKind::SOBOL] {
+        let luminance = mean_luminance(sampler);
+        let error = (luminance - reference).abs() / reference;
+
+        assert!(
+            error < TOLERANCE,
+            "{:?}: {} vs {}",
+            sampler,
+            luminance,
+            reference
+        );
+    }
+}
@@ -0,0 +1,134 @@
+// save_scene writes the state of the tracer back in the units of the builder, and
+// scene::load reads it back
+
+use std::fs;
+
+use glam::Vec3;
+
+use raytracer::prelude::{Camera, Tracer};
+use raytracer::raytracer::{scene, PrimitiveDesc, SceneFile};
+
+const EPSILON: f32 = 1e-4;
+
+fn tracer(normalize: bool) -> Tracer {
+    let mut file = SceneFile::load("scenes/spheres.toml").unwrap();
+    file.render.width = 32;
+    file.render.height = 18;
+    file.render.threads = 1;
+    // the ground is too large for the precision of the normalized units
+    file.primitives.retain(|p| p.name() != "ground");
+
+    let builder = pollster::block_on(file.builder()).unwrap();
+    let builder = if normalize {
+        builder.normalize_scale()
+    } else {
+        builder
+    };
+
+    pollster::block_on(builder.build())
+}
+
+fn path(name: &str) -> String {
+    let dir = std::env::temp_dir().join("raytracer-scene-file");
+    fs::create_dir_all(&dir).unwrap();
+
+    dir.join(name).to_string_lossy().into_owned()
+}
+
+fn save(tracer: &Tracer, name: &str) -> SceneFile {
+    let path = path(name);
+
+    tracer.save_scene(&path).unwrap();
+
+    SceneFile::load(&path).unwrap()
+}
+
+fn assert_near(a: [f32; 3], b: [f32; 3]) {
+    assert!(
+        Vec3::from_array(a).abs_diff_eq(Vec3::from_array(b), EPSILON),
+        "{:?} != {:?}",
+        a,
+        b
+    );
+}
+
+fn center(file: &SceneFile, name: &str) -> ([f32; 3], f32) {
+    file.primitives
+        .iter()
+        .find_map(|p| match p {
+            PrimitiveDesc::SPHERE {
+                name: n,
+                center,
+                radius,
+                ..
+            } if n == name => Some((*center, *radius)),
+            _ => None,
+        })
+        .unwrap()
+}
+
+fn round_trip(normalize: bool) {
+    let mut tracer = tracer(normalize);
+    let before = save(&tracer, &format!("before-{}.toml", normalize));
+
+    let camera = Camera::perspective(
+        Vec3::new(1., 0.5, -2.),
+        16. / 9.,
+        50_f32.to_radians(),
+        0.1,
+        100.,
+        -60_f32.to_radians(),
+        -10_f32.to_radians(),
+        Vec3::Y,
+    );
+    tracer.set_camera(camera);
+    assert!(tracer.set_light_position(0, Vec3::new(1., 3., -1.)));
+    assert!(tracer.translate_model("red", Vec3::new(0., 0.5, 0.)));
+
+    let after = save(&tracer, &format!("after-{}.toml", normalize));
+
+    assert_near(after.camera.position, [1., 0.5, -2.]);
+    assert!((after.camera.yaw + 60.).abs() < EPSILON);
+    assert!((after.camera.pitch + 10.).abs() < EPSILON);
+    assert!((after.camera.fov - 50.).abs() < EPSILON);
+    assert!((after.camera.far - 100.).abs() < 1e-2);
+
+    assert_near(after.lights[0].position, [1., 3., -1.]);
+
+    let (red, radius) = center(&before, "red");
+    let (moved, moved_radius) = center(&after, "red");
+    assert_near(moved, [red[0], red[1] + 0.5, red[2]]);
+    assert!((moved_radius - radius).abs() < EPSILON);
+    assert_eq!(center(&after, "green"), center(&before, "green"));
+}
+
+#[test]
+fn unchanged() {
+    let original = tracer(true);
+    let file = save(&original, "unchanged.toml");
+    let reloaded = pollster::block_on(pollster::block_on(file.builder()).unwrap().build());
+
+    assert_eq!(save(&reloaded, "reloaded.toml"), file);
+}
+
+#[test]
+fn loaded() {
+    let file = save(&tracer(false), "saved.toml");
+    let path = path("copy.toml");
+    scene::save(&path, &file).unwrap();
+
+    let builder = pollster::block_on(scene::load(&path)).unwrap();
+    let loaded = pollster::block_on(builder.build());
+
+    assert_eq!(save(&loaded, "loaded.toml"), file);
+}
+
+#[test]
+fn moved() {
+    round_trip(false);
+}
+
+#[test]
+fn moved_normalized() {
+    round_trip(true);
+}
@@ -0,0 +1,78 @@
+// the density grid must match its size, including the sizes whose number of voxels
+// overflows, and the shadow rays through the grid follow the Beer-Lambert law
+
+use glam::{UVec3, Vec3};
+
+use raytracer::raytracer::{Aabb, Hitable, Ray, VoxelVolume};
+
+fn bounds() -> Aabb {
+    Aabb::new(Vec3::splat(-1.), Vec3::splat(1.))
+}
+
+#[test]
+fn matching_grid() {
+    let volume = VoxelVolume::new("cloud", UVec3::new(2, 3, 4), vec![0.5; 24], bounds());
+
+    assert!(volume.is_ok());
+}
+
+#[test]
+fn mismatched_grid() {
+    let volume = VoxelVolume::new("cloud", UVec3::new(2, 3, 4), vec![0.5; 23], bounds());
+
+    assert!(volume.is_err());
+}
+
+#[test]
+fn overflowing_grid() {
+    // the product wraps to 0 in u32
+    let size = UVec3::new(1 << 16, 1 << 16, 1);
+    assert_eq!(size.x.wrapping_mul(size.y), 0);
+
+    let volume = VoxelVolume::new("cloud", size, Vec::new(), bounds());
+
+    assert!(volume.is_err());
+}
+
+// mean transmittance of the rays crossing a slab of the given thickness along z,
+// each ratio tracking estimate is 0 or 1 in a constant medium
+fn slab_transmittance(density: f32, sigma: f32, thickness: f32) -> f32 {
+    let bounds = Aabb::new(Vec3::new(-1., -1., 0.), Vec3::new(1., 1., thickness));
+    let volume = VoxelVolume::new("slab", UVec3::splat(2), vec![density; 8], bounds)
+        .unwrap()
+        .sigma(sigma);
+
+    let n = 64;
+    let mut sum = 0.;
+    for i in 0..n {
+        for j in 0..n {
+            let x = (i as f32 + 0.5) / n as f32 * 2. - 1.;
+            let y = (j as f32 + 0.5) / n as f32 * 2. - 1.;
+            let ray = Ray::new(Vec3::new(x, y, -1.), Vec3::Z);
+
+            sum += volume.transmittance(&ray, 0., 100.);
+        }
+    }
+
+    sum / (n * n) as f32
+}
+
+#[test]
+fn constant_slab() {
+    let (density, sigma, thickness): (f32, f32, f32) = (0.5, 2., 0.75);
+    let expected = (-density * sigma * thickness).exp();
+
+    let transmittance = slab_transmittance(density, sigma, thickness);
+
+    assert!(
+        (transmittance - expected).abs() < 0.03,
+        "{} instead of {}",
+        transmittance,
+        expected
+    );
+}
+
+#[test]
+fn empty_slab() {
+    assert_eq!(slab_transmittance(0., 2., 0.75), 1.);
+}
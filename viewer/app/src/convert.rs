@@ -0,0 +1,17 @@
+use gobs::{core::Color as GobsColor, render::ImageExtent2D};
+
+use raytracer::prelude::{Color, Extent};
+
+// conversions to the gobs types, functions as neither side is local to the viewer
+
+pub fn gobs_color(color: Color) -> GobsColor {
+    GobsColor::new(color.r, color.g, color.b, color.a)
+}
+
+pub fn image_extent(extent: Extent) -> ImageExtent2D {
+    ImageExtent2D::new(extent.width, extent.height)
+}
+
+pub fn tracer_extent(extent: ImageExtent2D) -> Extent {
+    Extent::new(extent.width, extent.height)
+}
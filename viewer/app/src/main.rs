@@ -0,0 +1,283 @@
+mod convert;
+
+use std::sync::Arc;
+
+use glam::{Quat, Vec3};
+
+use gobs::{
+    core::{
+        entity::{camera::Camera as GobsCamera, light::Light},
+        Color as GobsColor, Transform,
+    },
+    game::{
+        app::{Application, Run},
+        input::{Input, Key},
+    },
+    render::{
+        context::Context,
+        geometry::{Model, VertexFlag},
+        graph::{FrameGraph, RenderError},
+        material::{Material, MaterialProperty, Texture, TextureType},
+        pass::PassType,
+        renderable::Renderable,
+        SamplerFilter,
+    },
+    scene::{graph::scenegraph::NodeValue, scene::Scene, shape::Shapes},
+    ui::UIRenderer,
+};
+
+use raytracer::prelude::{Color, ExportOptions, Extent, Tracer};
+
+use convert::{gobs_color, image_extent, tracer_extent};
+
+struct App {
+    pub graph: FrameGraph,
+    pub scene: Scene,
+    ui: UIRenderer,
+    tracer: Tracer,
+    // threads of the pool, the panel can only use less
+    max_threads: u32,
+    material: Arc<Material>,
+    // created with the first frame, then updated in place with the dirty tiles
+    texture: Option<Arc<Texture>>,
+    cursor: (u32, u32),
+    selected: Option<String>,
+}
+
+impl Run for App {
+    async fn create(ctx: &Context) -> Self {
+        log::info!("Create");
+
+        let graph = FrameGraph::default(ctx);
+
+        let light = Light::new((0., 0., 10.), GobsColor::WHITE);
+
+        let extent = ctx.surface.get_extent(ctx.device.clone());
+
+        let camera = GobsCamera::ortho(
+            (0., 0., 1.),
+            extent.width as f32,
+            extent.height as f32,
+            0.1,
+            100.,
+            0.,
+            0.,
+            Vec3::Y,
+        );
+
+        let scene = Scene::new(camera, light);
+
+        let tracer = raytracer_viewer::tracer(tracer_extent(extent)).await;
+
+        log::info!("Tracer config: {:?}", tracer.config());
+
+        let ui = UIRenderer::new(ctx, graph.pass_by_type(PassType::Ui).unwrap());
+        let max_threads = tracer.config().threads;
+
+        let vertex_flags = VertexFlag::POSITION
+            | VertexFlag::TEXTURE
+            | VertexFlag::NORMAL
+            | VertexFlag::TANGENT
+            | VertexFlag::BITANGENT;
+
+        let material = Material::builder("mesh.vert.spv", "mesh.frag.spv")
+            .vertex_flags(vertex_flags)
+            .prop("diffuse", MaterialProperty::Texture)
+            .build(ctx, graph.pass_by_type(PassType::Forward).unwrap());
+
+        App {
+            graph,
+            scene,
+            ui,
+            tracer,
+            max_threads,
+            material,
+            texture: None,
+            cursor: (0, 0),
+            selected: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context, delta: f32) {
+        // the settings changed in the panel are used by this update
+        let tracer = &mut self.tracer;
+        let max_threads = self.max_threads;
+        self.ui.update(
+            ctx,
+            self.graph.pass_by_type(PassType::Ui).unwrap(),
+            delta,
+            |ectx| raytracer_viewer::control_panel(ectx, tracer, max_threads),
+        );
+
+        // used by the pacing instead of the interval between the updates
+        self.tracer.frame_time(delta);
+
+        if self.tracer.update() {
+            let tiles = self.tracer.dirty_tiles();
+            let preview = self.tracer.preview();
+            let extent = self.tracer.extent();
+
+            match &self.texture {
+                Some(texture) => {
+                    for (x, y, width, height) in tiles {
+                        let colors = (y..y + height)
+                            .flat_map(|row| {
+                                let start = (x + row * extent.width) as usize;
+                                preview[start..start + width as usize].iter().copied()
+                            })
+                            .map(gobs_color)
+                            .collect::<Vec<_>>();
+
+                        texture.update_region(ctx, &colors, (x, y), (width, height));
+                    }
+                }
+                None => self.create_rect(ctx, &preview),
+            }
+        }
+
+        self.scene.update(ctx, delta);
+    }
+
+    fn render(&mut self, ctx: &Context) -> Result<(), RenderError> {
+        log::trace!("Render frame {}", self.graph.frame_number);
+
+        self.graph.begin(ctx)?;
+
+        self.graph.render(ctx, &mut |pass, batch| match pass.ty() {
+            PassType::Compute => {}
+            PassType::Depth => {
+                self.scene.draw(ctx, pass, batch);
+            }
+            PassType::Forward => {
+                self.scene.draw(ctx, pass, batch);
+            }
+            PassType::Wire => {}
+            PassType::Ui => {
+                self.ui.draw(ctx, pass, batch);
+            }
+        })?;
+
+        self.graph.end(ctx)?;
+
+        log::trace!("End render");
+
+        Ok(())
+    }
+
+    fn resize(&mut self, ctx: &Context, width: u32, height: u32) {
+        log::trace!("Resize");
+
+        self.graph.resize(ctx);
+        self.scene.resize(width, height);
+
+        // the quad and its texture are created again at the new size
+        self.tracer.resize(Extent::new(width, height));
+        self.texture = None;
+    }
+
+    fn input(&mut self, _ctx: &Context, input: Input) {
+        self.ui.input(input);
+
+        match input {
+            Input::KeyPressed(key) => match key {
+                Key::P => self.screenshot(),
+                Key::H => {
+                    self.tracer.save_exr("raytracer.exr").expect("Saving");
+                    self.tracer.save_hdr("raytracer.hdr").expect("Saving");
+                }
+                Key::V => self.tracer.cycle_preview_mode(),
+                Key::S => {
+                    let (x, y) = self.cursor;
+                    self.selected = self.tracer.pick(x, y).map(|name| name.to_string());
+                    match &self.selected {
+                        Some(name) => log::info!("Selected: {}", name),
+                        None => log::info!("Nothing selected"),
+                    }
+                }
+                // moves the selected sphere
+                Key::Left => self.move_selected(Vec3::new(-0.05, 0., 0.)),
+                Key::Right => self.move_selected(Vec3::new(0.05, 0., 0.)),
+                Key::Up => self.move_selected(Vec3::new(0., 0.05, 0.)),
+                Key::Down => self.move_selected(Vec3::new(0., -0.05, 0.)),
+                _ => (),
+            },
+            // refine the area under the cursor first
+            Input::CursorMoved(x, y) => {
+                self.cursor = (x as u32, y as u32);
+                self.tracer.set_focus(x as u32, y as u32);
+            }
+            _ => (),
+        }
+    }
+
+    async fn start(&mut self, _ctx: &Context) {}
+
+    fn close(&mut self, ctx: &Context) {
+        log::info!("Closing");
+
+        self.tracer.cancel();
+
+        ctx.device.wait();
+
+        log::info!("Closed");
+    }
+}
+
+impl App {
+    // raytracer_0001.png, raytracer_0002.png... with the render settings
+    fn screenshot(&self) {
+        self.tracer
+            .export(&ExportOptions::new().prefix("raytracer"))
+            .expect("Saving");
+    }
+
+    // the quad showing the image, with the texture kept for the next frames
+    fn create_rect(&mut self, ctx: &Context, preview: &[Color]) {
+        let framebuffer = preview.iter().copied().map(gobs_color).collect::<Vec<_>>();
+
+        let extent = self.tracer.extent();
+
+        let texture = Texture::with_colors(
+            ctx,
+            &framebuffer,
+            image_extent(extent),
+            TextureType::Diffuse,
+            SamplerFilter::FilterLinear,
+        );
+        self.texture = Some(texture.clone());
+
+        let material_instance = self.material.instantiate(vec![texture]);
+
+        let rect = Model::builder("rect")
+            .mesh(Shapes::quad(), material_instance)
+            .build();
+
+        let transform = Transform::new(
+            [0., 0., 0.].into(),
+            Quat::IDENTITY,
+            [extent.width as f32, extent.height as f32, 1.].into(),
+        );
+
+        let root = self.scene.graph.get(self.scene.graph.root).unwrap();
+
+        for child in root.children.clone() {
+            self.scene.graph.remove(child);
+        }
+
+        self.scene
+            .graph
+            .insert(self.scene.graph.root, NodeValue::Model(rect), transform);
+    }
+
+    fn move_selected(&mut self, delta: Vec3) {
+        if let Some(name) = &self.selected {
+            self.tracer.translate_model(name, delta);
+        }
+    }
+}
+
+fn main() {
+    raytracer::init_logger();
+
+    Application::new("Raytracer", 1920, 1080).run::<App>();
+}
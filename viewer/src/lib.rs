@@ -0,0 +1,195 @@
+// scene and control panel of the windowed demo, the window itself is in app/ and
+// depends on gobs which is not part of the workspace
+
+use std::{sync::Arc, time::Duration};
+
+use glam::Vec3;
+
+use raytracer::prelude::{
+    Backend, Camera, CheckerTexture, ChunkStrategy, Color, Extent, LightPower, Material,
+    PointLight, Ray, SamplerKind, Sphere, Tonemap, Tonemapper, Tracer, TracerBuilder,
+};
+
+// chunk strategies of the control panel
+const STRATEGIES: [(&str, ChunkStrategy); 4] = [
+    ("Random", ChunkStrategy::RANDOM { pixels: 4096 }),
+    ("Lines", ChunkStrategy::LINE { pixels: 4096 }),
+    (
+        "Boxes",
+        ChunkStrategy::BOX {
+            width: 128,
+            height: 128,
+        },
+    ),
+    (
+        "Spiral",
+        ChunkStrategy::SPIRAL {
+            width: 128,
+            height: 128,
+        },
+    ),
+];
+
+// spheres on a checkered ground, rendered at the size of the window
+pub async fn tracer(extent: Extent) -> Tracer {
+    let aspect = extent.width as f32 / extent.height as f32;
+
+    TracerBuilder::new(extent)
+        .await
+        .camera(Camera::perspective(
+            Vec3::new(0., 0.2, 0.),
+            aspect,
+            45_f32.to_radians(),
+            0.1,
+            100.,
+            (-90_f32).to_radians(),
+            0.,
+            Vec3::Y,
+        ))
+        .rays(10)
+        .target_samples(500)
+        .reflects(10)
+        .threads(8)
+        .low_priority()
+        .adaptive_threads(2, 8)
+        .frame_budget(Duration::from_secs_f32(1. / 60.))
+        .light(
+            PointLight::new(
+                Vec3::new(0., 2., -2.),
+                Color::WHITE,
+                LightPower::LUMENS(250.),
+            )
+            .radius(0.1),
+        )
+        .exposure(0.)
+        .model(Sphere::textured(
+            "ground",
+            Vec3::new(0., -5000.2, 0.),
+            5000.,
+            Arc::new(CheckerTexture::new(
+                Arc::new(Color::GREY),
+                Arc::new(Color::WHITE),
+                0.25,
+            )),
+            0.1,
+        ))
+        .movable_model(Sphere::new(
+            "black",
+            Vec3::new(0., 0.5, 1.2),
+            0.3,
+            Color::BLACK,
+            0.8,
+        ))
+        .movable_model(Sphere::new(
+            "green",
+            Vec3::new(-0.5, 0.2, 0.7),
+            0.3,
+            Color::GREEN,
+            0.4,
+        ))
+        .movable_model(Sphere::with_material(
+            "red",
+            Vec3::new(0.5, 0.2, 0.7),
+            0.3,
+            Material::new(Arc::new(Color::RED), 0.1).clearcoat(1., 1.5),
+        ))
+        .background(background_color)
+        // the spheres are traced in a compute shader with the gpu feature
+        .backend(if cfg!(feature = "gpu") {
+            Backend::GPU
+        } else {
+            Backend::CPU
+        })
+        .strategy(ChunkStrategy::SPIRAL {
+            width: 128,
+            height: 128,
+        })
+        .sampler(SamplerKind::BLUENOISE)
+        .tonemap(Tonemap::new(Tonemapper::ACES))
+        .build()
+        .await
+}
+
+fn background_color(ray: &Ray) -> Color {
+    let dot_x = ray.direction.dot(Vec3::X);
+    let dot_y = ray.direction.dot(Vec3::Y);
+
+    Color::new(0.2 * dot_x, 0.5 + 0.5 * dot_y, 1., 1.)
+}
+
+// the settings changed in the panel are used by the next Tracer::update
+pub fn control_panel(ectx: &egui::Context, tracer: &mut Tracer, max_threads: u32) {
+    egui::Window::new("Render").show(ectx, |ui| {
+        let progress = tracer.progress();
+        ui.add(
+            egui::ProgressBar::new(progress.ratio().unwrap_or(0.)).text(format!(
+                "{} spp, {:.1}s",
+                progress.samples_per_pixel, progress.elapsed
+            )),
+        );
+
+        let config = tracer.config();
+
+        let mut samples = config.target_samples.unwrap_or(0);
+        if ui
+            .add(
+                egui::Slider::new(&mut samples, 0..=4096)
+                    .logarithmic(true)
+                    .text("Samples per pixel (0: no limit)"),
+            )
+            .changed()
+        {
+            tracer.set_target_samples((samples > 0).then_some(samples));
+        }
+
+        let mut reflects = config.reflects;
+        if ui
+            .add(egui::Slider::new(&mut reflects, 1..=32).text("Bounces"))
+            .changed()
+        {
+            tracer.set_reflects(reflects);
+        }
+
+        let mut threads = config.threads;
+        if ui
+            .add(egui::Slider::new(&mut threads, 1..=max_threads).text("Threads"))
+            .changed()
+        {
+            tracer.set_threads(threads);
+        }
+
+        let current = STRATEGIES
+            .iter()
+            .find(|(_, strategy)| {
+                std::mem::discriminant(strategy) == std::mem::discriminant(&config.strategy)
+            })
+            .map_or("Custom", |(name, _)| *name);
+        egui::ComboBox::from_label("Chunks")
+            .selected_text(current)
+            .show_ui(ui, |ui| {
+                for (name, strategy) in STRATEGIES {
+                    if ui.selectable_label(name == current, name).clicked() {
+                        tracer.set_strategy(strategy);
+                    }
+                }
+            });
+
+        let tonemap = tracer.tonemap();
+
+        let mut ev100 = tracer.exposure();
+        if ui
+            .add(egui::Slider::new(&mut ev100, -5.0..=5.0).text("Exposure (EV100)"))
+            .changed()
+        {
+            tracer.set_exposure(ev100);
+        }
+
+        let mut kelvin = tonemap.white_balance.unwrap_or(6504.);
+        if ui
+            .add(egui::Slider::new(&mut kelvin, 2000.0..=12000.0).text("White balance (K)"))
+            .changed()
+        {
+            tracer.set_white_balance(kelvin);
+        }
+    });
+}